@@ -0,0 +1,277 @@
+//! Hawk authentication for actix-web.
+//!
+//! `HawkMiddleware` authenticates each request with its `Authorization` header or `bewit` query
+//! parameter, and signs the responses to header-authenticated requests with a
+//! `Server-Authorization` header.  Handlers get the authenticated client with the `HawkAuth`
+//! extractor.
+//!
+//! The request's host and port come from its `Host` header, with the configuration's default
+//! port if the header has none.  Behind a reverse proxy, use `ServerConfig::trusted_proxies` to
+//! take them from the `Forwarded` or `X-Forwarded-*` headers the proxy adds; the middleware
+//! supplies the peer address these are checked against.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::sync::Arc;
+//! use actix_hawk::{HawkAuth, HawkMiddleware};
+//! use actix_web::{web, App};
+//! use hawk::http::server::ServerConfig;
+//! use hawk::{Key, SHA256};
+//!
+//! async fn handler(HawkAuth(client): HawkAuth, body: web::Bytes) -> String {
+//!     format!("{} sent {} bytes", client.id, body.len())
+//! }
+//!
+//! let key = Arc::new(Key::new(vec![1u8; 32], &SHA256));
+//! let app = App::new()
+//!     .wrap(HawkMiddleware::new(
+//!         move |id: &str| if id == "me" { Some(key.clone()) } else { None },
+//!         ServerConfig::new(443),
+//!     ))
+//!     .route("/upload", web::post().to(handler));
+//! # let _ = app;
+//! ```
+
+use std::fmt;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::sync::Arc;
+
+use actix_web::body::{self, BoxBody, EitherBody, MessageBody};
+use actix_web::dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderName, HeaderValue, CONTENT_TYPE};
+use actix_web::http::StatusCode;
+use actix_web::web::Bytes;
+use actix_web::{Error, FromRequest, HttpMessage, HttpRequest, HttpResponse, ResponseError};
+use futures_util::future::LocalBoxFuture;
+use hawk::http::server::{authenticate, needs_body, KeyLookup, PeerAddr, Rejection, ServerConfig};
+use hawk::AuthFailure;
+
+pub use hawk::http::server::Authenticated;
+
+/// Middleware authenticating requests with Hawk.
+///
+/// Requests that fail authentication are answered with a 401 or 400 response carrying a
+/// `WWW-Authenticate` challenge, without calling the wrapped service.  When the
+/// `Authorization` header includes a payload hash, the request body is buffered (subject to
+/// actix's `PayloadConfig` limit) and verified.
+#[derive(Clone)]
+pub struct HawkMiddleware {
+    settings: Rc<Settings>,
+}
+
+struct Settings {
+    keys: Arc<dyn KeyLookup>,
+    config: ServerConfig,
+    hash_responses: bool,
+}
+
+impl HawkMiddleware {
+    /// Create a new middleware, looking up client keys with `keys`.
+    pub fn new<K>(keys: K, config: ServerConfig) -> Self
+    where
+        K: KeyLookup + 'static,
+    {
+        HawkMiddleware {
+            settings: Rc::new(Settings {
+                keys: Arc::new(keys),
+                config,
+                hash_responses: false,
+            }),
+        }
+    }
+
+    /// Set whether to include a hash of the response body in `Server-Authorization`.  This
+    /// requires buffering each response body.
+    pub fn hash_responses(self, hash_responses: bool) -> Self {
+        HawkMiddleware {
+            settings: Rc::new(Settings {
+                keys: self.settings.keys.clone(),
+                config: self.settings.config.clone(),
+                hash_responses,
+            }),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for HawkMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = HawkService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(HawkService {
+            service: Rc::new(service),
+            settings: self.settings.clone(),
+        }))
+    }
+}
+
+/// The service created by `HawkMiddleware`.
+pub struct HawkService<S> {
+    service: Rc<S>,
+    settings: Rc<Settings>,
+}
+
+impl<S, B> Service<ServiceRequest> for HawkService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let settings = self.settings.clone();
+        Box::pin(async move {
+            let parts = match http_parts(req.request()) {
+                Some(parts) => parts,
+                None => return Ok(reject(req, AuthFailure::InvalidHost.into())),
+            };
+
+            let body = if needs_body(&parts) {
+                let bytes = req.extract::<Bytes>().await?;
+                req.set_payload(Payload::from(bytes.clone()));
+                Some(bytes)
+            } else {
+                None
+            };
+
+            let verified =
+                match authenticate(&parts, body.as_deref(), &*settings.keys, &settings.config) {
+                    Ok(verified) => verified,
+                    Err(rejection) => return Ok(reject(req, rejection)),
+                };
+            req.extensions_mut().insert(verified.client().clone());
+
+            let res = service.call(req).await?;
+            let (mut res, header) = if settings.hash_responses && !verified.client().bewit {
+                let (req, res) = res.into_parts();
+                let (res, body) = res.into_parts();
+                let bytes = match body::to_bytes(body).await {
+                    Ok(bytes) => bytes,
+                    Err(_) => {
+                        let res = HttpResponse::InternalServerError().finish();
+                        return Ok(ServiceResponse::new(req, res).map_into_right_body());
+                    }
+                };
+                let content_type = res
+                    .headers()
+                    .get(CONTENT_TYPE)
+                    .and_then(|v| v.to_str().ok())
+                    .unwrap_or("");
+                let header = verified.server_authorization(Some((content_type, &bytes[..])));
+                let res = res.set_body(BoxBody::new(bytes));
+                (ServiceResponse::new(req, res).map_into_right_body(), header)
+            } else {
+                (
+                    res.map_into_left_body(),
+                    verified.server_authorization(None),
+                )
+            };
+
+            let value = header
+                .ok()
+                .and_then(|h| h)
+                .and_then(|h| HeaderValue::from_str(&format!("Hawk {}", h)).ok());
+            if let Some(value) = value {
+                res.headers_mut().insert(
+                    HeaderName::from_static(hawk::http::SERVER_AUTHORIZATION),
+                    value,
+                );
+            }
+            Ok(res)
+        })
+    }
+}
+
+/// Convert the head of an actix request into an `http` request, with the path and query as its
+/// URI and the headers as received, for authentication by `hawk::http::server`, which takes the
+/// host from the `Host` header.
+fn http_parts(req: &HttpRequest) -> Option<http::request::Parts> {
+    // use the request as received, rather than actix's connection info, which trusts forwarding
+    // headers from any peer
+    let path = req
+        .uri()
+        .path_and_query()
+        .map(|pq| pq.as_str())
+        .unwrap_or("/");
+
+    let mut builder = http::Request::builder()
+        .method(req.method().as_str())
+        .uri(path);
+    for (name, value) in req.headers() {
+        builder = builder.header(name.as_str(), value.as_bytes());
+    }
+    if let Some(peer) = req.peer_addr() {
+        builder = builder.extension(PeerAddr(peer.ip()));
+    }
+    builder.body(()).ok().map(|req| req.into_parts().0)
+}
+
+fn reject<B>(req: ServiceRequest, rejection: Rejection) -> ServiceResponse<EitherBody<B>> {
+    req.into_response(HawkError(rejection).error_response())
+        .map_into_right_body()
+}
+
+/// The error for a request that failed Hawk authentication, responding with the failure's
+/// status and, for 401, a `WWW-Authenticate` challenge.
+#[derive(Debug)]
+pub struct HawkError(pub Rejection);
+
+impl HawkError {
+    /// Get the reason the request was rejected.
+    pub fn failure(&self) -> AuthFailure {
+        self.0.failure()
+    }
+}
+
+impl fmt::Display for HawkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.failure())
+    }
+}
+
+impl ResponseError for HawkError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.0.failure().status_code()).unwrap_or(StatusCode::UNAUTHORIZED)
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut builder = HttpResponse::build(self.status_code());
+        for (name, value) in self.0.response(()).headers() {
+            builder.insert_header((name.as_str(), value.as_bytes()));
+        }
+        builder.body(self.to_string())
+    }
+}
+
+/// An extractor for the client authenticated by `HawkMiddleware`.
+///
+/// Extraction fails with a 401 response if the request was not authenticated, such as when the
+/// middleware is not installed.
+#[derive(Debug, Clone)]
+pub struct HawkAuth(pub Authenticated);
+
+impl FromRequest for HawkAuth {
+    type Error = HawkError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(match req.extensions().get::<Authenticated>() {
+            Some(client) => Ok(HawkAuth(client.clone())),
+            None => Err(HawkError(AuthFailure::MissingAuthentication.into())),
+        })
+    }
+}
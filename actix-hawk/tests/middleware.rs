@@ -0,0 +1,197 @@
+use std::sync::Arc;
+
+use actix_hawk::{HawkAuth, HawkMiddleware};
+use actix_web::{test, web, App, HttpResponse};
+use hawk::http::server::ServerConfig;
+use hawk::{Credentials, Header, Key, PayloadHasher, RequestBuilder, TrustedProxies, SHA256};
+
+fn credentials() -> Credentials {
+    Credentials {
+        id: "test-client".to_string(),
+        key: Key::new(vec![1u8; 32], &SHA256),
+    }
+}
+
+fn middleware() -> HawkMiddleware {
+    middleware_with(ServerConfig::new(80))
+}
+
+fn middleware_with(config: ServerConfig) -> HawkMiddleware {
+    let key = Arc::new(credentials().key);
+    HawkMiddleware::new(
+        move |id: &str| {
+            if id == "test-client" {
+                Some(key.clone())
+            } else {
+                None
+            }
+        },
+        config,
+    )
+}
+
+async fn handler(HawkAuth(client): HawkAuth, body: web::Bytes) -> HttpResponse {
+    HttpResponse::Ok().content_type("text/plain").body(format!(
+        "{}: {}",
+        client.id,
+        String::from_utf8_lossy(&body)
+    ))
+}
+
+fn server_authorization<B>(res: &actix_web::dev::ServiceResponse<B>) -> Option<Header> {
+    res.headers()
+        .get("server-authorization")
+        .map(|v| v.to_str().unwrap()[5..].parse().unwrap())
+}
+
+#[actix_web::test]
+async fn signed_post() {
+    let app = test::init_service(
+        App::new()
+            .wrap(middleware().hash_responses(true))
+            .route("/resource", web::post().to(handler)),
+    )
+    .await;
+
+    let hash = PayloadHasher::hash("text/plain", &SHA256, "foo=bar");
+    let request = RequestBuilder::new("POST", "example.com", 80, "/resource?x=1")
+        .hash(&hash[..])
+        .request();
+    let header = request.make_header(&credentials()).unwrap();
+    let req = test::TestRequest::post()
+        .uri("/resource?x=1")
+        .insert_header(("host", "example.com"))
+        .insert_header(("content-type", "text/plain"))
+        .insert_header(("authorization", format!("Hawk {}", header)))
+        .set_payload("foo=bar")
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 200);
+
+    let server_header = server_authorization(&res).unwrap();
+    let body = test::read_body(res).await;
+    assert_eq!(body, "test-client: foo=bar");
+    let response = request.make_response_builder(&header).response();
+    assert!(response.validate_header_with_payload(
+        &server_header,
+        &credentials().key,
+        "text/plain",
+        &body
+    ));
+}
+
+#[actix_web::test]
+async fn wrong_payload() {
+    let app = test::init_service(
+        App::new()
+            .wrap(middleware())
+            .route("/resource", web::post().to(handler)),
+    )
+    .await;
+
+    let hash = PayloadHasher::hash("text/plain", &SHA256, "foo=bar");
+    let header = RequestBuilder::new("POST", "example.com", 80, "/resource")
+        .hash(&hash[..])
+        .request()
+        .make_header(&credentials())
+        .unwrap();
+    let req = test::TestRequest::post()
+        .uri("/resource")
+        .insert_header(("host", "example.com"))
+        .insert_header(("content-type", "text/plain"))
+        .insert_header(("authorization", format!("Hawk {}", header)))
+        .set_payload("foo=baz")
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 401);
+    assert!(res
+        .headers()
+        .get("www-authenticate")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("Hawk"));
+}
+
+#[actix_web::test]
+async fn bewit() {
+    let app = test::init_service(
+        App::new()
+            .wrap(middleware())
+            .route("/resource", web::get().to(handler)),
+    )
+    .await;
+
+    let bewit = RequestBuilder::new("GET", "example.com", 80, "/resource")
+        .request()
+        .make_bewit(&credentials(), time::Duration::minutes(1))
+        .unwrap();
+    let req = test::TestRequest::get()
+        .uri(&format!("/resource?bewit={}", bewit.to_str()))
+        .insert_header(("host", "example.com"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 200);
+    assert!(server_authorization(&res).is_none());
+    assert_eq!(test::read_body(res).await, "test-client: ");
+}
+
+#[actix_web::test]
+async fn unauthenticated() {
+    let app = test::init_service(
+        App::new()
+            .wrap(middleware())
+            .route("/resource", web::get().to(handler)),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/resource")
+        .insert_header(("host", "example.com"))
+        .to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 401);
+    assert_eq!(res.headers().get("www-authenticate").unwrap(), "Hawk");
+}
+
+#[actix_web::test]
+async fn extractor_without_middleware() {
+    let app = test::init_service(App::new().route("/resource", web::get().to(handler))).await;
+    let req = test::TestRequest::get().uri("/resource").to_request();
+    let res = test::call_service(&app, req).await;
+    assert_eq!(res.status(), 401);
+}
+
+#[actix_web::test]
+async fn trusted_proxy() {
+    let proxy = "10.0.0.1".parse().unwrap();
+    let config = ServerConfig::new(8080).trusted_proxies(TrustedProxies::new(vec![proxy]));
+    let app = test::init_service(
+        App::new()
+            .wrap(middleware_with(config))
+            .route("/resource", web::get().to(handler)),
+    )
+    .await;
+
+    let header = RequestBuilder::new("GET", "api.example.com", 443, "/resource")
+        .request()
+        .make_header(&credentials())
+        .unwrap();
+    let request = |peer: &str| {
+        test::TestRequest::get()
+            .uri("/resource")
+            .peer_addr(format!("{}:4321", peer).parse().unwrap())
+            .insert_header(("host", "10.0.0.5:8080"))
+            .insert_header(("x-forwarded-host", "api.example.com"))
+            .insert_header(("x-forwarded-proto", "https"))
+            .insert_header(("authorization", format!("Hawk {}", header)))
+            .to_request()
+    };
+
+    let res = test::call_service(&app, request("10.0.0.1")).await;
+    assert_eq!(res.status(), 200);
+
+    // the forwarding headers are ignored from other peers
+    let res = test::call_service(&app, request("192.0.2.1")).await;
+    assert_eq!(res.status(), 401);
+}
@@ -0,0 +1,250 @@
+//! Hawk authentication for axum.
+//!
+//! The `HawkAuth` extractor authenticates a request with its `Authorization` header or `bewit`
+//! query parameter, looking up client keys through a `HawkState` provided by the router's
+//! state.  Requests that fail authentication are rejected with a 401 response carrying a
+//! `WWW-Authenticate` challenge.
+//!
+//! Handlers that take the request body should use the `HawkPayload` extractor instead, which
+//! reads the body, verifies any payload hash against it, and returns it with the client.  Bodies
+//! larger than the `HawkState`'s payload limit, `DEFAULT_PAYLOAD_LIMIT` unless set with
+//! `HawkState::payload_limit`, are rejected with a 413 response.
+//!
+//! Extractors other than the last cannot read the request body, so handlers that use `HawkAuth`
+//! with another body extractor, such as `Json`, need the `buffer_payload` middleware to verify
+//! payload hashes.  It buffers the bodies of requests whose `Authorization` header includes a
+//! hash, and leaves the body available to later extractors.  Bodies larger than
+//! `DEFAULT_PAYLOAD_LIMIT` are rejected with a 413 response; use `buffer_payload_with_limit` for
+//! a different limit.  Without the middleware, requests whose header includes a hash are
+//! rejected by `HawkAuth` with a 500 response, as the hash cannot be verified.
+//!
+//! Behind a reverse proxy, use `ServerConfig::trusted_proxies` to take the host and port from
+//! the `Forwarded` or `X-Forwarded-*` headers the proxy adds.  The peer address is taken from a
+//! `PeerAddr` request extension or, with the `connect-info` feature, from axum's
+//! `ConnectInfo<SocketAddr>`, so serve the router with
+//! `into_make_service_with_connect_info::<SocketAddr>()`.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::sync::Arc;
+//! use axum::{routing::post, Router};
+//! use axum_hawk::{HawkPayload, HawkState};
+//! use hawk::http::server::ServerConfig;
+//! use hawk::{Key, SHA256};
+//!
+//! async fn handler(HawkPayload(client, body): HawkPayload) -> String {
+//!     format!("{} sent {} bytes", client.id, body.len())
+//! }
+//!
+//! let key = Arc::new(Key::new(vec![1u8; 32], &SHA256));
+//! let state = HawkState::new(
+//!     move |id: &str| if id == "me" { Some(key.clone()) } else { None },
+//!     ServerConfig::new(443),
+//! );
+//! let app: Router = Router::new()
+//!     .route("/upload", post(handler))
+//!     .with_state(state);
+//! # let _ = app;
+//! ```
+
+#[cfg(feature = "connect-info")]
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::body::Body;
+#[cfg(feature = "connect-info")]
+use axum::extract::ConnectInfo;
+use axum::extract::{FromRef, FromRequest, FromRequestParts, Request, State};
+use axum::http::request::Parts;
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use bytes::Bytes;
+use hawk::http::server::{authenticate, needs_body, KeyLookup, Rejection, ServerConfig};
+use hawk::AuthFailure;
+use http_body_util::{BodyExt, LengthLimitError, Limited};
+
+pub use hawk::http::server::{Authenticated, PeerAddr, DEFAULT_PAYLOAD_LIMIT};
+
+/// The key lookup and configuration used by `HawkAuth` and `HawkPayload`.
+///
+/// These find this in the router's state with `FromRef`, so it can be the state itself
+/// or a field of an application's state struct.
+#[derive(Clone)]
+pub struct HawkState {
+    keys: Arc<dyn KeyLookup>,
+    config: ServerConfig,
+    payload_limit: usize,
+}
+
+impl HawkState {
+    /// Create a new state, looking up client keys with `keys`.
+    pub fn new<K>(keys: K, config: ServerConfig) -> Self
+    where
+        K: KeyLookup + 'static,
+    {
+        HawkState {
+            keys: Arc::new(keys),
+            config,
+            payload_limit: DEFAULT_PAYLOAD_LIMIT,
+        }
+    }
+
+    /// Set the limit, in bytes, on the size of request bodies read by `HawkPayload`.  This
+    /// defaults to `DEFAULT_PAYLOAD_LIMIT`.
+    pub fn payload_limit(mut self, payload_limit: usize) -> Self {
+        self.payload_limit = payload_limit;
+        self
+    }
+}
+
+/// An extractor for the Hawk-authenticated client making a request.
+#[derive(Debug, Clone)]
+pub struct HawkAuth(pub Authenticated);
+
+/// An extractor for the Hawk-authenticated client making a request, and the request body,
+/// against which any payload hash has been verified.  This must be the handler's last
+/// extractor, as it reads the body.
+#[derive(Debug, Clone)]
+pub struct HawkPayload(pub Authenticated, pub Bytes);
+
+/// A request body buffered by `buffer_payload`, for verification of its payload hash.
+#[derive(Clone)]
+struct BufferedPayload(Bytes);
+
+impl<S> FromRequestParts<S> for HawkAuth
+where
+    HawkState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = HawkRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        // the request may already have been authenticated, if it is extracted more than once
+        if let Some(client) = parts.extensions.get::<Authenticated>() {
+            return Ok(HawkAuth(client.clone()));
+        }
+
+        #[cfg(feature = "connect-info")]
+        if parts.extensions.get::<PeerAddr>().is_none() {
+            if let Some(ConnectInfo(addr)) = parts.extensions.get::<ConnectInfo<SocketAddr>>() {
+                let peer = PeerAddr(addr.ip());
+                parts.extensions.insert(peer);
+            }
+        }
+
+        let state = HawkState::from_ref(state);
+        let body = parts
+            .extensions
+            .get::<BufferedPayload>()
+            .map(|payload| payload.0.clone());
+        if body.is_none() && needs_body(parts) {
+            return Err(HawkRejection::PayloadNotBuffered);
+        }
+        let verified = authenticate(parts, body.as_deref(), &*state.keys, &state.config)
+            .map_err(HawkRejection::Unauthenticated)?;
+        let client = verified.into_client();
+        parts.extensions.insert(client.clone());
+        Ok(HawkAuth(client))
+    }
+}
+
+impl<S> FromRequest<S> for HawkPayload
+where
+    HawkState: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = HawkRejection;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let (mut parts, body) = req.into_parts();
+        let limit = HawkState::from_ref(state).payload_limit;
+        let bytes = match Limited::new(body, limit).collect().await {
+            Ok(collected) => collected.to_bytes(),
+            Err(e) if e.is::<LengthLimitError>() => return Err(HawkRejection::PayloadTooLarge),
+            Err(_) => return Err(HawkRejection::InvalidPayload),
+        };
+        parts.extensions.insert(BufferedPayload(bytes.clone()));
+        let HawkAuth(client) = HawkAuth::from_request_parts(&mut parts, state).await?;
+        Ok(HawkPayload(client, bytes))
+    }
+}
+
+/// The rejection from `HawkAuth` and `HawkPayload`.
+#[derive(Debug)]
+pub enum HawkRejection {
+    /// The request failed authentication.  This responds with the failure's status and, for
+    /// 401, a `WWW-Authenticate` challenge.
+    Unauthenticated(Rejection),
+    /// The request's `Authorization` header includes a payload hash, but `buffer_payload` did not
+    /// buffer its body, so the hash cannot be verified.  This is a server misconfiguration, and
+    /// responds with 500.
+    PayloadNotBuffered,
+    /// The request body is larger than the `HawkState`'s payload limit.  This responds with
+    /// 413.
+    PayloadTooLarge,
+    /// The request body could not be read.  This responds with 400.
+    InvalidPayload,
+}
+
+impl HawkRejection {
+    /// Get the reason the request failed authentication, if it did.
+    pub fn failure(&self) -> Option<AuthFailure> {
+        match self {
+            HawkRejection::Unauthenticated(rejection) => Some(rejection.failure()),
+            _ => None,
+        }
+    }
+}
+
+impl IntoResponse for HawkRejection {
+    fn into_response(self) -> Response {
+        match self {
+            HawkRejection::Unauthenticated(rejection) => {
+                let message = rejection.failure().to_string();
+                rejection.response(Body::from(message))
+            }
+            HawkRejection::PayloadNotBuffered => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Request body was not buffered to verify its payload hash",
+            )
+                .into_response(),
+            HawkRejection::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+            HawkRejection::InvalidPayload => StatusCode::BAD_REQUEST.into_response(),
+        }
+    }
+}
+
+/// Middleware buffering the bodies of requests whose `Authorization` header includes a payload
+/// hash, so that `HawkAuth` can verify it.  Use with `axum::middleware::from_fn`.
+///
+/// Bodies larger than `DEFAULT_PAYLOAD_LIMIT` are rejected with a 413 response.
+pub async fn buffer_payload(req: Request, next: Next) -> Response {
+    buffer_payload_with_limit(State(DEFAULT_PAYLOAD_LIMIT), req, next).await
+}
+
+/// Middleware like `buffer_payload`, rejecting bodies larger than the given limit, in bytes,
+/// with a 413 response.  Use with `axum::middleware::from_fn_with_state`, passing the limit as
+/// the state.
+pub async fn buffer_payload_with_limit(
+    State(limit): State<usize>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let (mut parts, body) = req.into_parts();
+    if !needs_body(&parts) {
+        return next.run(Request::from_parts(parts, body)).await;
+    }
+
+    let bytes = match Limited::new(body, limit).collect().await {
+        Ok(collected) => collected.to_bytes(),
+        Err(e) if e.is::<LengthLimitError>() => {
+            return StatusCode::PAYLOAD_TOO_LARGE.into_response()
+        }
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    parts.extensions.insert(BufferedPayload(bytes.clone()));
+    next.run(Request::from_parts(parts, Body::from(bytes)))
+        .await
+}
@@ -0,0 +1,225 @@
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::routing::{get, post};
+use axum::{middleware, Router};
+use axum_hawk::{
+    buffer_payload, buffer_payload_with_limit, HawkAuth, HawkPayload, HawkState, PeerAddr,
+};
+use bytes::Bytes;
+use hawk::http::server::ServerConfig;
+use hawk::{Credentials, Key, PayloadHasher, RequestBuilder, TrustedProxies, SHA256};
+use http_body_util::BodyExt;
+use tower::ServiceExt;
+
+fn credentials() -> Credentials {
+    Credentials {
+        id: "test-client".to_string(),
+        key: Key::new(vec![1u8; 32], &SHA256),
+    }
+}
+
+async fn handler(HawkAuth(client): HawkAuth, body: Bytes) -> String {
+    format!("{}: {}", client.id, String::from_utf8_lossy(&body))
+}
+
+async fn payload_handler(HawkPayload(client, body): HawkPayload) -> String {
+    format!("{}: {}", client.id, String::from_utf8_lossy(&body))
+}
+
+fn app() -> Router {
+    app_with(ServerConfig::new(80))
+}
+
+fn app_with(config: ServerConfig) -> Router {
+    routes()
+        .layer(middleware::from_fn(buffer_payload))
+        .with_state(state(config))
+}
+
+fn state(config: ServerConfig) -> HawkState {
+    let key = Arc::new(credentials().key);
+    HawkState::new(
+        move |id: &str| {
+            if id == "test-client" {
+                Some(key.clone())
+            } else {
+                None
+            }
+        },
+        config,
+    )
+}
+
+fn routes() -> Router<HawkState> {
+    Router::new()
+        .route("/resource", get(handler))
+        .route("/resource", post(handler))
+}
+
+/// Make a POST request with the given body, signed with a hash of `signed_body`.
+fn post_request(body: &'static str, signed_body: &str) -> Request<Body> {
+    let hash = PayloadHasher::hash("text/plain", &SHA256, signed_body);
+    let header = RequestBuilder::new("POST", "example.com", 80, "/resource")
+        .hash(&hash[..])
+        .request()
+        .make_header(&credentials())
+        .unwrap();
+    let mut req = Request::post("/resource")
+        .header("host", "example.com")
+        .header("content-type", "text/plain")
+        .body(Body::from(body))
+        .unwrap();
+    hawk::http::set_authorization(&mut req, &header).unwrap();
+    req
+}
+
+async fn send(req: Request<Body>) -> (StatusCode, axum::http::HeaderMap, String) {
+    let res = app().oneshot(req).await.unwrap();
+    let (parts, body) = res.into_parts();
+    let body = body.collect().await.unwrap().to_bytes();
+    (
+        parts.status,
+        parts.headers,
+        String::from_utf8(body.to_vec()).unwrap(),
+    )
+}
+
+#[tokio::test]
+async fn header_with_payload() {
+    let (status, _, body) = send(post_request("foo=bar", "foo=bar")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "test-client: foo=bar");
+}
+
+#[tokio::test]
+async fn header_with_wrong_payload() {
+    let (status, headers, body) = send(post_request("foo=baz", "foo=bar")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(headers["www-authenticate"]
+        .to_str()
+        .unwrap()
+        .starts_with("Hawk"));
+    assert_eq!(body, "Bad payload hash");
+}
+
+#[tokio::test]
+async fn payload_not_buffered() {
+    let app = routes().with_state(state(ServerConfig::new(80)));
+    let res = app
+        .oneshot(post_request("foo=bar", "foo=bar"))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+#[tokio::test]
+async fn payload_too_large() {
+    let app = routes()
+        .layer(middleware::from_fn_with_state(4, buffer_payload_with_limit))
+        .with_state(state(ServerConfig::new(80)));
+    let res = app
+        .oneshot(post_request("foo=bar", "foo=bar"))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+/// An app using `HawkPayload`, without `buffer_payload`.
+fn payload_app(state: HawkState) -> Router {
+    Router::new()
+        .route("/resource", post(payload_handler))
+        .with_state(state)
+}
+
+#[tokio::test]
+async fn extracted_payload() {
+    let res = payload_app(state(ServerConfig::new(80)))
+        .oneshot(post_request("foo=bar", "foo=bar"))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(body, "test-client: foo=bar");
+}
+
+#[tokio::test]
+async fn extracted_wrong_payload() {
+    let res = payload_app(state(ServerConfig::new(80)))
+        .oneshot(post_request("foo=baz", "foo=bar"))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn extracted_payload_too_large() {
+    let res = payload_app(state(ServerConfig::new(80)).payload_limit(4))
+        .oneshot(post_request("foo=bar", "foo=bar"))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn bewit() {
+    let bewit = RequestBuilder::new("GET", "example.com", 80, "/resource")
+        .request()
+        .make_bewit(&credentials(), time::Duration::minutes(1))
+        .unwrap();
+    let req = Request::get(format!("/resource?bewit={}", bewit.to_str()))
+        .header("host", "example.com")
+        .body(Body::empty())
+        .unwrap();
+    let (status, _, body) = send(req).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "test-client: ");
+}
+
+#[tokio::test]
+async fn unauthenticated() {
+    let req = Request::get("/resource")
+        .header("host", "example.com")
+        .body(Body::empty())
+        .unwrap();
+    let (status, headers, _) = send(req).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(headers["www-authenticate"], "Hawk");
+}
+
+#[tokio::test]
+async fn trusted_proxy() {
+    let proxy = "10.0.0.1".parse().unwrap();
+    let config = ServerConfig::new(8080).trusted_proxies(TrustedProxies::new(vec![proxy]));
+    let header = RequestBuilder::new("GET", "api.example.com", 443, "/resource")
+        .request()
+        .make_header(&credentials())
+        .unwrap();
+    let request = |peer: &str| {
+        let mut req = Request::get("/resource")
+            .header("host", "10.0.0.5:8080")
+            .header(
+                "forwarded",
+                "for=192.0.2.1;host=api.example.com;proto=https",
+            )
+            .body(Body::empty())
+            .unwrap();
+        hawk::http::set_authorization(&mut req, &header).unwrap();
+        req.extensions_mut().insert(PeerAddr(peer.parse().unwrap()));
+        req
+    };
+
+    let res = app_with(config.clone())
+        .oneshot(request("10.0.0.1"))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // the forwarding headers are ignored from other peers
+    let res = app_with(config)
+        .oneshot(request("192.0.2.1"))
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
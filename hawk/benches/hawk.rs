@@ -0,0 +1,78 @@
+#[macro_use]
+extern crate criterion;
+extern crate hawk;
+extern crate time;
+
+use std::str::FromStr;
+use criterion::Criterion;
+use hawk::{Credentials, Header, Key, RequestBuilder, SHA256};
+use hawk::mac::{Mac, MacType};
+
+fn credentials() -> Credentials {
+    Credentials {
+        id: "dh37fgj492je".to_string(),
+        key: Key::new(vec![99u8; 32], &SHA256),
+    }
+}
+
+fn mac(c: &mut Criterion) {
+    let key = credentials().key;
+    let hash = vec![1u8; 32];
+    c.bench_function("Mac::new", move |b| {
+        b.iter(|| {
+            Mac::new(MacType::Header,
+                     &key,
+                     time::Timespec::new(1353832234, 0),
+                     "j4h3g2",
+                     "POST",
+                     "example.com",
+                     443,
+                     "/v1/users?limit=10",
+                     Some(&hash),
+                     Some("some-app-ext-data"))
+                .unwrap()
+        })
+    });
+}
+
+fn make_header(c: &mut Criterion) {
+    let credentials = credentials();
+    let hash = vec![1u8; 32];
+    c.bench_function("Request::make_header", move |b| {
+        let request = RequestBuilder::new("POST", "example.com", 443, "/v1/users")
+            .hash(&hash[..])
+            .ext("some-app-ext-data")
+            .request();
+        b.iter(|| request.make_header(&credentials).unwrap())
+    });
+}
+
+fn parse_header(c: &mut Criterion) {
+    let credentials = credentials();
+    let request = RequestBuilder::new("POST", "example.com", 443, "/v1/users")
+        .hash(&[1u8; 32][..])
+        .ext("some-app-ext-data")
+        .request();
+    let header = format!("{}", request.make_header(&credentials).unwrap());
+    c.bench_function("Header::from_str", move |b| {
+        b.iter(|| Header::from_str(&header).unwrap())
+    });
+}
+
+fn validate_header(c: &mut Criterion) {
+    let credentials = credentials();
+    let hash = vec![1u8; 32];
+    c.bench_function("Request::validate_header", move |b| {
+        let request = RequestBuilder::new("POST", "example.com", 443, "/v1/users")
+            .hash(&hash[..])
+            .ext("some-app-ext-data")
+            .request();
+        let header = request.make_header(&credentials).unwrap();
+        b.iter(|| {
+            assert!(request.validate_header(&header, &credentials.key, time::Duration::minutes(1)))
+        })
+    });
+}
+
+criterion_group!(benches, mac, make_header, parse_header, validate_header);
+criterion_main!(benches);
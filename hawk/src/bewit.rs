@@ -1,10 +1,17 @@
 use base64;
 use mac::Mac;
 use error::*;
+use failure::AuthFailure;
+use std::fmt;
 use std::str;
 use std::str::FromStr;
-use time::Timespec;
+use std::sync::Arc;
+use time::{self, Duration, Timespec};
 use std::borrow::Cow;
+#[cfg(feature = "serde")]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "serde")]
+use ext;
 
 /// A Bewit is a piece of data attached to a GET request that functions in place of a Hawk
 /// Authentication header.  It contains an id, a timestamp, a MAC, and an optional `ext` value.
@@ -21,18 +28,31 @@ impl<'a> Bewit<'a> {
     /// Create a new Bewit with the given values.
     ///
     /// See Request.make_bewit for an easier way to make a Bewit
-    pub fn new(id: &'a str, exp: Timespec, mac: Mac, ext: Option<&'a str>) -> Bewit<'a> {
+    pub fn new<I, E>(id: I, exp: Timespec, mac: Mac, ext: Option<E>) -> Bewit<'a>
+        where I: Into<Cow<'a, str>>,
+              E: Into<Cow<'a, str>>
+    {
         Bewit {
-            id: Cow::Borrowed(id),
+            id: id.into(),
             exp: exp,
             mac: Cow::Owned(mac),
             ext: match ext {
-                Some(s) => Some(Cow::Borrowed(s)),
+                Some(s) => Some(s.into()),
                 None => None,
             },
         }
     }
 
+    /// Convert this bewit into one that owns all of its fields, copying any that are borrowed.
+    pub fn into_owned(self) -> Bewit<'static> {
+        Bewit {
+            id: Cow::Owned(self.id.into_owned()),
+            exp: self.exp,
+            mac: Cow::Owned(self.mac.into_owned()),
+            ext: self.ext.map(|v| Cow::Owned(v.into_owned())),
+        }
+    }
+
     /// Generate the fully-encoded string for this Bewit
     pub fn to_str(&self) -> String {
         let raw = format!("{}\\{}\\{}\\{}",
@@ -69,6 +89,50 @@ impl<'a> Bewit<'a> {
             None => None,
         }
     }
+
+    /// Decode the Bewit's `ext` field as base64 JSON, as described in the `ext` module, skipping
+    /// any `HeaderBinding`.  Returns `None` if the Bewit has no `ext`, or only a binding.
+    #[cfg(feature = "serde")]
+    pub fn ext_json<T: DeserializeOwned>(&self) -> Result<Option<T>> {
+        match self.ext() {
+            Some(value) => ext::decode_app(value),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Bewit<'static> {
+    /// Find and parse the `bewit` query parameter in the given resource (path and query).
+    ///
+    /// The bewit's MAC is calculated over the resource without this parameter, so that is
+    /// returned alongside the bewit, for use as the path of the request to validate.  The JS
+    /// implementation's rules are followed: if the parameter appears more than once, the last
+    /// occurrence is used.  Returns `Ok(None)` if there is no `bewit` parameter.
+    pub fn from_resource(resource: &str) -> Result<Option<(Bewit<'static>, String)>> {
+        let start = resource.match_indices("bewit=")
+            .map(|(i, _)| i)
+            .filter(|&i| i > 0 && (&resource[i - 1..i] == "?" || &resource[i - 1..i] == "&"))
+            .last();
+        let start = match start {
+            Some(start) => start,
+            None => return Ok(None),
+        };
+
+        let value_start = start + "bewit=".len();
+        let (value, rest) = match resource[value_start..].find('&') {
+            Some(i) => (&resource[value_start..value_start + i], &resource[value_start + i + 1..]),
+            None => (&resource[value_start..], ""),
+        };
+
+        let bewit = Bewit::from_str(value)?;
+        // remove the separator before the parameter, restoring it if anything follows
+        let mut stripped = resource[..start - 1].to_string();
+        if !rest.is_empty() {
+            stripped.push_str(&resource[start - 1..start]);
+            stripped.push_str(rest);
+        }
+        Ok(Some((bewit, stripped)))
+    }
 }
 
 const BACKSLASH: u8 = '\\' as u8;
@@ -76,27 +140,33 @@ const BACKSLASH: u8 = '\\' as u8;
 impl<'a> FromStr for Bewit<'a> {
     type Err = Error;
     fn from_str(bewit: &str) -> Result<Bewit<'a>> {
-        let bewit = base64::decode(bewit).chain_err(|| "Error decoding bewit base64")?;
+        let bewit = base64::decode_config(bewit, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| Error::BewitEncoding)?;
 
         let parts: Vec<&[u8]> = bewit.split(|c| *c == BACKSLASH).collect();
         if parts.len() != 4 {
-            bail!("Invalid bewit format");
+            return Err(Error::BewitFormat);
         }
 
-        let id = String::from_utf8(parts[0].to_vec()).chain_err(|| "Invalid bewit id")?;
+        let invalid = Error::InvalidBewitComponent;
+
+        let id = String::from_utf8(parts[0].to_vec()).map_err(|_| invalid(BewitComponent::Id))?;
 
-        let exp = str::from_utf8(parts[1]).chain_err(|| "Invalid bewit exp")?;
-        let exp = i64::from_str(&exp).chain_err(|| "Invalid bewit exp")?;
+        let exp = str::from_utf8(parts[1]).map_err(|_| invalid(BewitComponent::Exp))?;
+        let exp = i64::from_str(&exp).map_err(|_| invalid(BewitComponent::Exp))?;
         let exp = Timespec::new(exp, 0);
 
-        let mac = str::from_utf8(parts[2]).chain_err(|| "Invalid bewit mac")?;
-        let mac = Mac::from(base64::decode(mac).chain_err(|| "Invalid bewit mac")?);
+        let mac = str::from_utf8(parts[2]).map_err(|_| invalid(BewitComponent::Mac))?;
+        let mac = base64::decode(mac)
+            .ok()
+            .and_then(|v| Mac::from_slice(&v))
+            .ok_or(invalid(BewitComponent::Mac))?;
 
         let ext = match parts[3].len() {
             0 => None,
             _ => {
                 Some(Cow::Owned(String::from_utf8(parts[3].to_vec())
-                                    .chain_err(|| "Invalid bew,it ext")?))
+                                    .map_err(|_| invalid(BewitComponent::Ext))?))
             }
         };
 
@@ -109,6 +179,83 @@ impl<'a> FromStr for Bewit<'a> {
     }
 }
 
+/// A check for bewits that have been revoked, such as links that were shared by mistake.
+///
+/// Implementations can consider the bewit's `id`, `mac` or `ext`; the MAC identifies a single
+/// bewit, while an `ext` value can identify a group of them.  This is implemented for closures,
+/// so `|bewit: &Bewit| revoked.contains(bewit.mac())` works where `revoked` is a set of MACs.
+pub trait BewitRevocation: Send + Sync {
+    /// Return true if the given bewit has been revoked.
+    fn is_revoked(&self, bewit: &Bewit) -> bool;
+}
+
+impl<F> BewitRevocation for F
+    where F: Fn(&Bewit) -> bool + Send + Sync
+{
+    fn is_revoked(&self, bewit: &Bewit) -> bool {
+        self(bewit)
+    }
+}
+
+/// A server's policy for the bewits it accepts, beyond the checks made by
+/// `Request::check_bewit`: a maximum lifetime and a revocation check.
+///
+/// The policy is checked with `check` after the bewit's MAC has been validated, so the bewit is
+/// known to have been made with the client's key.  By default, there is no limit on lifetime
+/// and nothing is revoked.
+#[derive(Clone, Default)]
+pub struct BewitPolicy {
+    max_ttl: Option<Duration>,
+    revocation: Option<Arc<dyn BewitRevocation>>,
+}
+
+impl BewitPolicy {
+    /// Create a new policy accepting all bewits.
+    pub fn new() -> Self {
+        BewitPolicy::default()
+    }
+
+    /// Reject bewits expiring more than `max_ttl` from now.  This limits the damage from a
+    /// leaked bewit whose creator chose a long lifetime.  Bewits are made using the client's
+    /// clock, so allow for some skew.
+    pub fn max_ttl(mut self, max_ttl: Duration) -> Self {
+        self.max_ttl = Some(max_ttl);
+        self
+    }
+
+    /// Reject bewits for which the given check reports revocation.
+    pub fn revocation<R>(mut self, revocation: R) -> Self
+        where R: BewitRevocation + 'static
+    {
+        self.revocation = Some(Arc::new(revocation));
+        self
+    }
+
+    /// Check the given bewit, which has already been validated, against this policy.
+    pub fn check(&self, bewit: &Bewit) -> ::std::result::Result<(), AuthFailure> {
+        if let Some(max_ttl) = self.max_ttl {
+            if bewit.exp() > time::now().to_timespec() + max_ttl {
+                return Err(AuthFailure::BewitLifetimeTooLong);
+            }
+        }
+        if let Some(ref revocation) = self.revocation {
+            if revocation.is_revoked(bewit) {
+                return Err(AuthFailure::BewitRevoked);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for BewitPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BewitPolicy")
+            .field("max_ttl", &self.max_ttl)
+            .field("revocation", &self.revocation.is_some())
+            .finish()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -137,7 +284,7 @@ mod test {
 
     #[test]
     fn test_to_str() {
-        let bewit = Bewit::new("me", Timespec::new(1353832834, 0), make_mac(), None);
+        let bewit = Bewit::new("me", Timespec::new(1353832834, 0), make_mac(), None::<&str>);
         assert_eq!(bewit.to_str(),
                    "bWVcMTM1MzgzMjgzNFxmaXk0ZTV3QmRhcEROeEhIZUExOE5yU3JVMVUzaVM2NmdtMFhqVEpwWXlVPVw");
         let bewit = Bewit::new("me", Timespec::new(1353832834, 0), make_mac(), Some("abcd"));
@@ -145,6 +292,33 @@ mod test {
                    "bWVcMTM1MzgzMjgzNFxmaXk0ZTV3QmRhcEROeEhIZUExOE5yU3JVMVUzaVM2NmdtMFhqVEpwWXlVPVxhYmNk");
     }
 
+    #[test]
+    fn test_new_mixed_types() {
+        let bewit = Bewit::new("me".to_string(),
+                               Timespec::new(1353832834, 0),
+                               make_mac(),
+                               Some("abcd"));
+        assert_eq!(bewit.id(), "me");
+        assert_eq!(bewit.ext(), Some("abcd"));
+    }
+
+    #[test]
+    fn test_into_owned() {
+        fn make_bewit() -> Bewit<'static> {
+            let id = "me".to_string();
+            let ext = "abcd".to_string();
+            Bewit::new(&id[..], Timespec::new(1353832834, 0), make_mac(), Some(&ext[..]))
+                .into_owned()
+        }
+
+        fn is_send_sync_static<T: Send + Sync + 'static>(_: &T) {}
+
+        let bewit = make_bewit();
+        is_send_sync_static(&bewit);
+        assert_eq!(bewit.id(), "me");
+        assert_eq!(bewit.ext(), Some("abcd"));
+    }
+
     #[test]
     fn test_accessors() {
         let bewit = Bewit::from_str("bWVcMTM1MzgzMjgzNFxmaXk0ZTV3QmRhcEROeEhIZUExOE5yU3JVMVUzaVM2NmdtMFhqVEpwWXlVPVw").unwrap();
@@ -156,18 +330,25 @@ mod test {
 
     #[test]
     fn test_from_str_invalid_base64() {
-        assert!(Bewit::from_str("!/==").is_err());
+        match Bewit::from_str("!/==") {
+            Err(Error::BewitEncoding) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
     }
 
     #[test]
     fn test_from_str_invalid_too_many_parts() {
-        let bewit = base64::encode(&"a\\123\\abc\\ext\\WHUT?".as_bytes());
-        assert!(Bewit::from_str(&bewit).is_err());
+        let bewit = base64::encode_config(&"a\\123\\abc\\ext\\WHUT?".as_bytes(),
+                                          base64::URL_SAFE_NO_PAD);
+        match Bewit::from_str(&bewit) {
+            Err(Error::BewitFormat) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
     }
 
     #[test]
     fn test_from_str_invalid_too_few_parts() {
-        let bewit = base64::encode(&"a\\123\\abc".as_bytes());
+        let bewit = base64::encode_config(&"a\\123\\abc".as_bytes(), base64::URL_SAFE_NO_PAD);
         assert!(Bewit::from_str(&bewit).is_err());
     }
 
@@ -178,13 +359,113 @@ mod test {
         let slash = '\\' as u8;
         let invalid1 = 0u8;
         let invalid2 = 159u8;
-        let bewit = base64::encode(&[invalid1, invalid2, slash, one, slash, a, slash, a]);
-        assert!(Bewit::from_str(&bewit).is_err());
-        let bewit = base64::encode(&[a, slash, invalid1, invalid2, slash, a, slash, a]);
-        assert!(Bewit::from_str(&bewit).is_err());
-        let bewit = base64::encode(&[a, slash, one, slash, invalid1, invalid2, slash, a]);
-        assert!(Bewit::from_str(&bewit).is_err());
-        let bewit = base64::encode(&[a, slash, one, slash, a, slash, invalid1, invalid2]);
-        assert!(Bewit::from_str(&bewit).is_err());
+        let check = |bytes: &[u8], expected: BewitComponent| {
+            match Bewit::from_str(&base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)) {
+                Err(Error::InvalidBewitComponent(c)) => assert_eq!(c, expected),
+                r => panic!("unexpected result {:?}", r),
+            }
+        };
+        check(&[invalid1, invalid2, slash, one, slash, a, slash, a],
+              BewitComponent::Id);
+        check(&[a, slash, invalid1, invalid2, slash, a, slash, a],
+              BewitComponent::Exp);
+        check(&[a, slash, one, slash, invalid1, invalid2, slash, a],
+              BewitComponent::Mac);
+        check(&[a, slash, one, slash, a, a, a, a, slash, invalid1, invalid2],
+              BewitComponent::Ext);
+    }
+
+    #[test]
+    fn test_from_str_url_safe() {
+        // some of these encode with the URL-safe characters `-` and `_`
+        let mut url_safe = false;
+        for ext in &["?>?", "a?>?", "aa?>?"] {
+            let bewit = Bewit::new("me", Timespec::new(1353832834, 0), make_mac(), Some(*ext));
+            let encoded = bewit.to_str();
+            url_safe = url_safe || encoded.contains('_') || encoded.contains('-');
+            let decoded = Bewit::from_str(&encoded).unwrap();
+            assert_eq!(decoded.ext(), Some(*ext));
+            assert_eq!(decoded.mac(), &make_mac());
+        }
+        assert!(url_safe);
+    }
+
+    #[test]
+    fn test_from_str_padding() {
+        // bewits are sent unpadded, but padded bewits are accepted, too
+        let mut padded = false;
+        for ext in &["a", "aa", "aaa"] {
+            let bewit = Bewit::new("me", Timespec::new(1353832834, 0), make_mac(), Some(*ext));
+            let unpadded = bewit.to_str();
+            assert!(!unpadded.ends_with('='));
+            let raw = base64::decode_config(&unpadded, base64::URL_SAFE_NO_PAD).unwrap();
+            let with_padding = base64::encode_config(&raw, base64::URL_SAFE);
+            padded = padded || with_padding.ends_with('=');
+            for encoded in &[unpadded, with_padding] {
+                let decoded = Bewit::from_str(encoded).unwrap();
+                assert_eq!(decoded.id(), "me");
+                assert_eq!(decoded.ext(), Some(*ext));
+                assert_eq!(decoded.mac(), &make_mac());
+                assert_eq!(decoded.to_str(), bewit.to_str());
+            }
+        }
+        assert!(padded);
+    }
+
+    #[test]
+    fn test_from_resource() {
+        let bewit = Bewit::new("me", Timespec::new(1353832834, 0), make_mac(), None::<&str>);
+        let encoded = bewit.to_str();
+
+        let check = |resource: String, expected: &str| {
+            let (found, stripped) = Bewit::from_resource(&resource).unwrap().unwrap();
+            assert_eq!(found.id(), "me");
+            assert_eq!(stripped, expected);
+        };
+        check(format!("/resource?bewit={}", encoded), "/resource");
+        check(format!("/resource?a=1&bewit={}", encoded), "/resource?a=1");
+        check(format!("/resource?bewit={}&a=1", encoded), "/resource?a=1");
+        check(format!("/resource?a=1&bewit={}&b=2", encoded), "/resource?a=1&b=2");
+    }
+
+    #[test]
+    fn test_from_resource_none() {
+        assert!(Bewit::from_resource("/resource").unwrap().is_none());
+        assert!(Bewit::from_resource("/resource?notbewit=abc").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_from_resource_invalid() {
+        match Bewit::from_resource("/resource?bewit=") {
+            Err(Error::BewitFormat) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    fn expiring_in(ttl: Duration) -> Bewit<'static> {
+        Bewit::new("me", time::now().to_timespec() + ttl, make_mac(), Some("share-42"))
+    }
+
+    #[test]
+    fn test_policy_default() {
+        let policy = BewitPolicy::new();
+        assert_eq!(policy.check(&expiring_in(Duration::weeks(520))), Ok(()));
+    }
+
+    #[test]
+    fn test_policy_max_ttl() {
+        let policy = BewitPolicy::new().max_ttl(Duration::hours(1));
+        assert_eq!(policy.check(&expiring_in(Duration::minutes(59))), Ok(()));
+        assert_eq!(policy.check(&expiring_in(Duration::hours(2))),
+                   Err(AuthFailure::BewitLifetimeTooLong));
+    }
+
+    #[test]
+    fn test_policy_revocation() {
+        let policy = BewitPolicy::new().revocation(|bewit: &Bewit| bewit.ext() == Some("share-42"));
+        assert_eq!(policy.check(&expiring_in(Duration::minutes(1))),
+                   Err(AuthFailure::BewitRevoked));
+        let other = Bewit::new("me", time::now().to_timespec(), make_mac(), None::<&str>);
+        assert_eq!(policy.check(&other), Ok(()));
     }
 }
@@ -0,0 +1,227 @@
+use base64;
+use ring::digest;
+use error::*;
+
+/// The prefix marking an `ext` value that carries a header binding.
+const PREFIX: &str = "hb1:";
+
+/// A binding of selected HTTP headers into a request's Hawk signature, carried in `ext`.
+///
+/// Hawk's MAC covers the method, resource, host, port, payload hash and `ext`, but no other
+/// headers, so an intermediary can change headers such as `Content-Type`, `Range` or an
+/// idempotency key without invalidating the signature.  As an extension to Hawk, a client can
+/// list the names of the headers it wants to protect, along with a digest of their values, in
+/// `ext`.  The MAC then covers the digest, and a server supporting the extension recomputes it
+/// from the headers it received.  Servers without support see only an unusual `ext` value.
+///
+/// The `ext` value has the form `hb1:<names>:<digest>`, optionally followed by `:` and the
+/// application's own `ext` value, where `<names>` is the comma-separated, lower-case header
+/// names and `<digest>` is the base64 digest, with the key's algorithm, of
+///
+/// ```text
+/// hawk.1.headers
+/// <name>
+/// <value>
+/// ...
+/// ```
+///
+/// with a line for each name and its value, and a final newline.  A header given more than once
+/// has its values joined with `, `; an absent header has an empty value.
+///
+/// # Examples
+///
+/// ```
+/// use hawk::{HeaderBinding, SHA256};
+///
+/// let headers = |name: &str| match name {
+///     "idempotency-key" => vec!["8e03978e".to_string()],
+///     _ => vec![],
+/// };
+/// let binding = HeaderBinding::new(&["Idempotency-Key"], &SHA256, &headers).unwrap();
+/// let ext = binding.to_ext(Some("app-data"));
+/// // pass `ext` to `RequestBuilder::ext`; on the server..
+/// let (found, app_ext) = HeaderBinding::from_ext(&ext).unwrap().unwrap();
+/// assert_eq!(app_ext, Some("app-data"));
+/// assert!(found.check(&SHA256, &headers));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderBinding {
+    names: Vec<String>,
+    digest: Vec<u8>,
+}
+
+impl HeaderBinding {
+    /// Bind the named headers, where `headers` returns the values of the named request header.
+    /// The names are case-insensitive, and must be valid header names.
+    pub fn new<S, F>(names: &[S],
+                     algorithm: &'static digest::Algorithm,
+                     headers: F)
+                     -> Result<HeaderBinding>
+        where S: AsRef<str>,
+              F: Fn(&str) -> Vec<String>
+    {
+        let names = names.iter().map(|n| n.as_ref().to_lowercase()).collect::<Vec<_>>();
+        if names.is_empty() || !names.iter().all(|n| valid_name(n)) {
+            return Err(Error::InvalidHeaderBinding);
+        }
+        let digest = binding_digest(&names, algorithm, headers);
+        Ok(HeaderBinding {
+               names: names,
+               digest: digest,
+           })
+    }
+
+    /// Get the `ext` value carrying this binding, followed by the application's own `ext`
+    /// value, if any.
+    pub fn to_ext(&self, app_ext: Option<&str>) -> String {
+        let mut ext = format!("{}{}:{}",
+                              PREFIX,
+                              self.names.join(","),
+                              base64::encode(&self.digest));
+        if let Some(app_ext) = app_ext {
+            ext.push(':');
+            ext.push_str(app_ext);
+        }
+        ext
+    }
+
+    /// Find the binding in the given `ext` value, returning it along with the application's
+    /// own `ext` value, if any.  This returns `None` if the `ext` value carries no binding, and
+    /// an error if it has the binding's prefix but is malformed.
+    pub fn from_ext(ext: &str) -> Result<Option<(HeaderBinding, Option<&str>)>> {
+        if !ext.starts_with(PREFIX) {
+            return Ok(None);
+        }
+        let mut parts = ext[PREFIX.len()..].splitn(3, ':');
+        let names = parts.next().unwrap_or("");
+        let digest = parts.next().ok_or(Error::InvalidHeaderBinding)?;
+        let app_ext = parts.next();
+
+        let names = names.split(',').map(|n| n.to_string()).collect::<Vec<_>>();
+        if !names.iter().all(|n| valid_name(n) && n.to_lowercase() == *n) {
+            return Err(Error::InvalidHeaderBinding);
+        }
+        let digest = base64::decode(digest).map_err(|_| Error::InvalidHeaderBinding)?;
+        Ok(Some((HeaderBinding {
+                     names: names,
+                     digest: digest,
+                 },
+                 app_ext)))
+    }
+
+    /// Get the names of the bound headers, in lower case.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Check that the bound headers, whose values `headers` returns, match this binding.
+    pub fn check<F>(&self, algorithm: &'static digest::Algorithm, headers: F) -> bool
+        where F: Fn(&str) -> Vec<String>
+    {
+        binding_digest(&self.names, algorithm, headers) == self.digest
+    }
+}
+
+fn binding_digest<F>(names: &[String], algorithm: &'static digest::Algorithm, headers: F) -> Vec<u8>
+    where F: Fn(&str) -> Vec<String>
+{
+    let mut context = digest::Context::new(algorithm);
+    context.update(b"hawk.1.headers\n");
+    for name in names {
+        let values = headers(name);
+        let values = values.iter().map(|v| v.trim()).collect::<Vec<_>>();
+        context.update(name.as_bytes());
+        context.update(b"\n");
+        context.update(values.join(", ").as_bytes());
+        context.update(b"\n");
+    }
+    context.finish().as_ref().to_vec()
+}
+
+/// Check that a header name is an HTTP token, so cannot contain the `ext` value's separators.
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() &&
+    name.bytes().all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ring::digest::{SHA256, SHA512};
+
+    fn headers(name: &str) -> Vec<String> {
+        match name {
+            "content-type" => vec!["text/plain".to_string()],
+            "range" => vec!["bytes=0-99".to_string()],
+            "x-multi" => vec!["a".to_string(), " b ".to_string()],
+            _ => vec![],
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let binding = HeaderBinding::new(&["Content-Type", "Range"], &SHA256, headers).unwrap();
+        assert_eq!(binding.names(), &["content-type".to_string(), "range".to_string()]);
+
+        let ext = binding.to_ext(None);
+        assert!(ext.starts_with("hb1:content-type,range:"));
+        let (found, app_ext) = HeaderBinding::from_ext(&ext).unwrap().unwrap();
+        assert_eq!(found, binding);
+        assert_eq!(app_ext, None);
+        assert!(found.check(&SHA256, headers));
+    }
+
+    #[test]
+    fn test_app_ext() {
+        let binding = HeaderBinding::new(&["range"], &SHA256, headers).unwrap();
+        let ext = binding.to_ext(Some("a:b:c"));
+        let (found, app_ext) = HeaderBinding::from_ext(&ext).unwrap().unwrap();
+        assert_eq!(found, binding);
+        assert_eq!(app_ext, Some("a:b:c"));
+    }
+
+    #[test]
+    fn test_changed_headers() {
+        let binding = HeaderBinding::new(&["range", "x-absent"], &SHA256, headers).unwrap();
+        assert!(!binding.check(&SHA256, |name| match name {
+            "range" => vec!["bytes=0-".to_string()],
+            _ => vec![],
+        }));
+        // a header that was absent when signed may not be added
+        assert!(!binding.check(&SHA256, |name| match name {
+            "x-absent" => vec!["surprise".to_string()],
+            n => headers(n),
+        }));
+        assert!(!binding.check(&SHA512, headers));
+    }
+
+    #[test]
+    fn test_multiple_values() {
+        let binding = HeaderBinding::new(&["x-multi"], &SHA256, headers).unwrap();
+        assert!(binding.check(&SHA256, |_| vec!["a, b".to_string()]));
+    }
+
+    #[test]
+    fn test_not_a_binding() {
+        assert_eq!(HeaderBinding::from_ext("some-app-ext").unwrap(), None);
+        assert_eq!(HeaderBinding::from_ext("").unwrap(), None);
+    }
+
+    #[test]
+    fn test_invalid() {
+        for ext in &["hb1:", "hb1:range", "hb1:Range:AAAA", "hb1:ran ge:AAAA", "hb1:range:!!"] {
+            match HeaderBinding::from_ext(ext) {
+                Err(Error::InvalidHeaderBinding) => {}
+                r => panic!("unexpected result for {:?}: {:?}", ext, r),
+            }
+        }
+        match HeaderBinding::new(&["bad:name"], &SHA256, headers) {
+            Err(Error::InvalidHeaderBinding) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        match HeaderBinding::new::<&str, _>(&[], &SHA256, headers) {
+            Err(Error::InvalidHeaderBinding) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use std::borrow::Cow;
+
+/// A value for an optional field of `RequestBuilder` or `ResponseBuilder`, either borrowed or
+/// owned, and optionally wrapped in `Option`.
+///
+/// This is implemented for `&str`, `String` and `Cow<str>` and, for payload hashes, `&[u8]`,
+/// `Vec<u8>` and `Cow<[u8]>`, as well as for `Option`s of each.  A bare `None` does not say
+/// which of these it is, so clear a field with, for example, `None::<&str>`.
+pub trait IntoOptionCow<'a, T: ?Sized + ToOwned> {
+    /// Convert this value into an optional `Cow`.
+    fn into_option_cow(self) -> Option<Cow<'a, T>>;
+}
+
+macro_rules! impl_into_option_cow {
+    ($target:ty; $($source:ty),*) => {
+        $(
+            impl<'a> IntoOptionCow<'a, $target> for $source {
+                fn into_option_cow(self) -> Option<Cow<'a, $target>> {
+                    Some(self.into())
+                }
+            }
+
+            impl<'a> IntoOptionCow<'a, $target> for Option<$source> {
+                fn into_option_cow(self) -> Option<Cow<'a, $target>> {
+                    self.map(Into::into)
+                }
+            }
+        )*
+    }
+}
+
+impl_into_option_cow!(str; &'a str, String, Cow<'a, str>);
+impl_into_option_cow!([u8]; &'a [u8], Vec<u8>, Cow<'a, [u8]>);
+
+#[cfg(test)]
+mod test {
+    use super::IntoOptionCow;
+    use std::borrow::Cow;
+
+    #[test]
+    fn test_str() {
+        assert_eq!("a".into_option_cow(), Some(Cow::Borrowed("a")));
+        assert_eq!(Some("a".to_string()).into_option_cow(),
+                   Some(Cow::Owned::<str>("a".to_string())));
+        assert_eq!(None::<&str>.into_option_cow(), None);
+    }
+
+    #[test]
+    fn test_bytes() {
+        assert_eq!((&[1u8][..]).into_option_cow(), Some(Cow::Borrowed(&[1u8][..])));
+        assert_eq!(vec![1u8].into_option_cow(), Some(Cow::Owned::<[u8]>(vec![1])));
+    }
+}
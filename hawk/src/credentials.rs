@@ -1,4 +1,5 @@
 use ring::{digest, hmac};
+use mac::Mac;
 
 /// Hawk key.
 ///
@@ -14,12 +15,29 @@ impl Key {
         Key(hmac::SigningKey::new(algorithm, key.into().as_ref()))
     }
 
+    /// Get the digest algorithm used with this key.  Payload hashes should be calculated with
+    /// the same algorithm.
+    pub fn algorithm(&self) -> &'static digest::Algorithm {
+        self.0.digest_algorithm()
+    }
+
     pub fn sign(&self, data: &[u8]) -> Vec<u8> {
         let digest = hmac::sign(&self.0, data);
         let mut mac = vec![0; self.0.digest_algorithm().output_len];
         mac.clone_from_slice(digest.as_ref());
         return mac;
     }
+
+    /// Sign the given data, returning the signature as a `Mac`, which is stored inline and
+    /// compared in constant time.
+    pub fn sign_mac(&self, data: &[u8]) -> Mac {
+        Mac::from_signature(hmac::sign(&self.0, data))
+    }
+
+    /// Begin an HMAC calculation with this key, for data supplied in pieces.
+    pub(crate) fn signing_context(&self) -> hmac::SigningContext {
+        hmac::SigningContext::with_key(&self.0)
+    }
 }
 
 /// Hawk credentials: an ID and a key associated with that ID.  The digest algorithm
@@ -1,12 +1,183 @@
-error_chain! {
-    errors {
-        HeaderParseError {
-            description("Unparseable Hawk header")
+use std::error;
+use std::fmt;
+use std::io;
+
+/// The result type used throughout this crate.
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+/// The components of a bewit, used to report which of them could not be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BewitComponent {
+    Id,
+    Exp,
+    Mac,
+    Ext,
+}
+
+impl fmt::Display for BewitComponent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "{}",
+               match *self {
+                   BewitComponent::Id => "id",
+                   BewitComponent::Exp => "exp",
+                   BewitComponent::Mac => "mac",
+                   BewitComponent::Ext => "ext",
+               })
+    }
+}
+
+/// An error from this crate.
+///
+/// Positions in header-parsing errors are byte offsets into the header value (the part
+/// following "Hawk ").
+#[derive(Debug)]
+pub enum Error {
+    /// The header does not have the form `attr="value", ..`; parsing failed at `position`.
+    HeaderSyntax { position: usize },
+
+    /// The header contains an attribute that is not part of Hawk.
+    UnknownAttribute { position: usize, attribute: String },
+
+    /// The value of the given attribute, beginning at `position`, could not be decoded.  For
+    /// example, `ts` is not an integer or `mac` is not valid base64.
+    InvalidAttribute {
+        position: usize,
+        attribute: &'static str,
+    },
+
+    /// The value for the given header attribute contains a character that cannot be represented
+    /// in a Hawk header.
+    InvalidCharacter { attribute: &'static str },
+
+    /// A header is missing an attribute required for the operation.
+    MissingAttribute(&'static str),
+
+    /// A header has an attribute that is not allowed in its kind of header, such as `id` in a
+    /// `Server-Authorization` header.
+    UnexpectedAttribute(&'static str),
+
+    /// A bewit is not valid URL-safe base64.
+    BewitEncoding,
+
+    /// A bewit does not consist of four `\`-separated components.
+    BewitFormat,
+
+    /// A component of a bewit could not be decoded.
+    InvalidBewitComponent(BewitComponent),
+
+    /// The given URL has no host.
+    UrlMissingHost(String),
+
+    /// The given URL has no port, and its scheme has no default port.
+    UrlMissingPort(String),
+
+    /// The given `Host` header is not a valid host and optional port.
+    InvalidHost(String),
+
+    /// An HTTP header value is not visible ASCII, so cannot be read or written as a Hawk header.
+    InvalidHeaderValue,
+
+    /// An I/O error, such as from reading a payload.
+    Io(io::Error),
+
+    /// A header binding names an invalid header, or an `ext` value carrying a binding is
+    /// malformed.
+    InvalidHeaderBinding,
+
+    /// An `ext` value is not valid base64.
+    ExtEncoding,
+
+    /// An `ext` value could not be converted to or from JSON.
+    ExtJson(Box<dyn error::Error + Send + Sync>),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::HeaderSyntax { position } => {
+                write!(f, "Unparseable Hawk header at position {}", position)
+            }
+            Error::UnknownAttribute { position, ref attribute } => {
+                write!(f,
+                       "Invalid Hawk field `{}` at position {}",
+                       attribute,
+                       position)
+            }
+            Error::InvalidAttribute { position, attribute } => {
+                write!(f,
+                       "Error parsing `{}` field at position {}",
+                       attribute,
+                       position)
+            }
+            Error::InvalidCharacter { attribute } => {
+                write!(f, "Hawk header field `{}` cannot contain `\"`", attribute)
+            }
+            Error::MissingAttribute(attribute) => {
+                write!(f, "Missing `{}` attribute in Hawk header", attribute)
+            }
+            Error::UnexpectedAttribute(attribute) => {
+                write!(f, "Unexpected `{}` attribute in Hawk header", attribute)
+            }
+            Error::BewitEncoding => write!(f, "Error decoding bewit base64"),
+            Error::BewitFormat => write!(f, "Invalid bewit format"),
+            Error::InvalidBewitComponent(component) => write!(f, "Invalid bewit {}", component),
+            Error::UrlMissingHost(ref url) => write!(f, "url {} has no host", url),
+            Error::UrlMissingPort(ref url) => write!(f, "url {} has no port", url),
+            Error::InvalidHost(ref host) => write!(f, "invalid host {}", host),
+            Error::InvalidHeaderValue => write!(f, "Header value is not visible ASCII"),
+            Error::Io(ref e) => write!(f, "{}", e),
+            Error::InvalidHeaderBinding => write!(f, "Invalid header binding"),
+            Error::ExtEncoding => write!(f, "Error decoding ext base64"),
+            Error::ExtJson(ref e) => write!(f, "Error converting ext JSON: {}", e),
         }
     }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Io(ref e) => Some(e),
+            Error::ExtJson(ref e) => Some(&**e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::ExtJson(Box::new(e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn send_sync() {
+        fn is_send_sync<T: error::Error + Send + Sync + 'static>() {}
+        is_send_sync::<Error>();
+    }
 
-    foreign_links {
-        Io(::std::io::Error);
-        Decode(::base64::DecodeError);
+    #[test]
+    fn display() {
+        assert_eq!(format!("{}", Error::HeaderSyntax { position: 7 }),
+                   "Unparseable Hawk header at position 7");
+        assert_eq!(format!("{}",
+                           Error::InvalidAttribute {
+                               position: 4,
+                               attribute: "ts",
+                           }),
+                   "Error parsing `ts` field at position 4");
+        assert_eq!(format!("{}", Error::InvalidBewitComponent(BewitComponent::Ext)),
+                   "Invalid bewit ext");
     }
 }
@@ -0,0 +1,273 @@
+use std::fmt;
+use header::Header;
+use credentials::Key;
+use mac::{self, MacType};
+use error::*;
+
+/// A variation on a request, differing from the request as the server understood it in a way
+/// that commonly causes MAC mismatches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Alternative {
+    /// A different port, such as 443 when a TLS terminator forwards requests to port 80
+    Port(u16),
+    /// The host with or without a `:port` suffix
+    Host(String),
+    /// The path with or without its query
+    Path(String),
+    /// The MAC calculated without the header's payload hash
+    WithoutHash,
+    /// The MAC calculated with the request's payload hash, which the header omits
+    WithRequestHash,
+}
+
+impl fmt::Display for Alternative {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Alternative::Port(port) => write!(f, "port {}", port),
+            Alternative::Host(ref host) => write!(f, "host `{}`", host),
+            Alternative::Path(ref path) => write!(f, "path `{}`", path),
+            Alternative::WithoutHash => write!(f, "no payload hash"),
+            Alternative::WithRequestHash => write!(f, "the request's payload hash"),
+        }
+    }
+}
+
+/// A diagnosis of why a header's MAC did or did not match a request, from
+/// `Request::explain_header`.
+///
+/// This contains normalized strings, which include the values from the header and request but
+/// no key material, and neither the expected nor the received MAC.  It is intended for logging
+/// on the server or reporting to a developer debugging a client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    /// The normalized string for the request as given, over which the MAC was calculated
+    pub normalized: String,
+
+    /// True if the header's MAC matches the request as given
+    pub matched: bool,
+
+    /// If the MAC did not match, the first alternative for which it would have matched
+    pub alternative: Option<Alternative>,
+
+    /// The normalized string for `alternative`
+    pub alternative_normalized: Option<String>,
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.matched {
+            write!(f, "MAC matches normalized string {:?}", self.normalized)
+        } else if let (&Some(ref alt), &Some(ref alt_normalized)) =
+            (&self.alternative, &self.alternative_normalized) {
+            write!(f,
+                   "MAC does not match normalized string {:?}, but would match with {} \
+                    (normalized string {:?})",
+                   self.normalized,
+                   alt,
+                   alt_normalized)
+        } else {
+            write!(f,
+                   "MAC does not match normalized string {:?}, or any alternative; check the \
+                    key, method and ext",
+                   self.normalized)
+        }
+    }
+}
+
+/// The parts of a request that contribute to the MAC of an `Authorization` header.
+pub struct Components<'a> {
+    pub method: &'a str,
+    pub host: &'a str,
+    pub port: u16,
+    pub path: &'a str,
+    pub hash: Option<&'a [u8]>,
+}
+
+/// Explain a header's MAC with respect to the given request components.  This backs
+/// `Request::explain_header`.
+pub fn explain(header: &Header, key: &Key, req: Components) -> Result<Explanation> {
+    let ts = header.ts.ok_or(Error::MissingAttribute("ts"))?;
+    let nonce = header.nonce.as_ref().ok_or(Error::MissingAttribute("nonce"))?;
+    let header_mac = header.mac.as_ref().ok_or(Error::MissingAttribute("mac"))?;
+    let header_hash = header.hash.as_ref().map(|h| &h[..]);
+    let ext = header.ext.as_ref().map(|e| &e[..]);
+
+    let check = |host: &str, port: u16, path: &str, hash: Option<&[u8]>| -> (String, bool) {
+        let normalized = mac::normalized_string(MacType::Header,
+                                                ts,
+                                                nonce,
+                                                req.method,
+                                                host,
+                                                port,
+                                                path,
+                                                hash,
+                                                ext);
+        let calculated = key.sign_mac(normalized.as_bytes());
+        let matched = &calculated == header_mac;
+        (normalized, matched)
+    };
+
+    let (normalized, matched) = check(req.host, req.port, req.path, header_hash);
+    let mut explanation = Explanation {
+        normalized: normalized,
+        matched: matched,
+        alternative: None,
+        alternative_normalized: None,
+    };
+    if matched {
+        return Ok(explanation);
+    }
+
+    for alt in alternatives(&req, header_hash) {
+        let (normalized, matched) = match alt {
+            Alternative::Port(port) => check(req.host, port, req.path, header_hash),
+            Alternative::Host(ref host) => check(host, req.port, req.path, header_hash),
+            Alternative::Path(ref path) => check(req.host, req.port, path, header_hash),
+            Alternative::WithoutHash => check(req.host, req.port, req.path, None),
+            Alternative::WithRequestHash => check(req.host, req.port, req.path, req.hash),
+        };
+        if matched {
+            explanation.alternative = Some(alt);
+            explanation.alternative_normalized = Some(normalized);
+            break;
+        }
+    }
+
+    Ok(explanation)
+}
+
+/// Generate the alternatives worth trying for the given request.
+fn alternatives(req: &Components, header_hash: Option<&[u8]>) -> Vec<Alternative> {
+    let mut alts = vec![];
+
+    // a `:port` suffix on the host, ignoring the colons in a bracketed IPv6 address
+    let host_port = match req.host.rfind(':') {
+        Some(i) if !req.host[i..].contains(']') => {
+            req.host[i + 1..].parse::<u16>().ok().map(|p| (&req.host[..i], p))
+        }
+        _ => None,
+    };
+
+    // ports: the defaults for http and https, and any port included in the host
+    let mut ports = vec![80, 443];
+    if let Some((_, port)) = host_port {
+        ports.push(port);
+    }
+    for port in ports {
+        if port != req.port && !alts.contains(&Alternative::Port(port)) {
+            alts.push(Alternative::Port(port));
+        }
+    }
+
+    // host with or without the port
+    match host_port {
+        Some((host, _)) => alts.push(Alternative::Host(host.to_string())),
+        None => alts.push(Alternative::Host(format!("{}:{}", req.host, req.port))),
+    }
+
+    // path without the query
+    if let Some(i) = req.path.find('?') {
+        alts.push(Alternative::Path(req.path[..i].to_string()));
+    }
+
+    // hash present or absent
+    if header_hash.is_some() {
+        alts.push(Alternative::WithoutHash);
+    } else if req.hash.is_some() {
+        alts.push(Alternative::WithRequestHash);
+    }
+
+    alts
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Alternative, Explanation};
+    use credentials::{Credentials, Key};
+    use request::RequestBuilder;
+    use ring::digest;
+
+    fn credentials() -> Credentials {
+        Credentials {
+            id: "me".to_string(),
+            key: Key::new(vec![99u8; 32], &digest::SHA256),
+        }
+    }
+
+    /// Make a header for the client's view of a request, and explain it against the server's.
+    fn explain(client: RequestBuilder, server: RequestBuilder) -> Explanation {
+        let credentials = credentials();
+        let header = client.request().make_header(&credentials).unwrap();
+        server.request().explain_header(&header, &credentials.key).unwrap()
+    }
+
+    #[test]
+    fn test_matched() {
+        let req = RequestBuilder::new("GET", "example.com", 443, "/foo?x=1");
+        let explanation = explain(req.clone(), req);
+        assert!(explanation.matched);
+        assert_eq!(explanation.alternative, None);
+        assert!(explanation.normalized.starts_with("hawk.1.header\n"));
+        assert!(explanation.normalized.ends_with("\nGET\n/foo?x=1\nexample.com\n443\n\n\n"));
+    }
+
+    #[test]
+    fn test_port() {
+        let explanation = explain(RequestBuilder::new("GET", "example.com", 443, "/foo"),
+                                  RequestBuilder::new("GET", "example.com", 80, "/foo"));
+        assert!(!explanation.matched);
+        assert_eq!(explanation.alternative, Some(Alternative::Port(443)));
+        assert!(explanation.alternative_normalized.unwrap().contains("\nexample.com\n443\n"));
+    }
+
+    #[test]
+    fn test_host_with_port() {
+        let explanation = explain(RequestBuilder::new("GET", "example.com", 8080, "/foo"),
+                                  RequestBuilder::new("GET", "example.com:8080", 8080, "/foo"));
+        assert_eq!(explanation.alternative,
+                   Some(Alternative::Host("example.com".to_string())));
+    }
+
+    #[test]
+    fn test_host_without_port() {
+        let explanation = explain(RequestBuilder::new("GET", "[::1]:8080", 8080, "/foo"),
+                                  RequestBuilder::new("GET", "[::1]", 8080, "/foo"));
+        assert_eq!(explanation.alternative,
+                   Some(Alternative::Host("[::1]:8080".to_string())));
+    }
+
+    #[test]
+    fn test_path() {
+        let explanation = explain(RequestBuilder::new("GET", "example.com", 443, "/foo"),
+                                  RequestBuilder::new("GET", "example.com", 443, "/foo?x=1"));
+        assert_eq!(explanation.alternative,
+                   Some(Alternative::Path("/foo".to_string())));
+    }
+
+    #[test]
+    fn test_request_hash() {
+        let hash = vec![1u8, 2, 3, 4];
+        let credentials = credentials();
+        let client = RequestBuilder::new("POST", "example.com", 443, "/foo")
+            .hash(&hash[..])
+            .request();
+        let mut header = client.make_header(&credentials).unwrap();
+        // the client calculated the MAC with a hash, but did not send it
+        header.hash = None;
+
+        let server = RequestBuilder::new("POST", "example.com", 443, "/foo")
+            .hash(&hash[..])
+            .request();
+        let explanation = server.explain_header(&header, &credentials.key).unwrap();
+        assert_eq!(explanation.alternative, Some(Alternative::WithRequestHash));
+    }
+
+    #[test]
+    fn test_no_match() {
+        let explanation = explain(RequestBuilder::new("GET", "example.com", 443, "/foo"),
+                                  RequestBuilder::new("PUT", "example.com", 443, "/foo"));
+        assert!(!explanation.matched);
+        assert_eq!(explanation.alternative, None);
+        assert!(format!("{}", explanation).contains("or any alternative"));
+    }
+}
@@ -0,0 +1,155 @@
+//! Structured `ext` values, enabled with the `serde` feature.
+//!
+//! Hawk's `ext` is an opaque string, limited to characters that can appear in a header.  To
+//! carry structured data, such as Taskcluster's temporary credentials and authorized scopes,
+//! JS clients set `ext` to the base64 encoding of a JSON value.  The functions here use the same
+//! encoding, as do the `ext_json` methods of `RequestBuilder`, `ResponseBuilder`, `Header` and
+//! `Bewit`.  The `Header` and `Bewit` methods skip any `HeaderBinding` at the start of `ext`,
+//! decoding the application's value that follows it.
+//!
+//! # Examples
+//!
+//! ```
+//! extern crate hawk;
+//! #[macro_use]
+//! extern crate serde_json;
+//!
+//! fn main() {
+//!     let ext = hawk::ext::encode(&json!({"certificate": {"version": 1}})).unwrap();
+//!     assert_eq!(ext, "eyJjZXJ0aWZpY2F0ZSI6eyJ2ZXJzaW9uIjoxfX0=");
+//!
+//!     let value: serde_json::Value = hawk::ext::decode(&ext).unwrap();
+//!     assert_eq!(value["certificate"]["version"], 1);
+//! }
+//! ```
+
+use base64;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json;
+use binding::HeaderBinding;
+use error::*;
+
+/// Encode the given value as an `ext` string: the standard base64 encoding of its JSON.
+pub fn encode<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    Ok(base64::encode(&serde_json::to_vec(value)?))
+}
+
+/// Decode an `ext` string produced by `encode`, or by a JS client, into a value.
+pub fn decode<T: DeserializeOwned>(ext: &str) -> Result<T> {
+    let json = base64::decode(ext).map_err(|_| Error::ExtEncoding)?;
+    Ok(serde_json::from_slice(&json)?)
+}
+
+/// Decode the application's part of an `ext` value, following any `HeaderBinding`, or `None` if
+/// a binding is followed by no application value.
+pub(crate) fn decode_app<T: DeserializeOwned>(ext: &str) -> Result<Option<T>> {
+    let app_ext = match HeaderBinding::from_ext(ext)? {
+        Some((_, app_ext)) => app_ext,
+        None => Some(ext),
+    };
+    match app_ext {
+        Some(value) => decode(value).map(Some),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeMap;
+    use credentials::{Credentials, Key};
+    use request::RequestBuilder;
+    use ring::digest;
+    use time::Duration;
+
+    type Scopes = BTreeMap<String, Vec<String>>;
+
+    #[test]
+    fn test_round_trip() {
+        let mut value: Scopes = BTreeMap::new();
+        value.insert("clientId".to_string(), vec!["scope:a".to_string(), "scope:b".to_string()]);
+        let ext = encode(&value).unwrap();
+        assert_eq!(decode::<Scopes>(&ext).unwrap(), value);
+    }
+
+    #[test]
+    fn test_js_compatible() {
+        // Buffer.from(JSON.stringify({"scopes": ["a"], "name": "caf\u00e9"})).toString("base64")
+        let ext = "eyJzY29wZXMiOlsiYSJdLCJuYW1lIjoiY2Fmw6kifQ==";
+        let value: serde_json::Value = decode(ext).unwrap();
+        assert_eq!(value["name"], "caf\u{e9}");
+        assert_eq!(value["scopes"][0], "a");
+    }
+
+    #[test]
+    fn test_invalid() {
+        match decode::<serde_json::Value>("not base64!") {
+            Err(Error::ExtEncoding) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        // base64 for `{"a":`
+        match decode::<serde_json::Value>("eyJhIjo=") {
+            Err(Error::ExtJson(_)) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_header_and_bewit() {
+        let credentials = Credentials {
+            id: "me".to_string(),
+            key: Key::new(vec![1u8; 32], &digest::SHA256),
+        };
+        let mut value: Scopes = BTreeMap::new();
+        value.insert("scopes".to_string(), vec!["queue:create-task".to_string()]);
+        let request = RequestBuilder::new("GET", "example.com", 443, "/")
+            .ext_json(&value)
+            .unwrap()
+            .request();
+
+        let header = request.make_header(&credentials).unwrap();
+        assert_eq!(header.ext_json::<Scopes>().unwrap(), Some(value.clone()));
+        assert!(request.validate_header(&header, &credentials.key, Duration::minutes(1)));
+
+        let bewit = request.make_bewit(&credentials, Duration::minutes(1)).unwrap();
+        assert_eq!(bewit.ext_json::<Scopes>().unwrap(), Some(value));
+
+        let response = request.make_response_builder(&header)
+            .ext_json(&[1, 2, 3])
+            .unwrap()
+            .response()
+            .make_header(&credentials.key)
+            .unwrap();
+        assert_eq!(response.ext_json::<Vec<u32>>().unwrap(), Some(vec![1, 2, 3]));
+
+        let plain = RequestBuilder::new("GET", "example.com", 443, "/")
+            .request()
+            .make_header(&credentials)
+            .unwrap();
+        assert_eq!(plain.ext_json::<Vec<u32>>().unwrap(), None);
+    }
+
+    #[test]
+    fn test_header_binding() {
+        let credentials = Credentials {
+            id: "me".to_string(),
+            key: Key::new(vec![1u8; 32], &digest::SHA256),
+        };
+        let binding = HeaderBinding::new(&["range"], &digest::SHA256, |_| vec![]).unwrap();
+        let app_ext = encode(&[1, 2, 3]).unwrap();
+        let header = RequestBuilder::new("GET", "example.com", 443, "/")
+            .ext(binding.to_ext(Some(&app_ext)))
+            .request()
+            .make_header(&credentials)
+            .unwrap();
+        assert_eq!(header.ext_json::<Vec<u32>>().unwrap(), Some(vec![1, 2, 3]));
+
+        let header = RequestBuilder::new("GET", "example.com", 443, "/")
+            .ext(binding.to_ext(None))
+            .request()
+            .make_header(&credentials)
+            .unwrap();
+        assert_eq!(header.ext_json::<Vec<u32>>().unwrap(), None);
+    }
+}
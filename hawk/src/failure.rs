@@ -0,0 +1,201 @@
+use std::fmt;
+use header::{Header, HeaderBuilder};
+use credentials::Key;
+use mac::Mac;
+use error::*;
+use time;
+
+/// The reasons for which a server may reject a Hawk-authenticated request.
+///
+/// Each reason corresponds to an `error` value in the `WWW-Authenticate: Hawk` challenge sent by
+/// the JS implementation, and to an HTTP status code that should accompany it.  Servers can
+/// generate the challenge with `make_header`; clients can recover the reason from a received
+/// challenge with `from_header`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthFailure {
+    /// The request carried no Hawk authentication at all
+    MissingAuthentication,
+    /// The `Authorization` header could not be parsed
+    InvalidHeaderSyntax,
+    /// The `Authorization` header lacks one of `id`, `ts`, `nonce` or `mac`
+    MissingAttributes,
+    /// The `id` does not correspond to any known credentials
+    UnknownCredentials,
+    /// The MAC does not match the request
+    BadMac,
+    /// The server requires a payload hash, but none was given
+    MissingRequiredPayloadHash,
+    /// The payload hash does not match the request body
+    BadPayloadHash,
+    /// The nonce has been used before
+    InvalidNonce,
+    /// The timestamp is outside the allowed skew; the challenge carries the server's time
+    StaleTimestamp,
+    /// Both an `Authorization` header and a bewit were given
+    MultipleAuthentications,
+    /// The bewit could not be decoded
+    InvalidBewit,
+    /// A bewit was used with a method other than GET or HEAD
+    InvalidMethod,
+    /// The bewit has expired
+    AccessExpired,
+    /// The `Host` header is missing or invalid, so the request cannot be authenticated
+    InvalidHost,
+    /// The bewit expires further in the future than the server's `BewitPolicy` allows
+    BewitLifetimeTooLong,
+    /// The bewit has been revoked, according to the server's `BewitPolicy`
+    BewitRevoked,
+    /// The headers bound into `ext` with a `HeaderBinding` do not match the request
+    BadHeaderBinding,
+}
+
+impl AuthFailure {
+    /// Get the `error` value used in the `WWW-Authenticate` challenge for this failure.  This
+    /// is `None` for `MissingAuthentication`, for which the challenge carries no attributes.
+    pub fn message(&self) -> Option<&'static str> {
+        match *self {
+            AuthFailure::MissingAuthentication => None,
+            AuthFailure::InvalidHeaderSyntax => Some("Invalid header syntax"),
+            AuthFailure::MissingAttributes => Some("Missing attributes"),
+            AuthFailure::UnknownCredentials => Some("Unknown credentials"),
+            AuthFailure::BadMac => Some("Bad mac"),
+            AuthFailure::MissingRequiredPayloadHash => Some("Missing required payload hash"),
+            AuthFailure::BadPayloadHash => Some("Bad payload hash"),
+            AuthFailure::InvalidNonce => Some("Invalid nonce"),
+            AuthFailure::StaleTimestamp => Some("Stale timestamp"),
+            AuthFailure::MultipleAuthentications => Some("Multiple authentications"),
+            AuthFailure::InvalidBewit => Some("Invalid bewit encoding"),
+            AuthFailure::InvalidMethod => Some("Invalid method"),
+            AuthFailure::AccessExpired => Some("Access expired"),
+            AuthFailure::InvalidHost => Some("Invalid Host header"),
+            AuthFailure::BewitLifetimeTooLong => Some("Bewit lifetime too long"),
+            AuthFailure::BewitRevoked => Some("Bewit revoked"),
+            AuthFailure::BadHeaderBinding => Some("Bad header binding"),
+        }
+    }
+
+    /// Get the HTTP status code suggested for a response reporting this failure.
+    ///
+    /// Malformed requests get 400 Bad Request, and the JS implementation sends no
+    /// `WWW-Authenticate` header with those; all other failures get 401 Unauthorized.
+    pub fn status_code(&self) -> u16 {
+        match *self {
+            AuthFailure::InvalidHeaderSyntax |
+            AuthFailure::MissingAttributes |
+            AuthFailure::MultipleAuthentications |
+            AuthFailure::InvalidBewit |
+            AuthFailure::InvalidHost => 400,
+            _ => 401,
+        }
+    }
+
+    /// Determine the failure from the `error` attribute of a received `WWW-Authenticate`
+    /// header.  Returns `None` if the error is not one known to this library.
+    pub fn from_header(header: &Header) -> Option<AuthFailure> {
+        let message = match header.error {
+            Some(ref e) => e,
+            None => return Some(AuthFailure::MissingAuthentication),
+        };
+        let failures = [AuthFailure::InvalidHeaderSyntax,
+                        AuthFailure::MissingAttributes,
+                        AuthFailure::UnknownCredentials,
+                        AuthFailure::BadMac,
+                        AuthFailure::MissingRequiredPayloadHash,
+                        AuthFailure::BadPayloadHash,
+                        AuthFailure::InvalidNonce,
+                        AuthFailure::StaleTimestamp,
+                        AuthFailure::MultipleAuthentications,
+                        AuthFailure::InvalidBewit,
+                        AuthFailure::InvalidMethod,
+                        AuthFailure::AccessExpired,
+                        AuthFailure::InvalidHost,
+                        AuthFailure::BewitLifetimeTooLong,
+                        AuthFailure::BewitRevoked,
+                        AuthFailure::BadHeaderBinding];
+        failures.iter().cloned().find(|f| f.message() == Some(&message[..]))
+    }
+
+    /// Create the `WWW-Authenticate` header value (the part following "Hawk ") for this failure.
+    ///
+    /// For `StaleTimestamp`, if the client's key is given, the challenge includes the server's
+    /// current time in `ts` along with its MAC in `tsm`, allowing the client to correct its
+    /// clock.  The key is ignored for other failures.
+    pub fn make_header(&self, key: Option<&Key>) -> Result<Header> {
+        let mut builder = HeaderBuilder::challenge(self.message());
+        if let (&AuthFailure::StaleTimestamp, Some(key)) = (self, key) {
+            let ts = time::Timespec::new(time::now().to_timespec().sec, 0);
+            builder = builder.ts(ts).tsm(Mac::new_ts(key, ts)?);
+        }
+        builder.build()
+    }
+}
+
+impl fmt::Display for AuthFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message().unwrap_or("Missing authentication"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::AuthFailure;
+    use credentials::Key;
+    use mac::Mac;
+    use ring::digest;
+    use std::str::FromStr;
+    use header::Header;
+
+    #[test]
+    fn test_missing_authentication() {
+        let header = AuthFailure::MissingAuthentication.make_header(None).unwrap();
+        assert_eq!(format!("{}", header), "");
+        assert_eq!(AuthFailure::MissingAuthentication.status_code(), 401);
+        assert_eq!(AuthFailure::from_header(&header),
+                   Some(AuthFailure::MissingAuthentication));
+    }
+
+    #[test]
+    fn test_bad_mac() {
+        let header = AuthFailure::BadMac.make_header(None).unwrap();
+        assert_eq!(format!("{}", header), "error=\"Bad mac\"");
+        assert_eq!(AuthFailure::BadMac.status_code(), 401);
+        assert_eq!(AuthFailure::from_header(&header), Some(AuthFailure::BadMac));
+    }
+
+    #[test]
+    fn test_bad_request() {
+        assert_eq!(AuthFailure::InvalidHeaderSyntax.status_code(), 400);
+        assert_eq!(AuthFailure::MissingAttributes.status_code(), 400);
+        assert_eq!(AuthFailure::InvalidHost.status_code(), 400);
+    }
+
+    #[test]
+    fn test_stale_timestamp() {
+        let key = Key::new("tok", &digest::SHA256);
+        let header = AuthFailure::StaleTimestamp.make_header(Some(&key)).unwrap();
+        let ts = header.ts.unwrap();
+        assert_eq!(header.tsm, Some(Mac::new_ts(&key, ts).unwrap()));
+        assert_eq!(header.error, Some("Stale timestamp".to_string()));
+
+        // and survives a round trip
+        let header = Header::from_str(&format!("{}", header)).unwrap();
+        assert_eq!(AuthFailure::from_header(&header),
+                   Some(AuthFailure::StaleTimestamp));
+        assert_eq!(header.tsm, Some(Mac::new_ts(&key, ts).unwrap()));
+    }
+
+    #[test]
+    fn test_stale_timestamp_without_key() {
+        let header = AuthFailure::StaleTimestamp.make_header(None).unwrap();
+        assert_eq!(header.ts, None);
+        assert_eq!(header.tsm, None);
+        assert_eq!(header.error, Some("Stale timestamp".to_string()));
+    }
+
+    #[test]
+    fn test_unknown_error() {
+        let mut header = AuthFailure::BadMac.make_header(None).unwrap();
+        header.error = Some("Something else".to_string());
+        assert_eq!(AuthFailure::from_header(&header), None);
+    }
+}
@@ -0,0 +1,375 @@
+use std::net::IpAddr;
+use request::RequestBuilder;
+use error::*;
+
+/// A set of reverse proxies, such as TLS-terminating load balancers, that are trusted to report
+/// the host, port and scheme a client used to reach the server.
+///
+/// Behind such a proxy, the host and port the client signed (say `api.example.com:443`) differ
+/// from those the server sees (say `10.0.0.5:8080`).  When a request arrives from a trusted
+/// proxy, the `Forwarded` header (RFC 7239) or, failing that, the `X-Forwarded-Host`,
+/// `X-Forwarded-Proto` and `X-Forwarded-Port` headers, give the values the client used.  These
+/// headers are ignored for requests from any other peer, as clients can set them to anything.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    addrs: Vec<IpAddr>,
+}
+
+/// The host, port and scheme a client used, as reported by trusted proxies.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Forwarded {
+    /// The host the client requested, without any port; IPv6 hosts retain their brackets.
+    pub host: Option<String>,
+    /// The port the client requested, from the forwarded host or an explicit port.
+    pub port: Option<u16>,
+    /// The scheme the client used, such as `https`.
+    pub scheme: Option<String>,
+}
+
+impl TrustedProxies {
+    /// Trust proxies at the given addresses.
+    pub fn new<I>(addrs: I) -> Self
+        where I: IntoIterator<Item = IpAddr>
+    {
+        TrustedProxies { addrs: addrs.into_iter().collect() }
+    }
+
+    /// Determine whether the given address is a trusted proxy.  IPv4 addresses match their
+    /// IPv4-mapped IPv6 forms, as seen by servers listening on dual-stack sockets.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        let addr = match addr {
+            IpAddr::V6(v6) if v6.segments()[..6] == [0, 0, 0, 0, 0, 0xffff] => {
+                v6.to_ipv4().map(IpAddr::V4).unwrap_or(addr)
+            }
+            _ => addr,
+        };
+        self.addrs.iter().any(|a| *a == addr)
+    }
+
+    /// Get the forwarded host, port and scheme for a request from `peer`, where `headers`
+    /// returns the values of the named request header.
+    ///
+    /// This returns `None` if `peer` is not trusted or no forwarding headers are present.
+    ///
+    /// A `Forwarded` header has an element for each proxy the request passed through.  The
+    /// element added by `peer` is used, unless its `for` parameter is itself a trusted proxy, in
+    /// which case the element before it is used, and so on, so that a chain of trusted proxies
+    /// reports the values seen by the outermost of them.  Of the `X-Forwarded-*` headers, which
+    /// carry no such chain, the last value of each is used.
+    pub fn forwarded<F>(&self, peer: IpAddr, headers: F) -> Option<Forwarded>
+        where F: Fn(&str) -> Vec<String>
+    {
+        if !self.contains(peer) {
+            return None;
+        }
+
+        let elements: Vec<Vec<(String, String)>> = headers("forwarded")
+            .iter()
+            .flat_map(|value| split_quoted(value, ','))
+            .map(|element| parse_element(&element))
+            .collect();
+        if !elements.is_empty() {
+            let mut i = elements.len() - 1;
+            while i > 0 && self.is_trusted_node(param(&elements[i], "for")) {
+                i -= 1;
+            }
+            let element = &elements[i];
+            let (host, port) = match param(element, "host").and_then(|h| split_host(h).ok()) {
+                Some((host, port)) => (Some(host), port),
+                None => (None, None),
+            };
+            return Some(Forwarded {
+                host: host,
+                port: port,
+                scheme: param(element, "proto").map(|p| p.to_ascii_lowercase()),
+            });
+        }
+
+        let last = |name: &str| {
+            headers(name)
+                .iter()
+                .flat_map(|value| value.rsplit(',').next().map(|v| v.trim().to_string()))
+                .filter(|v| !v.is_empty())
+                .last()
+        };
+        let (host, host_port) = match last("x-forwarded-host").and_then(|h| split_host(&h).ok()) {
+            Some((host, port)) => (Some(host), port),
+            None => (None, None),
+        };
+        let port = last("x-forwarded-port").and_then(|p| p.parse().ok()).or(host_port);
+        let scheme = last("x-forwarded-proto").map(|p| p.to_ascii_lowercase());
+        if host.is_none() && port.is_none() && scheme.is_none() {
+            return None;
+        }
+        Some(Forwarded {
+            host: host,
+            port: port,
+            scheme: scheme,
+        })
+    }
+
+    /// Determine whether a `Forwarded` node (the value of a `for` parameter) is a trusted proxy.
+    fn is_trusted_node(&self, node: Option<&str>) -> bool {
+        let node = match node {
+            Some(node) => node,
+            None => return false,
+        };
+        // nodes are `ip`, `ip:port`, `[ipv6]` or `[ipv6]:port`, or obfuscated identifiers
+        let addr = if node.starts_with('[') {
+            node[1..].split(']').next().and_then(|a| a.parse().ok())
+        } else {
+            node.split(':').next().and_then(|a| a.parse().ok())
+        };
+        addr.map(|a| self.contains(a)).unwrap_or(false)
+    }
+}
+
+impl Forwarded {
+    /// Apply the forwarded values to a request builder for the request as the server saw it.
+    ///
+    /// A forwarded host replaces the builder's host.  The port is the forwarded port, if any, or
+    /// else the default port for the forwarded scheme (443 for `https` and 80 for `http`).  If
+    /// neither is known, the builder's port is left unchanged.
+    pub fn apply<'a>(&self, builder: RequestBuilder<'a>) -> RequestBuilder<'a> {
+        let builder = match self.host {
+            Some(ref host) => builder.host(host.clone()),
+            None => builder,
+        };
+        let port = self.port.or_else(|| {
+            match self.scheme.as_ref().map(|s| &s[..]) {
+                Some("https") => Some(443),
+                Some("http") => Some(80),
+                _ => None,
+            }
+        });
+        match port {
+            Some(port) => builder.port(port),
+            None => builder,
+        }
+    }
+}
+
+/// Split a header value on the given separator, ignoring separators within quoted strings.
+fn split_quoted(value: &str, separator: char) -> Vec<String> {
+    let mut parts = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    for c in value.chars() {
+        if escaped {
+            escaped = false;
+        } else if quoted && c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            quoted = !quoted;
+        } else if c == separator && !quoted {
+            parts.push(current.trim().to_string());
+            current = String::new();
+            continue;
+        }
+        current.push(c);
+    }
+    parts.push(current.trim().to_string());
+    parts.into_iter().filter(|p| !p.is_empty()).collect()
+}
+
+/// Parse a `Forwarded` element into its parameters, with lower-cased names and unquoted values.
+fn parse_element(element: &str) -> Vec<(String, String)> {
+    split_quoted(element, ';')
+        .iter()
+        .filter_map(|pair| {
+            let mut kv = pair.splitn(2, '=');
+            let name = kv.next()?.trim().to_ascii_lowercase();
+            let value = kv.next()?.trim();
+            let value = if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+                unquote(&value[1..value.len() - 1])
+            } else {
+                value.to_string()
+            };
+            Some((name, value))
+        })
+        .collect()
+}
+
+/// Remove the escapes from the contents of a quoted string.
+fn unquote(value: &str) -> String {
+    let mut unquoted = String::with_capacity(value.len());
+    let mut escaped = false;
+    for c in value.chars() {
+        if c == '\\' && !escaped {
+            escaped = true;
+        } else {
+            unquoted.push(c);
+            escaped = false;
+        }
+    }
+    unquoted
+}
+
+fn param<'a>(element: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    element.iter().find(|&&(ref n, _)| n == name).map(|&(_, ref v)| &v[..])
+}
+
+/// Split a `host[:port]` value, such as a `Host` header, into the host and optional port.  The
+/// host may be a bracketed IPv6 address, which keeps its brackets.
+///
+/// This returns `Error::InvalidHost` if the value is malformed.
+pub fn split_host(value: &str) -> Result<(String, Option<u16>)> {
+    let invalid = || Error::InvalidHost(value.to_string());
+    let (host, port) = if value.starts_with('[') {
+        let end = value.find(']').ok_or_else(&invalid)?;
+        match &value[end + 1..] {
+            "" => (&value[..end + 1], None),
+            rest if rest.starts_with(':') => (&value[..end + 1], Some(&rest[1..])),
+            _ => return Err(invalid()),
+        }
+    } else {
+        match value.rfind(':') {
+            Some(i) => (&value[..i], Some(&value[i + 1..])),
+            None => (value, None),
+        }
+    };
+    if host.is_empty() || host.contains(|c: char| c == '@' || c == '/' || c.is_whitespace()) {
+        return Err(invalid());
+    }
+    let port = match port {
+        Some(port) => Some(port.parse().map_err(|_| invalid())?),
+        None => None,
+    };
+    Ok((host.to_string(), port))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+    use credentials::{Credentials, Key};
+    use ring::digest;
+    use time::Duration;
+
+    fn ip(addr: &str) -> IpAddr {
+        addr.parse().unwrap()
+    }
+
+    fn proxies() -> TrustedProxies {
+        TrustedProxies::new(vec![ip("10.0.0.1"), ip("10.0.0.2"), ip("fd00::1")])
+    }
+
+    fn forwarded(peer: &str, headers: &[(&str, &str)]) -> Option<Forwarded> {
+        let mut map: HashMap<String, Vec<String>> = HashMap::new();
+        for &(name, value) in headers {
+            map.entry(name.to_string()).or_insert_with(Vec::new).push(value.to_string());
+        }
+        proxies().forwarded(ip(peer), |name| map.get(name).cloned().unwrap_or_default())
+    }
+
+    fn expected(host: Option<&str>, port: Option<u16>, scheme: Option<&str>) -> Option<Forwarded> {
+        Some(Forwarded {
+                 host: host.map(|h| h.to_string()),
+                 port: port,
+                 scheme: scheme.map(|s| s.to_string()),
+             })
+    }
+
+    #[test]
+    fn test_split_host() {
+        assert_eq!(split_host("example.com").unwrap(), ("example.com".to_string(), None));
+        assert_eq!(split_host("example.com:8443").unwrap(),
+                   ("example.com".to_string(), Some(8443)));
+        assert_eq!(split_host("[::1]:9000").unwrap(), ("[::1]".to_string(), Some(9000)));
+        assert_eq!(split_host("[::1]").unwrap(), ("[::1]".to_string(), None));
+        for host in &["", "example.com:http", "[::1", "[::1]x", "user@example.com", "a b"] {
+            match split_host(host) {
+                Err(Error::InvalidHost(_)) => {}
+                r => panic!("unexpected result for {:?}: {:?}", host, r),
+            }
+        }
+    }
+
+    #[test]
+    fn test_untrusted_peer() {
+        assert_eq!(forwarded("192.0.2.1", &[("forwarded", "host=evil.com;proto=https")]),
+                   None);
+        assert_eq!(forwarded("192.0.2.1", &[("x-forwarded-host", "evil.com")]), None);
+    }
+
+    #[test]
+    fn test_no_headers() {
+        assert_eq!(forwarded("10.0.0.1", &[]), None);
+    }
+
+    #[test]
+    fn test_mapped_ipv4_peer() {
+        assert!(proxies().contains(ip("::ffff:10.0.0.1")));
+        assert!(proxies().contains(ip("fd00::1")));
+        assert!(!proxies().contains(ip("::10.0.0.1")));
+    }
+
+    #[test]
+    fn test_forwarded() {
+        assert_eq!(forwarded("10.0.0.1",
+                             &[("forwarded",
+                                "for=192.0.2.60;Proto=HTTPS;host=\"api.example.com\"")]),
+                   expected(Some("api.example.com"), None, Some("https")));
+        assert_eq!(forwarded("10.0.0.1", &[("forwarded", "host=\"[2001:db8::1]:8443\"")]),
+                   expected(Some("[2001:db8::1]"), Some(8443), None));
+    }
+
+    #[test]
+    fn test_forwarded_chain() {
+        // the client sent its own element, then two trusted proxies added theirs
+        let headers = [("forwarded", "for=1.2.3.4;host=evil.com"),
+                       ("forwarded", "for=192.0.2.60;host=api.example.com;proto=https, \
+                                      for=\"10.0.0.2:1234\";host=internal;proto=http")];
+        assert_eq!(forwarded("10.0.0.1", &headers),
+                   expected(Some("api.example.com"), None, Some("https")));
+
+        // an untrusted intermediary's element is not looked past
+        let headers = [("forwarded",
+                        "for=192.0.2.60;host=api.example.com, for=192.0.2.9;host=x")];
+        assert_eq!(forwarded("10.0.0.1", &headers), expected(Some("x"), None, None));
+    }
+
+    #[test]
+    fn test_x_forwarded() {
+        assert_eq!(forwarded("10.0.0.1",
+                             &[("x-forwarded-host", "evil.com, api.example.com"),
+                               ("x-forwarded-proto", "https"),
+                               ("x-forwarded-port", "8443")]),
+                   expected(Some("api.example.com"), Some(8443), Some("https")));
+        assert_eq!(forwarded("10.0.0.1", &[("x-forwarded-host", "api.example.com:444")]),
+                   expected(Some("api.example.com"), Some(444), None));
+        assert_eq!(forwarded("10.0.0.1", &[("x-forwarded-proto", "https")]),
+                   expected(None, None, Some("https")));
+    }
+
+    #[test]
+    fn test_forwarded_takes_precedence() {
+        assert_eq!(forwarded("10.0.0.1",
+                             &[("forwarded", "host=api.example.com"),
+                               ("x-forwarded-host", "other.example.com")]),
+                   expected(Some("api.example.com"), None, None));
+    }
+
+    #[test]
+    fn test_apply() {
+        let credentials = Credentials {
+            id: "me".to_string(),
+            key: Key::new(vec![1u8; 32], &digest::SHA256),
+        };
+        let header = RequestBuilder::new("GET", "api.example.com", 443, "/resource")
+            .request()
+            .make_header(&credentials)
+            .unwrap();
+
+        let seen = RequestBuilder::new("GET", "10.0.0.5", 8080, "/resource");
+        let forwarded = expected(Some("api.example.com"), None, Some("https")).unwrap();
+        let request = forwarded.apply(seen.clone()).request();
+        assert!(request.validate_header(&header, &credentials.key, Duration::minutes(1)));
+
+        // without a port or scheme, the port is unchanged
+        let forwarded = expected(Some("api.example.com"), None, None).unwrap();
+        let request = forwarded.apply(seen).request();
+        assert!(!request.validate_header(&header, &credentials.key, Duration::minutes(1)));
+    }
+}
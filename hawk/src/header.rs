@@ -2,14 +2,22 @@ use base64;
 use std::fmt;
 use std::str::FromStr;
 use mac::Mac;
+use credentials::Key;
 use error::*;
 use time::Timespec;
+#[cfg(feature = "serde")]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "serde")]
+use ext;
 
 /// Representation of a Hawk `Authorization` header value (the part following "Hawk ").
 ///
 /// Headers can be derived froms trings using the `FromStr` trait, and formatted into a
 /// string using the `fmt_header` method.
 ///
+/// The same representation is used for `Server-Authorization` and `WWW-Authenticate` header
+/// values.  The `tsm` and `error` fields only appear in the latter; see `AuthFailure`.
+///
 /// All fields are optional, although for specific purposes some fields must be present.
 #[derive(Clone, PartialEq, Debug)]
 pub struct Header {
@@ -21,12 +29,15 @@ pub struct Header {
     pub hash: Option<Vec<u8>>,
     pub app: Option<String>,
     pub dlg: Option<String>,
+    pub tsm: Option<Mac>,
+    pub error: Option<String>,
 }
 
 impl Header {
     /// Create a new Header with the full set of Hawk fields.
     ///
-    /// This is a low-level funtion. Headers are more often created from Request or Responses.
+    /// This is a low-level funtion. Headers are more often created from Request or Responses,
+    /// and `HeaderBuilder` is more convenient for creating them directly.
     ///
     /// Note that none of the string-formatted header components can contain the character `\"`.
     pub fn new<S>(id: Option<S>,
@@ -41,25 +52,37 @@ impl Header {
         where S: Into<String>
     {
         Ok(Header {
-            id: Header::check_component(id)?,
+            id: Header::check_component("id", id)?,
             ts: ts,
-            nonce: Header::check_component(nonce)?,
+            nonce: Header::check_component("nonce", nonce)?,
             mac: mac,
-            ext: Header::check_component(ext)?,
+            ext: Header::check_component("ext", ext)?,
             hash: hash,
-            app: Header::check_component(app)?,
-            dlg: Header::check_component(dlg)?,
+            app: Header::check_component("app", app)?,
+            dlg: Header::check_component("dlg", dlg)?,
+            tsm: None,
+            error: None,
         })
     }
 
+    /// Decode the `ext` attribute as base64 JSON, as described in the `ext` module, skipping any
+    /// `HeaderBinding`.  Returns `None` if the header has no `ext`, or only a binding.
+    #[cfg(feature = "serde")]
+    pub fn ext_json<T: DeserializeOwned>(&self) -> Result<Option<T>> {
+        match self.ext {
+            Some(ref value) => ext::decode_app(value),
+            None => Ok(None),
+        }
+    }
+
     /// Check a header component for validity.
-    fn check_component<S>(value: Option<S>) -> Result<Option<String>>
+    fn check_component<S>(attribute: &'static str, value: Option<S>) -> Result<Option<String>>
         where S: Into<String>
     {
         if let Some(value) = value {
             let value = value.into();
             if value.contains("\"") {
-                bail!("Hawk headers cannot contain `\\`");
+                return Err(Error::InvalidCharacter { attribute: attribute });
             }
             Ok(Some(value))
         } else {
@@ -67,6 +90,22 @@ impl Header {
         }
     }
 
+    /// Get the server's timestamp from a `WWW-Authenticate` challenge.
+    ///
+    /// This returns the `ts` attribute only if it is accompanied by a `tsm` attribute that
+    /// verifies with the given key, so the result can be trusted to adjust the local clock.
+    pub fn challenge_ts(&self, key: &Key) -> Option<Timespec> {
+        match (self.ts, self.tsm.as_ref()) {
+            (Some(ts), Some(tsm)) => {
+                match Mac::new_ts(key, ts) {
+                    Ok(ref calculated) if calculated == tsm => Some(ts),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
     /// Format the header for transmission in an Authorization header, omitting the `"Hawk "`
     /// prefix.
     pub fn fmt_header(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -79,6 +118,10 @@ impl Header {
             write!(f, "{}ts=\"{}\"", sep, ts.sec)?;
             sep = ", ";
         }
+        if let Some(ref tsm) = self.tsm {
+            write!(f, "{}tsm=\"{}\"", sep, base64::encode(tsm))?;
+            sep = ", ";
+        }
         if let Some(ref nonce) = self.nonce {
             write!(f, "{}nonce=\"{}\"", sep, nonce)?;
             sep = ", ";
@@ -101,6 +144,10 @@ impl Header {
         }
         if let Some(ref dlg) = self.dlg {
             write!(f, "{}dlg=\"{}\"", sep, dlg)?;
+            sep = ", ";
+        }
+        if let Some(ref error) = self.error {
+            write!(f, "{}error=\"{}\"", sep, error)?;
         }
         Ok(())
     }
@@ -121,12 +168,15 @@ impl FromStr for Header {
         let mut id: Option<&str> = None;
         let mut ts: Option<Timespec> = None;
         let mut nonce: Option<&str> = None;
-        let mut mac: Option<Vec<u8>> = None;
+        let mut mac: Option<Mac> = None;
         // Optional attributes
         let mut hash: Option<Vec<u8>> = None;
         let mut ext: Option<&str> = None;
         let mut app: Option<&str> = None;
         let mut dlg: Option<&str> = None;
+        // WWW-Authenticate attributes
+        let mut tsm: Option<Mac> = None;
+        let mut error: Option<&str> = None;
 
         while p.len() > 0 {
             // Skip whitespace and commas used as separators
@@ -137,14 +187,22 @@ impl FromStr for Header {
             match p.find("=") {
                 Some(v) => {
                     let attr = &p[..v].trim();
+                    let attr_position = s.len() - p.len();
                     if p.len() < v + 1 {
-                        bail!(ErrorKind::HeaderParseError);
+                        return Err(Error::HeaderSyntax { position: s.len() - p.len() });
                     }
                     p = (&p[v + 1..]).trim_left();
                     if !p.starts_with("\"") {
-                        bail!(ErrorKind::HeaderParseError);
+                        return Err(Error::HeaderSyntax { position: s.len() - p.len() });
                     }
                     p = &p[1..];
+                    let position = s.len() - p.len();
+                    let invalid = |attribute| {
+                        Error::InvalidAttribute {
+                            position: position,
+                            attribute: attribute,
+                        }
+                    };
                     // We have poor RFC 7235 compliance here as we ought to support backslash
                     // escaped characters, but hawk doesn't allow this we won't either.  All
                     // strings must be surrounded by ".." and contain no such characters.
@@ -155,23 +213,35 @@ impl FromStr for Header {
                             match *attr {
                                 "id" => id = Some(val),
                                 "ts" => {
-                                    let epoch = i64::from_str(val)
-                                        .chain_err(|| "Error parsing `ts` field")?;
+                                    let epoch = i64::from_str(val).map_err(|_| invalid("ts"))?;
                                     ts = Some(Timespec::new(epoch, 0));
                                 }
                                 "mac" => {
                                     mac = Some(base64::decode(val)
-                                                   .chain_err(|| "Error parsing `mac` field")?);
+                                                   .ok()
+                                                   .and_then(|v| Mac::from_slice(&v))
+                                                   .ok_or_else(|| invalid("mac"))?);
                                 }
                                 "nonce" => nonce = Some(val),
                                 "ext" => ext = Some(val),
                                 "hash" => {
-                                    hash = Some(base64::decode(val)
-                                                    .chain_err(|| "Error parsing `hash` field")?);
+                                    hash = Some(base64::decode(val).map_err(|_| invalid("hash"))?);
                                 }
                                 "app" => app = Some(val),
                                 "dlg" => dlg = Some(val),
-                                _ => bail!("Invalid Hawk field {}", *attr),
+                                "tsm" => {
+                                    tsm = Some(base64::decode(val)
+                                                   .ok()
+                                                   .and_then(|v| Mac::from_slice(&v))
+                                                   .ok_or_else(|| invalid("tsm"))?);
+                                }
+                                "error" => error = Some(val),
+                                _ => {
+                                    return Err(Error::UnknownAttribute {
+                                                   position: attr_position,
+                                                   attribute: attr.to_string(),
+                                               })
+                                }
                             };
                             // Break if we are at end of string, otherwise skip separator
                             if p.len() < v + 1 {
@@ -179,10 +249,10 @@ impl FromStr for Header {
                             }
                             p = &p[v + 1..].trim_left();
                         }
-                        None => bail!(ErrorKind::HeaderParseError),
+                        None => return Err(Error::HeaderSyntax { position: s.len() - p.len() }),
                     }
                 }
-                None => bail!(ErrorKind::HeaderParseError),
+                None => return Err(Error::HeaderSyntax { position: s.len() - p.len() }),
             };
         }
 
@@ -196,10 +266,7 @@ impl FromStr for Header {
                 Some(nonce) => Some(nonce.to_string()),
                 None => None,
             },
-            mac: match mac {
-                Some(mac) => Some(Mac::from(mac)),
-                None => None,
-            },
+            mac: mac,
             ext: match ext {
                 Some(ext) => Some(ext.to_string()),
                 None => None,
@@ -213,28 +280,241 @@ impl FromStr for Header {
                 Some(dlg) => Some(dlg.to_string()),
                 None => None,
             },
+            tsm: tsm,
+            error: match error {
+                Some(error) => Some(error.to_string()),
+                None => None,
+            },
+        })
+    }
+}
+
+/// A builder for `Header` values, with named setters for each field.
+///
+/// Each of the three kinds of header used in Hawk has its own constructor, giving the attributes
+/// that header requires: `authorization` for a request's `Authorization` header,
+/// `server_authorization` for a response's `Server-Authorization` header, and `challenge` for a
+/// `WWW-Authenticate` header.  The remaining attributes of each kind can then be set:
+///
+/// * `Authorization`: `ext`, `hash`, `app` and `dlg`
+/// * `Server-Authorization`: `ext` and `hash`
+/// * `WWW-Authenticate`: `ts` and `tsm`
+///
+/// Building a header of one of these kinds fails with `Error::UnexpectedAttribute` if any other
+/// attribute was set.  A builder from `new` has no kind, and accepts any attributes.
+///
+/// As with `Header::new`, the string-formatted components are checked when the header is built.
+///
+/// # Examples
+///
+/// ```
+/// extern crate time;
+/// extern crate hawk;
+///
+/// use hawk::HeaderBuilder;
+/// use hawk::mac::Mac;
+///
+/// fn main() {
+///     let mac = Mac::from(vec![1, 2, 3, 4]);
+///     let header = HeaderBuilder::authorization("dh37fgj492je",
+///                                               time::Timespec::new(1353832234, 0),
+///                                               "j4h3g2",
+///                                               mac)
+///         .ext("my-ext-value")
+///         .build()
+///         .unwrap();
+///     assert_eq!(header.id.unwrap(), "dh37fgj492je");
+///     assert_eq!(header.ext.unwrap(), "my-ext-value");
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct HeaderBuilder(Header, Option<HeaderKind>);
+
+/// The kinds of header made by `HeaderBuilder`'s constructors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum HeaderKind {
+    Authorization,
+    ServerAuthorization,
+    Challenge,
+}
+
+impl HeaderKind {
+    /// Get the attributes allowed in this kind of header.
+    fn attributes(&self) -> &'static [&'static str] {
+        match *self {
+            HeaderKind::Authorization => &["id", "ts", "nonce", "mac", "ext", "hash", "app", "dlg"],
+            HeaderKind::ServerAuthorization => &["mac", "ext", "hash"],
+            HeaderKind::Challenge => &["ts", "tsm", "error"],
+        }
+    }
+}
+
+impl Default for HeaderBuilder {
+    fn default() -> Self {
+        let header = Header {
+            id: None,
+            ts: None,
+            nonce: None,
+            mac: None,
+            ext: None,
+            hash: None,
+            app: None,
+            dlg: None,
+            tsm: None,
+            error: None,
+        };
+        HeaderBuilder(header, None)
+    }
+}
+
+impl HeaderBuilder {
+    /// Create a new builder with no fields set.
+    pub fn new() -> Self {
+        HeaderBuilder::default()
+    }
+
+    /// Create a new builder for a request's `Authorization` header, with the required fields.
+    pub fn authorization(id: &str, ts: Timespec, nonce: &str, mac: Mac) -> Self {
+        HeaderBuilder::new()
+            .kind(HeaderKind::Authorization)
+            .id(id)
+            .ts(ts)
+            .nonce(nonce)
+            .mac(mac)
+    }
+
+    /// Create a new builder for a response's `Server-Authorization` header.
+    pub fn server_authorization(mac: Mac) -> Self {
+        HeaderBuilder::new().kind(HeaderKind::ServerAuthorization).mac(mac)
+    }
+
+    /// Create a new builder for a `WWW-Authenticate` challenge, with the given `error`
+    /// attribute.  See `AuthFailure` for the usual values.
+    pub fn challenge<'a, S: Into<Option<&'a str>>>(error: S) -> Self {
+        HeaderBuilder::new().kind(HeaderKind::Challenge).error(error)
+    }
+
+    fn kind(mut self, kind: HeaderKind) -> Self {
+        self.1 = Some(kind);
+        self
+    }
+
+    /// Set the `id` attribute
+    pub fn id<'a, S: Into<Option<&'a str>>>(mut self, id: S) -> Self {
+        self.0.id = id.into().map(|v| v.to_string());
+        self
+    }
+
+    /// Set the `ts` attribute
+    pub fn ts<T: Into<Option<Timespec>>>(mut self, ts: T) -> Self {
+        self.0.ts = ts.into();
+        self
+    }
+
+    /// Set the `nonce` attribute
+    pub fn nonce<'a, S: Into<Option<&'a str>>>(mut self, nonce: S) -> Self {
+        self.0.nonce = nonce.into().map(|v| v.to_string());
+        self
+    }
+
+    /// Set the `mac` attribute
+    pub fn mac<M: Into<Option<Mac>>>(mut self, mac: M) -> Self {
+        self.0.mac = mac.into();
+        self
+    }
+
+    /// Set the `ext` attribute
+    pub fn ext<'a, S: Into<Option<&'a str>>>(mut self, ext: S) -> Self {
+        self.0.ext = ext.into().map(|v| v.to_string());
+        self
+    }
+
+    /// Set the `hash` attribute
+    pub fn hash<'a, H: Into<Option<&'a [u8]>>>(mut self, hash: H) -> Self {
+        self.0.hash = hash.into().map(|v| v.to_vec());
+        self
+    }
+
+    /// Set the `app` attribute
+    pub fn app<'a, S: Into<Option<&'a str>>>(mut self, app: S) -> Self {
+        self.0.app = app.into().map(|v| v.to_string());
+        self
+    }
+
+    /// Set the `dlg` attribute
+    pub fn dlg<'a, S: Into<Option<&'a str>>>(mut self, dlg: S) -> Self {
+        self.0.dlg = dlg.into().map(|v| v.to_string());
+        self
+    }
+
+    /// Set the `tsm` attribute
+    pub fn tsm<M: Into<Option<Mac>>>(mut self, tsm: M) -> Self {
+        self.0.tsm = tsm.into();
+        self
+    }
+
+    /// Set the `error` attribute
+    pub fn error<'a, S: Into<Option<&'a str>>>(mut self, error: S) -> Self {
+        self.0.error = error.into().map(|v| v.to_string());
+        self
+    }
+
+    /// Check the header's components and return the header.
+    pub fn build(self) -> Result<Header> {
+        let header = self.0;
+        if let Some(kind) = self.1 {
+            let set = [("id", header.id.is_some()),
+                       ("ts", header.ts.is_some()),
+                       ("nonce", header.nonce.is_some()),
+                       ("mac", header.mac.is_some()),
+                       ("ext", header.ext.is_some()),
+                       ("hash", header.hash.is_some()),
+                       ("app", header.app.is_some()),
+                       ("dlg", header.dlg.is_some()),
+                       ("tsm", header.tsm.is_some()),
+                       ("error", header.error.is_some())];
+            for &(attribute, is_set) in set.iter() {
+                if is_set && !kind.attributes().contains(&attribute) {
+                    return Err(Error::UnexpectedAttribute(attribute));
+                }
+            }
+        }
+        Ok(Header {
+            id: Header::check_component("id", header.id)?,
+            nonce: Header::check_component("nonce", header.nonce)?,
+            ext: Header::check_component("ext", header.ext)?,
+            app: Header::check_component("app", header.app)?,
+            dlg: Header::check_component("dlg", header.dlg)?,
+            error: Header::check_component("error", header.error)?,
+            ..header
         })
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::Header;
+    use super::{Header, HeaderBuilder};
+    use base64;
+    use error::Error;
     use time::Timespec;
     use std::str::FromStr;
     use mac::Mac;
+    use credentials::Key;
+    use ring::digest;
 
     #[test]
     fn illegal_id() {
-        assert!(Header::new(Some("ab\"cdef"),
-                            Some(Timespec::new(1234, 0)),
-                            Some("nonce"),
-                            Some(Mac::from(vec![])),
-                            Some("ext"),
-                            None,
-                            None,
-                            None)
-            .is_err());
+        match Header::new(Some("ab\"cdef"),
+                          Some(Timespec::new(1234, 0)),
+                          Some("nonce"),
+                          Some(Mac::from(vec![])),
+                          Some("ext"),
+                          None,
+                          None,
+                          None) {
+            Err(Error::InvalidCharacter { attribute: "id" }) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
     }
 
     #[test]
@@ -315,7 +595,43 @@ mod test {
                                       nonce=\"j4h3g2\", ext=\"some-app-ext-data\", \
                                       mac=\"6!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!AE=\", \
                                       app=\"my-app\", dlg=\"my-authority\"");
-        assert!(r.is_err());
+        match r {
+            Err(Error::InvalidAttribute { position: 82, attribute: "mac" }) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn from_str_invalid_ts() {
+        match Header::from_str("id=\"xyz\", ts=\"abc\"") {
+            Err(Error::InvalidAttribute { position: 14, attribute: "ts" }) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn from_str_mac_too_long() {
+        let s = format!("id=\"xyz\", mac=\"{}\"", base64::encode(&[0u8; 65][..]));
+        match Header::from_str(&s) {
+            Err(Error::InvalidAttribute { position: 15, attribute: "mac" }) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn from_str_unknown_attribute() {
+        match Header::from_str("id=\"xyz\", foo=\"abc\"") {
+            Err(Error::UnknownAttribute { position: 10, ref attribute }) if attribute == "foo" => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn from_str_unquoted() {
+        match Header::from_str("id=\"xyz\", ts=1353832234") {
+            Err(Error::HeaderSyntax { position: 13 }) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
     }
 
     #[test]
@@ -434,4 +750,148 @@ mod test {
         let s2 = Header::from_str(&formatted).unwrap();
         assert!(s2 == s);
     }
+
+    #[test]
+    fn from_str_challenge() {
+        let s = Header::from_str("ts=\"1353832234\", \
+                                  tsm=\"6R4rV5iE+NPoym+WwjeHzjAGXUtLNIxmo1vpMofpLAE=\", \
+                                  error=\"Stale timestamp\"")
+            .unwrap();
+        assert!(s.id == None);
+        assert!(s.ts == Some(Timespec::new(1353832234, 0)));
+        assert!(s.tsm ==
+                Some(Mac::from(vec![233, 30, 43, 87, 152, 132, 248, 211, 232, 202, 111, 150,
+                                    194, 55, 135, 206, 48, 6, 93, 75, 75, 52, 140, 102, 163,
+                                    91, 233, 50, 135, 233, 44, 1])));
+        assert!(s.error == Some("Stale timestamp".to_string()));
+    }
+
+    #[test]
+    fn to_str_challenge() {
+        let mut s = Header::new::<String>(None, None, None, None, None, None, None, None).unwrap();
+        s.ts = Some(Timespec::new(1353832234, 0));
+        s.tsm = Some(Mac::from(vec![8, 35, 182, 149, 42, 111, 33, 192, 19, 22, 94, 43, 118, 176,
+                                    65, 69, 86, 4, 156, 184, 85, 107, 249, 242, 172, 200, 66,
+                                    209, 57, 63, 38, 83]));
+        s.error = Some("Stale timestamp".to_string());
+        let formatted = format!("{}", s);
+        println!("got: {}", formatted);
+        assert!(formatted ==
+                "ts=\"1353832234\", tsm=\"CCO2lSpvIcATFl4rdrBBRVYEnLhVa/nyrMhC0Tk/JlM=\", \
+                 error=\"Stale timestamp\"");
+        let s2 = Header::from_str(&formatted).unwrap();
+        assert!(s2 == s);
+    }
+    #[test]
+    fn challenge_ts() {
+        let key = Key::new("tok", &digest::SHA256);
+        let ts = Timespec::new(1353832234, 0);
+        let mut s = Header::new::<String>(None, None, None, None, None, None, None, None).unwrap();
+        s.error = Some("Stale timestamp".to_string());
+        assert_eq!(s.challenge_ts(&key), None);
+
+        s.ts = Some(ts);
+        assert_eq!(s.challenge_ts(&key), None);
+
+        s.tsm = Some(Mac::new_ts(&key, ts).unwrap());
+        assert_eq!(s.challenge_ts(&key), Some(ts));
+        assert_eq!(s.challenge_ts(&Key::new("WRONG", &digest::SHA256)), None);
+
+        s.ts = Some(Timespec::new(1353832235, 0));
+        assert_eq!(s.challenge_ts(&key), None);
+    }
+    #[test]
+    fn builder_empty() {
+        let s = HeaderBuilder::new().build().unwrap();
+        assert!(s == Header::new::<String>(None, None, None, None, None, None, None, None)
+                         .unwrap());
+    }
+
+    #[test]
+    fn builder_authorization() {
+        let mac = Mac::from(vec![8, 35, 182, 149, 42, 111, 33, 192, 19, 22, 94, 43, 118, 176, 65,
+                                 69, 86, 4, 156, 184, 85, 107, 249, 242, 172, 200, 66, 209, 57,
+                                 63, 38, 83]);
+        let s = HeaderBuilder::authorization("dh37fgj492je",
+                                             Timespec::new(1353832234, 0),
+                                             "j4h3g2",
+                                             mac.clone())
+            .ext("my-ext-value")
+            .hash(&[1, 2, 3, 4][..])
+            .app("my-app")
+            .dlg("my-dlg")
+            .build()
+            .unwrap();
+        assert!(s ==
+                Header::new(Some("dh37fgj492je"),
+                            Some(Timespec::new(1353832234, 0)),
+                            Some("j4h3g2"),
+                            Some(mac),
+                            Some("my-ext-value"),
+                            Some(vec![1, 2, 3, 4]),
+                            Some("my-app"),
+                            Some("my-dlg"))
+                    .unwrap());
+    }
+
+    #[test]
+    fn builder_server_authorization() {
+        let s = HeaderBuilder::server_authorization(Mac::from(vec![1, 2, 3, 4]))
+            .ext("server-ext")
+            .hash(None)
+            .build()
+            .unwrap();
+        assert!(format!("{}", s) == "mac=\"AQIDBA==\", ext=\"server-ext\"");
+    }
+
+    #[test]
+    fn builder_challenge() {
+        let s = HeaderBuilder::challenge("Stale timestamp")
+            .ts(Timespec::new(1353832234, 0))
+            .tsm(Mac::from(vec![1, 2, 3, 4]))
+            .build()
+            .unwrap();
+        assert!(format!("{}", s) ==
+                "ts=\"1353832234\", tsm=\"AQIDBA==\", error=\"Stale timestamp\"");
+        let s = HeaderBuilder::challenge(None).build().unwrap();
+        assert!(format!("{}", s) == "");
+    }
+
+    #[test]
+    fn builder_illegal_components() {
+        assert!(HeaderBuilder::new().id("ab\"cdef").build().is_err());
+        assert!(HeaderBuilder::new().nonce("no\"nce").build().is_err());
+        assert!(HeaderBuilder::new().ext("ex\"t").build().is_err());
+        assert!(HeaderBuilder::new().app("a\"pp").build().is_err());
+        assert!(HeaderBuilder::new().dlg("d\"lg").build().is_err());
+        assert!(HeaderBuilder::challenge("err\"or").build().is_err());
+    }
+
+    #[test]
+    fn builder_unexpected_attributes() {
+        fn unexpected(builder: HeaderBuilder) -> &'static str {
+            match builder.build() {
+                Err(Error::UnexpectedAttribute(attribute)) => attribute,
+                r => panic!("unexpected result {:?}", r),
+            }
+        }
+
+        let mac = Mac::from(vec![1, 2, 3, 4]);
+        let ts = Timespec::new(1353832234, 0);
+        assert_eq!(unexpected(HeaderBuilder::authorization("id", ts, "nonce", mac.clone())
+                                  .tsm(mac.clone())),
+                   "tsm");
+        assert_eq!(unexpected(HeaderBuilder::server_authorization(mac.clone()).id("id")),
+                   "id");
+        assert_eq!(unexpected(HeaderBuilder::server_authorization(mac.clone()).nonce("nonce")),
+                   "nonce");
+        assert_eq!(unexpected(HeaderBuilder::server_authorization(mac.clone()).app("app")),
+                   "app");
+        assert_eq!(unexpected(HeaderBuilder::challenge("Bad mac").mac(mac.clone())),
+                   "mac");
+        assert_eq!(unexpected(HeaderBuilder::challenge("Bad mac").ext("ext")), "ext");
+
+        // a builder without a kind accepts anything
+        assert!(HeaderBuilder::new().id("id").tsm(mac).build().is_ok());
+    }
 }
@@ -0,0 +1,333 @@
+//! Support for requests and responses from the [`http`](https://docs.rs/http) crate, enabled
+//! with the `http` feature.
+//!
+//! This module builds a `RequestBuilder` from an `http::Request` or its `Parts`, and reads and
+//! writes the Hawk `Authorization` and `Server-Authorization` headers.  Libraries built on the
+//! `http` crate, such as hyper, can use these functions directly.
+//!
+//! # Examples
+//!
+//! ```
+//! extern crate hawk;
+//! extern crate http;
+//! extern crate time;
+//!
+//! use hawk::{Credentials, Key, SHA256};
+//!
+//! fn main() {
+//!     let credentials = Credentials {
+//!         id: "test-client".to_string(),
+//!         key: Key::new(vec![99u8; 32], &SHA256),
+//!     };
+//!
+//!     let mut req = http::Request::get("https://example.com/v1/users?limit=10")
+//!         .body(())
+//!         .unwrap();
+//!     let header = hawk::http::request_builder(&req, 443)
+//!         .unwrap()
+//!         .request()
+//!         .make_header(&credentials)
+//!         .unwrap();
+//!     hawk::http::set_authorization(&mut req, &header).unwrap();
+//!
+//!     // on the server, the same request is reconstructed and the header validated
+//!     let received = hawk::http::authorization(req.headers()).unwrap().unwrap();
+//!     let request = hawk::http::request_builder(&req, 443).unwrap().request();
+//!     assert!(request.validate_header(&received, &credentials.key, time::Duration::minutes(1)));
+//! }
+//! ```
+
+use http_crate::{self, HeaderMap, HeaderValue, Method, Uri};
+use http_crate::header::{AUTHORIZATION, HOST};
+use http_crate::request::Parts;
+use http_crate::uri::Authority;
+use header::Header;
+use binding::HeaderBinding;
+use request::RequestBuilder;
+use ring::digest::Algorithm;
+use error::*;
+use std::str::FromStr;
+
+pub mod server;
+
+/// The name of the `Server-Authorization` header.
+pub const SERVER_AUTHORIZATION: &'static str = "server-authorization";
+
+/// Create a `RequestBuilder` for the given request.
+///
+/// The host and port are taken from the URI's authority (which, for HTTP/2, is the
+/// `:authority` pseudo-header) or, failing that, from the `Host` header.  IPv6 hosts retain
+/// their brackets, as in `[::1]`, matching `Url::host_str`.  If neither gives a port, the
+/// default port for the URI's scheme is used, or `default_port` if the URI has no scheme, as is
+/// typical of requests received by a server.  A server behind a TLS terminator should pass 443
+/// here.
+///
+/// The resource is the URI's path and query.
+pub fn request_builder<'a, B>(req: &'a http_crate::Request<B>,
+                              default_port: u16)
+                              -> Result<RequestBuilder<'a>> {
+    from_components(req.method(), req.uri(), req.headers(), default_port)
+}
+
+/// Create a `RequestBuilder` for the given request parts, as for `request_builder`.
+pub fn request_builder_from_parts<'a>(parts: &'a Parts,
+                                      default_port: u16)
+                                      -> Result<RequestBuilder<'a>> {
+    from_components(&parts.method, &parts.uri, &parts.headers, default_port)
+}
+
+fn from_components<'a>(method: &'a Method,
+                       uri: &'a Uri,
+                       headers: &'a HeaderMap,
+                       default_port: u16)
+                       -> Result<RequestBuilder<'a>> {
+    let (host, port) = match uri.authority() {
+        Some(authority) => (authority.host(), authority.port_u16()),
+        None => {
+            let host = headers.get(HOST)
+                .ok_or_else(|| Error::UrlMissingHost(uri.to_string()))?;
+            let host = host.to_str().map_err(|_| Error::InvalidHeaderValue)?;
+            let authority = Authority::from_str(host)
+                .map_err(|_| Error::InvalidHost(host.to_string()))?;
+            // find the host within the header value, so that it can be borrowed
+            let start = host.rfind('@').map(|i| i + 1).unwrap_or(0);
+            (&host[start..start + authority.host().len()], authority.port_u16())
+        }
+    };
+    if host.is_empty() {
+        return Err(Error::UrlMissingHost(uri.to_string()));
+    }
+
+    let port = match port {
+        Some(port) => port,
+        None => {
+            match uri.scheme_str() {
+                Some("https") => 443,
+                Some("http") => 80,
+                Some(_) => return Err(Error::UrlMissingPort(uri.to_string())),
+                None => default_port,
+            }
+        }
+    };
+
+    let path = uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+
+    Ok(RequestBuilder::new(method.as_str(), host, port, path))
+}
+
+/// Set the `Authorization` header of the given request to the given Hawk header.
+pub fn set_authorization<B>(req: &mut http_crate::Request<B>, header: &Header) -> Result<()> {
+    req.headers_mut().insert(AUTHORIZATION, header_value(header)?);
+    Ok(())
+}
+
+/// Set the `Server-Authorization` header of the given response to the given Hawk header.
+pub fn set_server_authorization<B>(res: &mut http_crate::Response<B>,
+                                   header: &Header)
+                                   -> Result<()> {
+    res.headers_mut().insert(SERVER_AUTHORIZATION, header_value(header)?);
+    Ok(())
+}
+
+/// Get the Hawk `Authorization` header from the given headers, if any.  A header with a scheme
+/// other than Hawk is treated as absent.
+pub fn authorization(headers: &HeaderMap) -> Result<Option<Header>> {
+    parse_header(headers.get(AUTHORIZATION))
+}
+
+/// Get the Hawk `Server-Authorization` header from the given headers, such as those of a
+/// response, if any.
+pub fn server_authorization(headers: &HeaderMap) -> Result<Option<Header>> {
+    parse_header(headers.get(SERVER_AUTHORIZATION))
+}
+
+/// Bind the named headers of the given request headers into a `HeaderBinding`, whose `ext` value
+/// is then given to `RequestBuilder::ext`.  Servers using `server::authenticate` check the
+/// binding if `ServerConfig::header_bindings` is enabled.  Only headers whose values are visible ASCII can be bound; this returns
+/// `InvalidHeaderBinding` if any named header has another value.
+pub fn bind_headers<S>(headers: &HeaderMap,
+                       names: &[S],
+                       algorithm: &'static Algorithm)
+                       -> Result<HeaderBinding>
+    where S: AsRef<str>
+{
+    if !names.iter().all(|name| bindable(headers, name.as_ref())) {
+        return Err(Error::InvalidHeaderBinding);
+    }
+    HeaderBinding::new(names, algorithm, |name| header_values(headers, name))
+}
+
+/// Check that every value of the named header is visible ASCII, so can be bound.
+fn bindable(headers: &HeaderMap, name: &str) -> bool {
+    headers.get_all(name).iter().all(|v| v.to_str().is_ok())
+}
+
+/// Get the values of the named header, which `bindable` has checked.
+fn header_values(headers: &HeaderMap, name: &str) -> Vec<String> {
+    headers.get_all(name)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+        .collect()
+}
+
+/// Make the value of an `Authorization` or `Server-Authorization` header carrying the given Hawk
+/// header, for clients whose requests are not `http::Request`s.
+pub fn header_value(header: &Header) -> Result<HeaderValue> {
+    HeaderValue::from_str(&format!("Hawk {}", header)).map_err(|_| Error::InvalidHeaderValue)
+}
+
+fn parse_header(value: Option<&HeaderValue>) -> Result<Option<Header>> {
+    let value = match value {
+        Some(value) => value.to_str().map_err(|_| Error::InvalidHeaderValue)?,
+        None => return Ok(None),
+    };
+    if !value.starts_with("Hawk ") {
+        return Ok(None);
+    }
+    Header::from_str(&value[5..]).map(Some)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use http_crate::{self, Request};
+    use credentials::{Credentials, Key};
+    use ring::digest;
+    use time;
+
+    fn parts(req: Request<()>) -> Parts {
+        req.into_parts().0
+    }
+
+    #[test]
+    fn test_client_request() {
+        let req = Request::post("https://example.com/v1/users?limit=10").body(()).unwrap();
+        let request = request_builder(&req, 80).unwrap().request();
+        let expected = RequestBuilder::new("POST", "example.com", 443, "/v1/users?limit=10")
+            .request();
+        assert_eq!(format!("{:?}", request), format!("{:?}", expected));
+    }
+
+    #[test]
+    fn test_explicit_port() {
+        let req = Request::get("http://example.com:8080/").body(()).unwrap();
+        let request = request_builder(&req, 443).unwrap().request();
+        let expected = RequestBuilder::new("GET", "example.com", 8080, "/").request();
+        assert_eq!(format!("{:?}", request), format!("{:?}", expected));
+    }
+
+    #[test]
+    fn test_server_request_host_header() {
+        let req = Request::get("/resource?a=b").header("Host", "example.com").body(()).unwrap();
+        let parts = parts(req);
+        let request = request_builder_from_parts(&parts, 443).unwrap().request();
+        let expected = RequestBuilder::new("GET", "example.com", 443, "/resource?a=b").request();
+        assert_eq!(format!("{:?}", request), format!("{:?}", expected));
+    }
+
+    #[test]
+    fn test_server_request_host_header_port() {
+        let req = Request::get("/").header("Host", "example.com:9999").body(()).unwrap();
+        let request = request_builder(&req, 443).unwrap().request();
+        let expected = RequestBuilder::new("GET", "example.com", 9999, "/").request();
+        assert_eq!(format!("{:?}", request), format!("{:?}", expected));
+    }
+
+    #[test]
+    fn test_ipv6() {
+        let req = Request::get("/").header("Host", "[::1]:9999").body(()).unwrap();
+        let request = request_builder(&req, 443).unwrap().request();
+        let expected = RequestBuilder::new("GET", "[::1]", 9999, "/").request();
+        assert_eq!(format!("{:?}", request), format!("{:?}", expected));
+
+        let req = Request::get("http://[::1]/").body(()).unwrap();
+        let request = request_builder(&req, 443).unwrap().request();
+        let expected = RequestBuilder::new("GET", "[::1]", 80, "/").request();
+        assert_eq!(format!("{:?}", request), format!("{:?}", expected));
+    }
+
+    #[test]
+    fn test_http2_authority() {
+        // HTTP/2 requests carry `:authority`, which appears as the URI's authority
+        let req = Request::get("https://example.com:8443/x")
+            .version(http_crate::Version::HTTP_2)
+            .body(())
+            .unwrap();
+        let request = request_builder(&req, 443).unwrap().request();
+        let expected = RequestBuilder::new("GET", "example.com", 8443, "/x").request();
+        assert_eq!(format!("{:?}", request), format!("{:?}", expected));
+    }
+
+    #[test]
+    fn test_missing_host() {
+        let req = Request::get("/").body(()).unwrap();
+        match request_builder(&req, 443) {
+            Err(Error::UrlMissingHost(_)) => {}
+            r => panic!("unexpected result {:?}", r.map(|b| b.request())),
+        }
+    }
+
+    #[test]
+    fn test_invalid_host() {
+        let req = Request::get("/").header("Host", "exa mple.com").body(()).unwrap();
+        match request_builder(&req, 443) {
+            Err(Error::InvalidHost(_)) => {}
+            r => panic!("unexpected result {:?}", r.map(|b| b.request())),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let credentials = Credentials {
+            id: "me".to_string(),
+            key: Key::new(vec![99u8; 32], &digest::SHA256),
+        };
+        let mut req = Request::get("https://example.com/resource").body(()).unwrap();
+        let header = request_builder(&req, 443)
+            .unwrap()
+            .request()
+            .make_header(&credentials)
+            .unwrap();
+        set_authorization(&mut req, &header).unwrap();
+
+        let received = authorization(req.headers()).unwrap().unwrap();
+        assert_eq!(format!("{}", received), format!("{}", header));
+
+        // as received by a server, without scheme or authority in the URI
+        let (mut parts, _) = req.into_parts();
+        parts.uri = "/resource".parse().unwrap();
+        parts.headers.insert(HOST, HeaderValue::from_static("example.com"));
+        let request = request_builder_from_parts(&parts, 443).unwrap().request();
+        assert!(request.validate_header(&received, &credentials.key, time::Duration::minutes(1)));
+    }
+
+    #[test]
+    fn test_server_authorization() {
+        let mut res = http_crate::Response::new(());
+        assert_eq!(server_authorization(res.headers()).unwrap(), None);
+
+        let header = Header::from_str("mac=\"AQIDBA==\", ext=\"x\"").unwrap();
+        set_server_authorization(&mut res, &header).unwrap();
+        assert_eq!(res.headers()[SERVER_AUTHORIZATION],
+                   "Hawk mac=\"AQIDBA==\", ext=\"x\"");
+        assert_eq!(server_authorization(res.headers()).unwrap(), Some(header));
+    }
+
+    #[test]
+    fn test_other_scheme() {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Basic Zm9vOmJhcg=="));
+        assert_eq!(authorization(&headers).unwrap(), None);
+    }
+
+    #[test]
+    fn test_bind_opaque_value() {
+        let mut headers = HeaderMap::new();
+        headers.insert("idempotency-key", HeaderValue::from_bytes(b"caf\xc3\xa9").unwrap());
+        match bind_headers(&headers, &["Idempotency-Key"], &digest::SHA256) {
+            Err(Error::InvalidHeaderBinding) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+}
@@ -0,0 +1,1083 @@
+//! Framework-neutral server-side authentication of `http` requests.
+//!
+//! Integrations for particular servers (such as `hyper1-hawk`) use `authenticate` to check a
+//! request's Hawk `Authorization` header or bewit, `Rejection::response` to build the response
+//! for a request that fails, and `Verified::server_authorization` or `SignedBody` to sign
+//! responses.
+
+use std::error;
+use std::fmt;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use bytes::Bytes;
+use http_body::{Body, Frame, SizeHint};
+use http_crate::{self, HeaderMap, HeaderValue, Method, StatusCode};
+use http_crate::header::{CONTENT_TYPE, WWW_AUTHENTICATE};
+use http_crate::request::Parts;
+use time::Duration;
+use bewit::{Bewit, BewitPolicy};
+use credentials::Key;
+use failure::AuthFailure;
+use forwarded::TrustedProxies;
+use header::Header;
+use binding::HeaderBinding;
+use payload::{self, PayloadHasher};
+use request::RequestBuilder;
+use response::ResponseBuilder;
+use error::*;
+use super::{SERVER_AUTHORIZATION, authorization, bindable, header_value, header_values,
+            request_builder_from_parts};
+
+/// A source of keys for Hawk ids, such as a database of clients.
+///
+/// This is implemented for closures, so `|id: &str| clients.get(id).cloned()` works where
+/// `clients` is a `HashMap<String, Arc<Key>>`.
+pub trait KeyLookup: Send + Sync {
+    /// Get the key for the given id, or `None` if the id is not known.
+    fn key(&self, id: &str) -> Option<Arc<Key>>;
+}
+
+impl<F> KeyLookup for F
+    where F: Fn(&str) -> Option<Arc<Key>> + Send + Sync
+{
+    fn key(&self, id: &str) -> Option<Arc<Key>> {
+        self(id)
+    }
+}
+
+/// The identity of an authenticated client.  Server integrations add this to the request's
+/// extensions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Authenticated {
+    /// The client's Hawk id
+    pub id: String,
+    /// The `ext` value from the header or bewit, less any `HeaderBinding`
+    pub ext: Option<String>,
+    /// The `app` value from the header
+    pub app: Option<String>,
+    /// The `dlg` value from the header
+    pub dlg: Option<String>,
+    /// True if the request was authenticated with a bewit rather than a header
+    pub bewit: bool,
+    /// The configured host alias the request was signed for, if `ServerConfig::host_aliases`
+    /// is in use
+    pub alias: Option<(String, u16)>,
+    /// The names of the headers the client bound into the signature with a `HeaderBinding`,
+    /// which have been checked, in lower case
+    pub bound_headers: Vec<String>,
+}
+
+/// The address of the peer that sent a request.  Server integrations add this to the request's
+/// extensions, where `authenticate` uses it to decide whether to trust forwarding headers; see
+/// `ServerConfig::trusted_proxies`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerAddr(pub IpAddr);
+
+/// Configuration for server-side authentication.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    default_port: u16,
+    ts_skew: Duration,
+    bewits: bool,
+    bewit_policy: BewitPolicy,
+    trusted_proxies: Option<Arc<TrustedProxies>>,
+    host_aliases: Option<Arc<Vec<(String, u16)>>>,
+    header_bindings: bool,
+}
+
+impl ServerConfig {
+    /// Create a new configuration.  The `default_port` is used for requests whose `Host` header
+    /// has no port; see `request_builder`.  By default, a timestamp skew of one minute is
+    /// allowed, bewits are accepted and header bindings are not checked.
+    pub fn new(default_port: u16) -> Self {
+        ServerConfig {
+            default_port: default_port,
+            ts_skew: Duration::minutes(1),
+            bewits: true,
+            bewit_policy: BewitPolicy::new(),
+            trusted_proxies: None,
+            host_aliases: None,
+            header_bindings: false,
+        }
+    }
+
+    /// Set the allowed difference between the client's and the server's clocks.
+    pub fn ts_skew(mut self, ts_skew: Duration) -> Self {
+        self.ts_skew = ts_skew;
+        self
+    }
+
+    /// Set whether requests may be authenticated with a bewit.
+    pub fn bewits(mut self, bewits: bool) -> Self {
+        self.bewits = bewits;
+        self
+    }
+
+    /// Restrict the bewits accepted to those allowed by the given policy, limiting their lifetime
+    /// or revoking some of them.
+    pub fn bewit_policy(mut self, bewit_policy: BewitPolicy) -> Self {
+        self.bewit_policy = bewit_policy;
+        self
+    }
+
+    /// Take the host, port and scheme of requests from the given proxies from the forwarding
+    /// headers they add, as described for `TrustedProxies`.  The peer address is found in the
+    /// request's `PeerAddr` extension; requests without one are treated as coming from an
+    /// untrusted peer.
+    pub fn trusted_proxies(mut self, trusted_proxies: TrustedProxies) -> Self {
+        self.trusted_proxies = Some(Arc::new(trusted_proxies));
+        self
+    }
+
+    /// Accept requests signed for any of the given `(host, port)` pairs, such as the several
+    /// public names of a service, instead of the host and port derived from the request.  The
+    /// alias that matched is reported in `Authenticated::alias`, and used to sign the response.
+    pub fn host_aliases<H, I>(mut self, aliases: I) -> Self
+        where H: Into<String>,
+              I: IntoIterator<Item = (H, u16)>
+    {
+        let aliases = aliases.into_iter().map(|(host, port)| (host.into(), port)).collect();
+        self.host_aliases = Some(Arc::new(aliases));
+        self
+    }
+
+    /// Set whether to check the headers that clients bind into `ext` with a `HeaderBinding`.
+    /// When enabled, an `ext` value beginning with the binding's `hb1:` prefix must carry a
+    /// well-formed binding that matches the request's headers, and `Authenticated::ext` is the
+    /// application's value following it.  When disabled, as by default, `ext` is passed through
+    /// unchanged.
+    pub fn header_bindings(mut self, header_bindings: bool) -> Self {
+        self.header_bindings = header_bindings;
+        self
+    }
+
+    /// Get the default port for requests without an explicit port.
+    pub fn default_port(&self) -> u16 {
+        self.default_port
+    }
+}
+
+/// A successfully authenticated request.
+pub struct Verified {
+    client: Authenticated,
+    key: Arc<Key>,
+    // for requests authenticated with a header, the basis of the `Server-Authorization` header
+    response: Option<ResponseBuilder<'static>>,
+}
+
+impl Verified {
+    /// Get the authenticated client.
+    pub fn client(&self) -> &Authenticated {
+        &self.client
+    }
+
+    /// Get the authenticated client, discarding the information needed to sign a response.
+    pub fn into_client(self) -> Authenticated {
+        self.client
+    }
+
+    /// Create a `Server-Authorization` header for the response to this request, including a
+    /// hash of the response payload if `payload` (its content type and body) is given.
+    ///
+    /// Requests authenticated with a bewit have no `Server-Authorization`, so this returns
+    /// `None` for them.
+    pub fn server_authorization(&self, payload: Option<(&str, &[u8])>) -> Result<Option<Header>> {
+        let response = match self.response {
+            Some(ref response) => response.clone().response(),
+            None => return Ok(None),
+        };
+        match payload {
+            Some((content_type, body)) => {
+                response.make_header_with_payload(&self.key, content_type, body).map(Some)
+            }
+            None => response.make_header(&self.key).map(Some),
+        }
+    }
+
+    /// Create a `PayloadHasher` for a response with the given `Content-Type`, for a response
+    /// body that is hashed as it is streamed rather than buffered.
+    pub fn response_hasher(&self, content_type: &str) -> PayloadHasher {
+        PayloadHasher::new(payload::content_type(content_type), self.key.algorithm())
+    }
+
+    /// Create a `Server-Authorization` header for the response to this request, including the
+    /// given payload hash, as calculated with `response_hasher`.  This returns `None` for
+    /// requests authenticated with a bewit, as for `server_authorization`.
+    pub fn server_authorization_with_hash(&self, hash: &[u8]) -> Result<Option<Header>> {
+        let response = match self.response {
+            Some(ref response) => response.clone().hash(hash).response(),
+            None => return Ok(None),
+        };
+        response.make_header(&self.key).map(Some)
+    }
+}
+
+impl fmt::Debug for Verified {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Verified")
+            .field("client", &self.client)
+            .finish()
+    }
+}
+
+/// A response body that is hashed as it is sent, and followed by a `Server-Authorization`
+/// trailer including that hash.  This lets servers sign a streamed response with a payload hash
+/// without buffering it.
+///
+/// Any trailers from the inner body are passed on, with `Server-Authorization` added; if the
+/// inner body has none, a trailers frame is added after it.  Requests authenticated with a
+/// bewit have no `Server-Authorization`, so their responses get no trailer.  If the response
+/// cannot be signed, the body ends with an error rather than an unsigned trailer.
+pub struct SignedBody<B> {
+    inner: Pin<Box<B>>,
+    // the request's verification and the running hash of the body, until the trailer is sent
+    signer: Option<(Verified, PayloadHasher)>,
+}
+
+impl<B> SignedBody<B> {
+    /// Sign the given response body, which has the given `Content-Type`.
+    pub fn new(body: B, verified: Verified, content_type: &str) -> Self {
+        let hasher = verified.response_hasher(content_type);
+        SignedBody {
+            inner: Box::pin(body),
+            signer: Some((verified, hasher)),
+        }
+    }
+
+    /// Pass the given body through unchanged, without a trailer.
+    pub fn unsigned(body: B) -> Self {
+        SignedBody {
+            inner: Box::pin(body),
+            signer: None,
+        }
+    }
+
+    /// Add `Server-Authorization` to the given trailers, finishing the hash.
+    fn sign(&mut self, trailers: &mut HeaderMap) -> Result<()> {
+        if let Some((verified, hasher)) = self.signer.take() {
+            if let Some(header) = verified.server_authorization_with_hash(&hasher.finish())? {
+                trailers.insert(SERVER_AUTHORIZATION, header_value(&header)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<B> Body for SignedBody<B>
+    where B: Body<Data = Bytes>,
+          B::Error: Into<Box<dyn error::Error + Send + Sync>>
+{
+    type Data = Bytes;
+    type Error = Box<dyn error::Error + Send + Sync>;
+
+    fn poll_frame(self: Pin<&mut Self>,
+                  cx: &mut Context)
+                  -> Poll<Option<::std::result::Result<Frame<Bytes>, Self::Error>>> {
+        let this = self.get_mut();
+        let frame = match this.inner.as_mut().poll_frame(cx) {
+            Poll::Ready(Some(Ok(frame))) => frame,
+            Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e.into()))),
+            Poll::Ready(None) if this.signer.is_some() => {
+                let mut trailers = HeaderMap::new();
+                let frame = this.sign(&mut trailers).map(|_| Frame::trailers(trailers));
+                return Poll::Ready(Some(frame.map_err(Into::into)));
+            }
+            Poll::Ready(None) => return Poll::Ready(None),
+            Poll::Pending => return Poll::Pending,
+        };
+        let frame = match frame.into_data() {
+            Ok(data) => {
+                if let Some((_, ref mut hasher)) = this.signer {
+                    hasher.update(&data);
+                }
+                Frame::data(data)
+            }
+            Err(frame) => {
+                match frame.into_trailers() {
+                    Ok(mut trailers) => {
+                        if let Err(e) = this.sign(&mut trailers) {
+                            return Poll::Ready(Some(Err(e.into())));
+                        }
+                        Frame::trailers(trailers)
+                    }
+                    Err(frame) => frame,
+                }
+            }
+        };
+        Poll::Ready(Some(Ok(frame)))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.signer.is_none() && self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        if self.signer.is_some() {
+            // an exact size would let servers send a `Content-Length`, leaving no room for
+            // trailers
+            let mut hint = SizeHint::new();
+            hint.set_lower(self.inner.size_hint().lower());
+            hint
+        } else {
+            self.inner.size_hint()
+        }
+    }
+}
+
+/// A request that failed authentication.
+#[derive(Clone)]
+pub struct Rejection {
+    failure: AuthFailure,
+    key: Option<Arc<Key>>,
+}
+
+impl Rejection {
+    /// Get the reason the request was rejected.
+    pub fn failure(&self) -> AuthFailure {
+        self.failure
+    }
+
+    /// Build a response reporting this failure, with the given body.
+    ///
+    /// Failures with status 401 carry a `WWW-Authenticate` challenge; for a stale timestamp, it
+    /// includes the server's time, signed with the client's key.
+    pub fn response<B>(&self, body: B) -> http_crate::Response<B> {
+        let mut res = http_crate::Response::new(body);
+        *res.status_mut() = StatusCode::from_u16(self.failure.status_code())
+            .unwrap_or(StatusCode::UNAUTHORIZED);
+        if self.failure.status_code() == 401 {
+            let key = self.key.as_ref().map(|k| &**k);
+            let challenge = self.failure
+                .make_header(key)
+                .ok()
+                .and_then(|h| {
+                    let value = format!("{}", h);
+                    let value = if value.is_empty() {
+                        "Hawk".to_string()
+                    } else {
+                        format!("Hawk {}", value)
+                    };
+                    HeaderValue::from_str(&value).ok()
+                })
+                .unwrap_or_else(|| HeaderValue::from_static("Hawk"));
+            res.headers_mut().insert(WWW_AUTHENTICATE, challenge);
+        }
+        res
+    }
+}
+
+impl fmt::Debug for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Rejection").field("failure", &self.failure).finish()
+    }
+}
+
+impl From<AuthFailure> for Rejection {
+    fn from(failure: AuthFailure) -> Self {
+        Rejection {
+            failure: failure,
+            key: None,
+        }
+    }
+}
+
+/// The default limit, in bytes, integrations use on the size of request bodies they buffer to
+/// verify a payload hash.
+pub const DEFAULT_PAYLOAD_LIMIT: usize = 2 * 1024 * 1024;
+
+/// Determine whether `authenticate` needs the request body for the given request, because its
+/// `Authorization` header includes a payload hash.  Integrations can use this to avoid
+/// buffering bodies unnecessarily.
+pub fn needs_body(parts: &Parts) -> bool {
+    match authorization(&parts.headers) {
+        Ok(Some(header)) => header.hash.is_some(),
+        _ => false,
+    }
+}
+
+/// Authenticate a request using its `Authorization` header or `bewit` query parameter.
+///
+/// If the header includes a payload hash, it is verified against `body` and the request's
+/// `Content-Type`.  `body` must be `Some` whenever `needs_body` returns true: a request whose
+/// header has a hash is rejected with `BadPayloadHash` if its body is not supplied.
+pub fn authenticate(parts: &Parts,
+                    body: Option<&[u8]>,
+                    keys: &dyn KeyLookup,
+                    config: &ServerConfig)
+                    -> ::std::result::Result<Verified, Rejection> {
+    let header = authorization(&parts.headers).map_err(|_| AuthFailure::InvalidHeaderSyntax)?;
+    let bewit = if config.bewits {
+        let resource = parts.uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+        match Bewit::from_resource(resource) {
+            Ok(bewit) => bewit,
+            Err(_) => {
+                check_bewit_method(&parts.method)?;
+                return Err(AuthFailure::InvalidBewit.into());
+            }
+        }
+    } else {
+        None
+    };
+
+    match (header, bewit) {
+        (Some(_), Some(_)) => Err(AuthFailure::MultipleAuthentications.into()),
+        (None, None) => Err(AuthFailure::MissingAuthentication.into()),
+        (Some(header), None) => authenticate_header(parts, body, header, keys, config),
+        (None, Some((bewit, resource))) => {
+            check_bewit_method(&parts.method)?;
+            authenticate_bewit(parts, bewit, resource, keys, config)
+        }
+    }
+}
+
+fn check_bewit_method(method: &Method) -> ::std::result::Result<(), Rejection> {
+    if *method != Method::GET && *method != Method::HEAD {
+        return Err(AuthFailure::InvalidMethod.into());
+    }
+    Ok(())
+}
+
+/// Build the Hawk request for the given parts, applying any forwarding headers from a trusted
+/// proxy.
+fn request_builder<'a>(parts: &'a Parts,
+                       config: &ServerConfig)
+                       -> ::std::result::Result<RequestBuilder<'a>, Rejection> {
+    let builder = request_builder_from_parts(parts, config.default_port)
+        .map_err(|_| AuthFailure::InvalidHost)?;
+    let proxies = match config.trusted_proxies {
+        Some(ref proxies) => proxies,
+        None => return Ok(builder),
+    };
+    let peer = match parts.extensions.get::<PeerAddr>() {
+        Some(peer) => peer.0,
+        None => return Ok(builder),
+    };
+    let forwarded = proxies.forwarded(peer, |name| {
+        parts.headers
+            .get_all(name)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .map(|v| v.to_string())
+            .collect()
+    });
+    Ok(match forwarded {
+           Some(forwarded) => forwarded.apply(builder),
+           None => builder,
+       })
+}
+
+fn authenticate_header(parts: &Parts,
+                       body: Option<&[u8]>,
+                       header: Header,
+                       keys: &dyn KeyLookup,
+                       config: &ServerConfig)
+                       -> ::std::result::Result<Verified, Rejection> {
+    let id = match (&header.id, &header.ts, &header.nonce, &header.mac) {
+        (&Some(ref id), &Some(_), &Some(_), &Some(_)) => id.clone(),
+        _ => return Err(AuthFailure::MissingAttributes.into()),
+    };
+    let key = keys.key(&id).ok_or(AuthFailure::UnknownCredentials)?;
+    let builder = request_builder(parts, config)?;
+
+    let hash = match (body, &header.hash) {
+        (Some(body), &Some(_)) => {
+            let content_type = parts.headers
+                .get(CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("");
+            Some(PayloadHasher::hash(payload::content_type(content_type), key.algorithm(), body))
+        }
+        // the hash cannot be verified without the body
+        (None, &Some(_)) => return Err(AuthFailure::BadPayloadHash.into()),
+        (_, &None) => None,
+    };
+    let builder = builder.hash(hash.as_ref().map(|h| &h[..]));
+    let request = builder.clone().request();
+
+    let checked = match config.host_aliases {
+        Some(ref aliases) => {
+            request.check_header_aliases(&header, &key, config.ts_skew, aliases)
+                .map(|i| Some(aliases[i].clone()))
+        }
+        None => request.check_header(&header, &key, config.ts_skew).map(|_| None),
+    };
+    let alias = match checked {
+        Ok(alias) => alias,
+        Err(failure) => {
+            return Err(Rejection {
+                           failure: failure,
+                           key: Some(key),
+                       })
+        }
+    };
+
+    // headers bound into `ext` are checked only once the MAC shows the client sent the binding
+    let (ext, bound_headers) = match header.ext {
+        Some(ref ext) if config.header_bindings => {
+            match HeaderBinding::from_ext(ext) {
+                Ok(Some((binding, app_ext))) => {
+                    let headers = &parts.headers;
+                    if !binding.names().iter().all(|name| bindable(headers, name)) ||
+                       !binding.check(key.algorithm(), |name| header_values(headers, name)) {
+                        return Err(Rejection {
+                                       failure: AuthFailure::BadHeaderBinding,
+                                       key: Some(key),
+                                   });
+                    }
+                    (app_ext.map(|e| e.to_string()), binding.names().to_vec())
+                }
+                Ok(None) => (Some(ext.clone()), vec![]),
+                Err(_) => return Err(AuthFailure::BadHeaderBinding.into()),
+            }
+        }
+        _ => (header.ext.clone(), vec![]),
+    };
+
+    // the response is signed for the host and port the client used
+    let request = match alias {
+        Some((ref host, port)) => builder.host(host.clone()).port(port).request(),
+        None => request,
+    };
+    let response = request.make_response_builder(&header).into_owned();
+    Ok(Verified {
+           client: Authenticated {
+               id: id,
+               ext: ext,
+               app: header.app.clone(),
+               dlg: header.dlg.clone(),
+               bewit: false,
+               alias: alias,
+               bound_headers: bound_headers,
+           },
+           key: key,
+           response: Some(response),
+       })
+}
+
+fn authenticate_bewit(parts: &Parts,
+                      bewit: Bewit<'static>,
+                      resource: String,
+                      keys: &dyn KeyLookup,
+                      config: &ServerConfig)
+                      -> ::std::result::Result<Verified, Rejection> {
+    let key = keys.key(bewit.id()).ok_or(AuthFailure::UnknownCredentials)?;
+    let request = request_builder(parts, config)?
+        .path(resource)
+        .request();
+
+    let alias = match config.host_aliases {
+        Some(ref aliases) => {
+            Some(aliases[request.check_bewit_aliases(&bewit, &key, aliases)?].clone())
+        }
+        None => {
+            request.check_bewit(&bewit, &key)?;
+            None
+        }
+    };
+    config.bewit_policy.check(&bewit)?;
+
+    Ok(Verified {
+           client: Authenticated {
+               id: bewit.id().to_string(),
+               ext: bewit.ext().map(|e| e.to_string()),
+               app: None,
+               dlg: None,
+               bewit: true,
+               alias: alias,
+               bound_headers: vec![],
+           },
+           key: key,
+           response: None,
+       })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+    use std::collections::HashMap;
+    use http_crate::Request as HttpRequest;
+    use credentials::Credentials;
+    use request::RequestBuilder;
+    use ring::digest;
+    use http::set_authorization;
+
+    /// Parse a Hawk header value, skipping the "Hawk " prefix.
+    fn parse(value: &HeaderValue) -> Header {
+        Header::from_str(&value.to_str().unwrap()[5..]).unwrap()
+    }
+
+    fn credentials() -> Credentials {
+        Credentials {
+            id: "me".to_string(),
+            key: Key::new(vec![99u8; 32], &digest::SHA256),
+        }
+    }
+
+    fn keys() -> HashMap<String, Arc<Key>> {
+        let mut keys = HashMap::new();
+        keys.insert("me".to_string(), Arc::new(credentials().key));
+        keys
+    }
+
+    /// Sign a request as a client would, then strip the scheme and authority as a server would
+    /// see it.
+    fn signed(method: &str, path: &str, body: Option<(&str, &[u8])>) -> Parts {
+        let url = format!("http://example.com{}", path);
+        let mut req = HttpRequest::builder().method(method).uri(&url[..]);
+        if let Some((content_type, _)) = body {
+            req = req.header(CONTENT_TYPE, content_type);
+        }
+        let mut req = req.body(()).unwrap();
+        let hash = body.map(|(content_type, body)| {
+                                PayloadHasher::hash(payload::content_type(content_type),
+                                                    &digest::SHA256,
+                                                    body)
+                            });
+        let header = ::http::request_builder(&req, 80)
+            .unwrap()
+            .hash(hash.as_ref().map(|h| &h[..]))
+            .request()
+            .make_header(&credentials())
+            .unwrap();
+        set_authorization(&mut req, &header).unwrap();
+        server_parts(req, path)
+    }
+
+    fn server_parts(req: HttpRequest<()>, path: &str) -> Parts {
+        let (mut parts, _) = req.into_parts();
+        parts.uri = path.parse().unwrap();
+        parts.headers.insert("host", HeaderValue::from_static("example.com"));
+        parts
+    }
+
+    fn authenticate(parts: &Parts, body: Option<&[u8]>) -> ::std::result::Result<Verified, Rejection> {
+        authenticate_with(parts, body, &ServerConfig::new(80))
+    }
+
+    fn authenticate_with(parts: &Parts,
+                         body: Option<&[u8]>,
+                         config: &ServerConfig)
+                         -> ::std::result::Result<Verified, Rejection> {
+        let keys = keys();
+        super::authenticate(parts, body, &move |id: &str| keys.get(id).cloned(), config)
+    }
+
+    fn bindings() -> ServerConfig {
+        ServerConfig::new(80).header_bindings(true)
+    }
+
+    fn failure(parts: &Parts, body: Option<&[u8]>) -> AuthFailure {
+        authenticate(parts, body).unwrap_err().failure()
+    }
+
+    #[test]
+    fn test_header() {
+        let parts = signed("GET", "/resource?x=1", None);
+        let verified = authenticate(&parts, None).unwrap();
+        assert_eq!(verified.client(),
+                   &Authenticated {
+                        id: "me".to_string(),
+                        ext: None,
+                        app: None,
+                        dlg: None,
+                        bewit: false,
+                        alias: None,
+                        bound_headers: vec![],
+                    });
+
+        // the server authorization validates on the client
+        let server_header = verified.server_authorization(Some(("text/plain", b"OK"))).unwrap();
+        let client_header = ::http::authorization(&parts.headers).unwrap().unwrap();
+        let request = RequestBuilder::new("GET", "example.com", 80, "/resource?x=1").request();
+        let response = request.make_response_builder(&client_header).response();
+        assert!(response.validate_header_with_payload(&server_header.unwrap(),
+                                                      &credentials().key,
+                                                      "text/plain",
+                                                      b"OK"));
+    }
+
+    #[test]
+    fn test_plain_ext() {
+        let mut req = HttpRequest::get("http://example.com/resource").body(()).unwrap();
+        let header = ::http::request_builder(&req, 80)
+            .unwrap()
+            .ext("app:data:hb1:")
+            .request()
+            .make_header(&credentials())
+            .unwrap();
+        set_authorization(&mut req, &header).unwrap();
+        let parts = server_parts(req, "/resource");
+
+        let verified = authenticate_with(&parts, None, &bindings()).unwrap();
+        assert_eq!(verified.client().ext, Some("app:data:hb1:".to_string()));
+        assert_eq!(verified.client().bound_headers, Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_header_binding() {
+        let mut req = HttpRequest::put("http://example.com/resource")
+            .header("idempotency-key", "8e03978e")
+            .header(CONTENT_TYPE, "text/plain")
+            .body(())
+            .unwrap();
+        let ext = ::http::bind_headers(req.headers(), &["Idempotency-Key"], &digest::SHA256)
+            .unwrap()
+            .to_ext(Some("app-data"));
+        let header = ::http::request_builder(&req, 80)
+            .unwrap()
+            .ext(&ext[..])
+            .request()
+            .make_header(&credentials())
+            .unwrap();
+        set_authorization(&mut req, &header).unwrap();
+        let parts = server_parts(req, "/resource");
+
+        let verified = authenticate_with(&parts, None, &bindings()).unwrap();
+        assert_eq!(verified.client().ext, Some("app-data".to_string()));
+        assert_eq!(verified.client().bound_headers, vec!["idempotency-key".to_string()]);
+
+        let mut changed = parts.clone();
+        changed.headers.insert("idempotency-key", HeaderValue::from_static("00000000"));
+        assert_eq!(authenticate_with(&changed, None, &bindings()).unwrap_err().failure(),
+                   AuthFailure::BadHeaderBinding);
+
+        // headers that were not bound may still change
+        let mut unbound = parts.clone();
+        unbound.headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/html"));
+        assert!(authenticate_with(&unbound, None, &bindings()).is_ok());
+
+        // bound values that are not visible ASCII are never accepted
+        let mut opaque = parts.clone();
+        opaque.headers.append("idempotency-key", HeaderValue::from_bytes(b"\xff").unwrap());
+        assert_eq!(authenticate_with(&opaque, None, &bindings()).unwrap_err().failure(),
+                   AuthFailure::BadHeaderBinding);
+    }
+
+    #[test]
+    fn test_header_bindings_disabled() {
+        let mut req = HttpRequest::put("http://example.com/resource")
+            .header("idempotency-key", "8e03978e")
+            .body(())
+            .unwrap();
+        let ext = ::http::bind_headers(req.headers(), &["Idempotency-Key"], &digest::SHA256)
+            .unwrap()
+            .to_ext(Some("app-data"));
+        let header = ::http::request_builder(&req, 80)
+            .unwrap()
+            .ext(&ext[..])
+            .request()
+            .make_header(&credentials())
+            .unwrap();
+        set_authorization(&mut req, &header).unwrap();
+        let mut parts = server_parts(req, "/resource");
+        parts.headers.insert("idempotency-key", HeaderValue::from_static("00000000"));
+
+        // without header bindings enabled, ext is opaque, even if it looks like a binding
+        let verified = authenticate(&parts, None).unwrap();
+        assert_eq!(verified.client().ext, Some(ext));
+        assert_eq!(verified.client().bound_headers, Vec::<String>::new());
+
+        let mut req = HttpRequest::get("http://example.com/resource").body(()).unwrap();
+        let header = ::http::request_builder(&req, 80)
+            .unwrap()
+            .ext("hb1:not-a-binding")
+            .request()
+            .make_header(&credentials())
+            .unwrap();
+        set_authorization(&mut req, &header).unwrap();
+        let parts = server_parts(req, "/resource");
+        let verified = authenticate(&parts, None).unwrap();
+        assert_eq!(verified.client().ext, Some("hb1:not-a-binding".to_string()));
+        assert_eq!(authenticate_with(&parts, None, &bindings()).unwrap_err().failure(),
+                   AuthFailure::BadHeaderBinding);
+    }
+
+    /// A body of the given data chunks.
+    struct Chunks(Vec<&'static [u8]>);
+
+    impl Body for Chunks {
+        type Data = Bytes;
+        type Error = Error;
+
+        fn poll_frame(self: Pin<&mut Self>,
+                      _: &mut Context)
+                      -> Poll<Option<::std::result::Result<Frame<Bytes>, Error>>> {
+            let chunks = &mut self.get_mut().0;
+            if chunks.is_empty() {
+                Poll::Ready(None)
+            } else {
+                Poll::Ready(Some(Ok(Frame::data(Bytes::from_static(chunks.remove(0))))))
+            }
+        }
+    }
+
+    /// Read the data and trailers of a body that never waits.
+    fn read<B: Body<Data = Bytes>>(body: B) -> (Vec<u8>, Option<HeaderMap>)
+        where B::Error: fmt::Debug
+    {
+        let mut body = Box::pin(body);
+        let mut cx = Context::from_waker(::std::task::Waker::noop());
+        let (mut data, mut trailers) = (vec![], None);
+        while let Poll::Ready(Some(frame)) = body.as_mut().poll_frame(&mut cx) {
+            let frame = frame.unwrap();
+            if let Some(chunk) = frame.data_ref() {
+                data.extend_from_slice(chunk);
+            }
+            if let Ok(t) = frame.into_trailers() {
+                trailers = Some(t);
+            }
+        }
+        (data, trailers)
+    }
+
+    #[test]
+    fn test_signed_body() {
+        let parts = signed("GET", "/resource", None);
+        let verified = authenticate(&parts, None).unwrap();
+        let body = SignedBody::new(Chunks(vec![b"O", b"K"]), verified, "text/plain");
+        assert!(body.size_hint().exact().is_none());
+        let (data, trailers) = read(body);
+        assert_eq!(data, b"OK");
+        let server_header = ::http::server_authorization(&trailers.unwrap()).unwrap().unwrap();
+
+        let client_header = ::http::authorization(&parts.headers).unwrap().unwrap();
+        let request = RequestBuilder::new("GET", "example.com", 80, "/resource").request();
+        let response = request.make_response_builder(&client_header).response();
+        assert!(response.validate_header_with_payload(&server_header,
+                                                      &credentials().key,
+                                                      "text/plain",
+                                                      b"OK"));
+
+        let (data, trailers) = read(SignedBody::unsigned(Chunks(vec![b"OK"])));
+        assert_eq!(data, b"OK");
+        assert!(trailers.is_none());
+    }
+
+    #[test]
+    fn test_streamed_hash() {
+        let parts = signed("GET", "/resource", None);
+        let verified = authenticate(&parts, None).unwrap();
+        let mut hasher = verified.response_hasher("text/plain; charset=utf-8");
+        hasher.update(b"O");
+        hasher.update(b"K");
+        let server_header = verified.server_authorization_with_hash(&hasher.finish())
+            .unwrap()
+            .unwrap();
+
+        let client_header = ::http::authorization(&parts.headers).unwrap().unwrap();
+        let request = RequestBuilder::new("GET", "example.com", 80, "/resource").request();
+        let response = request.make_response_builder(&client_header).response();
+        assert!(response.validate_header_with_payload(&server_header,
+                                                      &credentials().key,
+                                                      "text/plain",
+                                                      b"OK"));
+    }
+
+    #[test]
+    fn test_payload() {
+        let parts = signed("POST", "/resource", Some(("text/plain", b"hello")));
+        assert!(needs_body(&parts));
+        assert!(authenticate(&parts, Some(b"hello")).is_ok());
+        assert_eq!(failure(&parts, Some(b"goodbye")), AuthFailure::BadPayloadHash);
+        assert_eq!(failure(&parts, None), AuthFailure::BadPayloadHash);
+    }
+
+    #[test]
+    fn test_failures() {
+        let parts = signed("GET", "/resource", None);
+        // the server sees a different path
+        let mut moved = parts.clone();
+        moved.uri = "/elsewhere".parse().unwrap();
+        assert_eq!(failure(&moved, None), AuthFailure::BadMac);
+
+        let mut unauthenticated = parts.clone();
+        unauthenticated.headers.remove("authorization");
+        assert_eq!(failure(&unauthenticated, None),
+                   AuthFailure::MissingAuthentication);
+
+        let mut no_host = parts.clone();
+        no_host.headers.remove("host");
+        assert_eq!(failure(&no_host, None), AuthFailure::InvalidHost);
+
+        let mut garbage = parts.clone();
+        garbage.headers.insert("authorization", HeaderValue::from_static("Hawk garbage"));
+        assert_eq!(failure(&garbage, None), AuthFailure::InvalidHeaderSyntax);
+
+        let mut missing = parts.clone();
+        missing.headers.insert("authorization", HeaderValue::from_static("Hawk id=\"me\""));
+        assert_eq!(failure(&missing, None), AuthFailure::MissingAttributes);
+    }
+
+    #[test]
+    fn test_trusted_proxy() {
+        let mut req = HttpRequest::get("https://api.example.com/resource").body(()).unwrap();
+        let header = ::http::request_builder(&req, 80)
+            .unwrap()
+            .request()
+            .make_header(&credentials())
+            .unwrap();
+        set_authorization(&mut req, &header).unwrap();
+        let mut parts = server_parts(req, "/resource");
+        parts.headers.insert("host", HeaderValue::from_static("10.0.0.5:8080"));
+        let forwarded = "for=192.0.2.1;host=api.example.com;proto=https";
+        parts.headers.insert("forwarded", HeaderValue::from_static(forwarded));
+
+        let keys = keys();
+        let keys = move |id: &str| keys.get(id).cloned();
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        let config = ServerConfig::new(8080).trusted_proxies(TrustedProxies::new(vec![proxy]));
+
+        // without a peer address, the forwarding headers are ignored
+        let rejection = super::authenticate(&parts, None, &keys, &config).unwrap_err();
+        assert_eq!(rejection.failure(), AuthFailure::BadMac);
+
+        parts.extensions.insert(PeerAddr(proxy));
+        assert!(super::authenticate(&parts, None, &keys, &config).is_ok());
+
+        parts.extensions.insert(PeerAddr("192.0.2.1".parse().unwrap()));
+        let rejection = super::authenticate(&parts, None, &keys, &config).unwrap_err();
+        assert_eq!(rejection.failure(), AuthFailure::BadMac);
+    }
+
+    #[test]
+    fn test_host_aliases() {
+        let header = RequestBuilder::new("GET", "api.example.com", 443, "/resource")
+            .request()
+            .make_header(&credentials())
+            .unwrap();
+        let mut req = HttpRequest::get("/resource").body(()).unwrap();
+        set_authorization(&mut req, &header).unwrap();
+        let parts = server_parts(req, "/resource");
+
+        let keys = keys();
+        let keys = move |id: &str| keys.get(id).cloned();
+        let config = ServerConfig::new(80).host_aliases(vec![("legacy.example.com", 443),
+                                                              ("api.example.com", 443)]);
+        let verified = super::authenticate(&parts, None, &keys, &config).unwrap();
+        assert_eq!(verified.client().alias,
+                   Some(("api.example.com".to_string(), 443)));
+
+        // the response is signed for the alias
+        let server_header = verified.server_authorization(None).unwrap().unwrap();
+        let response = RequestBuilder::new("GET", "api.example.com", 443, "/resource")
+            .request()
+            .make_response_builder(&header)
+            .response();
+        assert!(response.validate_header(&server_header, &credentials().key));
+
+        // the host the request was received on is no longer accepted
+        let config = ServerConfig::new(80).host_aliases(vec![("legacy.example.com", 443)]);
+        let rejection = super::authenticate(&parts, None, &keys, &config).unwrap_err();
+        assert_eq!(rejection.failure(), AuthFailure::BadMac);
+
+        // bewits are checked against the aliases, too
+        let bewit = RequestBuilder::new("GET", "legacy.example.com", 443, "/resource")
+            .request()
+            .make_bewit(&credentials(), Duration::minutes(1))
+            .unwrap()
+            .to_str();
+        let path = format!("/resource?bewit={}", bewit);
+        let parts = server_parts(HttpRequest::get(&path[..]).body(()).unwrap(), &path);
+        let verified = super::authenticate(&parts, None, &keys, &config).unwrap();
+        assert_eq!(verified.client().alias,
+                   Some(("legacy.example.com".to_string(), 443)));
+    }
+
+    #[test]
+    fn test_unknown_credentials() {
+        let parts = signed("GET", "/resource", None);
+        let keys = |_: &str| None;
+        let rejection = super::authenticate(&parts, None, &keys, &ServerConfig::new(80))
+            .unwrap_err();
+        assert_eq!(rejection.failure(), AuthFailure::UnknownCredentials);
+    }
+
+    #[test]
+    fn test_bewit() {
+        let bewit = RequestBuilder::new("GET", "example.com", 80, "/resource?x=1")
+            .request()
+            .make_bewit(&credentials(), Duration::minutes(1))
+            .unwrap()
+            .to_str();
+        let path = format!("/resource?x=1&bewit={}", bewit);
+        let req = HttpRequest::get(&path[..]).body(()).unwrap();
+        let parts = server_parts(req, &path);
+
+        let verified = authenticate(&parts, None).unwrap();
+        assert!(verified.client().bewit);
+        assert_eq!(verified.server_authorization(None).unwrap(), None);
+
+        let mut post = parts.clone();
+        post.method = Method::POST;
+        assert_eq!(failure(&post, None), AuthFailure::InvalidMethod);
+
+        let mut both = parts.clone();
+        both.headers.insert("authorization", signed("GET", "/", None).headers["authorization"].clone());
+        assert_eq!(failure(&both, None), AuthFailure::MultipleAuthentications);
+
+        let req = HttpRequest::get("/resource?bewit=!!!").body(()).unwrap();
+        assert_eq!(failure(&server_parts(req, "/resource?bewit=!!!"), None),
+                   AuthFailure::InvalidBewit);
+    }
+
+    #[test]
+    fn test_bewit_policy() {
+        let bewit = |ttl: Duration| {
+            let bewit = RequestBuilder::new("GET", "example.com", 80, "/resource")
+                .ext("link-1")
+                .request()
+                .make_bewit(&credentials(), ttl)
+                .unwrap()
+                .to_str();
+            let path = format!("/resource?bewit={}", bewit);
+            let req = HttpRequest::get(&path[..]).body(()).unwrap();
+            server_parts(req, &path)
+        };
+        let config = |policy: BewitPolicy| ServerConfig::new(80).bewit_policy(policy);
+        let keys = keys();
+        let keys = move |id: &str| keys.get(id).cloned();
+        let check = |parts: &Parts, config: &ServerConfig| {
+            super::authenticate(parts, None, &keys, config)
+                .map(|_| ())
+                .map_err(|r| r.failure())
+        };
+
+        let max_ttl = config(BewitPolicy::new().max_ttl(Duration::hours(1)));
+        assert_eq!(check(&bewit(Duration::minutes(5)), &max_ttl), Ok(()));
+        assert_eq!(check(&bewit(Duration::days(30)), &max_ttl),
+                   Err(AuthFailure::BewitLifetimeTooLong));
+
+        let revoked = config(BewitPolicy::new()
+                                 .revocation(|bewit: &Bewit| bewit.ext() == Some("link-1")));
+        assert_eq!(check(&bewit(Duration::minutes(5)), &revoked),
+                   Err(AuthFailure::BewitRevoked));
+    }
+
+    #[test]
+    fn test_rejection_response() {
+        let res = Rejection::from(AuthFailure::BadMac).response(());
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(res.headers()[WWW_AUTHENTICATE], "Hawk error=\"Bad mac\"");
+
+        let res = Rejection::from(AuthFailure::MissingAuthentication).response(());
+        assert_eq!(res.headers()[WWW_AUTHENTICATE], "Hawk");
+
+        let res = Rejection::from(AuthFailure::InvalidHeaderSyntax).response(());
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert!(res.headers().get(WWW_AUTHENTICATE).is_none());
+    }
+
+    #[test]
+    fn test_stale_timestamp_response() {
+        let rejection = Rejection {
+            failure: AuthFailure::StaleTimestamp,
+            key: Some(Arc::new(credentials().key)),
+        };
+        let res = rejection.response(());
+        let challenge = parse(&res.headers()[WWW_AUTHENTICATE]);
+        assert!(challenge.challenge_ts(&credentials().key).is_some());
+    }
+}
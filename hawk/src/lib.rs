@@ -2,6 +2,11 @@
 //! authentictation. It is a low-level crate, used by higher-level crates to integrate with various
 //! Rust HTTP libraries.  For example `hyper-hawk` integrates Hawk with Hyper.
 //!
+//! The `ext` prefix `hb1:` is reserved for header bindings (see `HeaderBinding`).  Servers using
+//! `http::server::authenticate` with `ServerConfig::header_bindings` enabled interpret any `ext`
+//! value with this prefix as a binding, and reject the request if it is malformed, so
+//! applications should not use it for other data.
+//!
 //! # Examples
 //!
 //! ## Hawk Client
@@ -50,7 +55,7 @@
 //! extern crate time;
 //! extern crate hawk;
 //!
-//! use hawk::{RequestBuilder, Header, Key, SHA256};
+//! use hawk::{RequestBuilder, HeaderBuilder, Key, SHA256};
 //! use hawk::mac::Mac;
 //!
 //! fn main() {
@@ -58,14 +63,16 @@
 //!                             106, 102, 134, 144, 128, 225, 239, 95, 132, 202,
 //!                             154, 213, 118, 19, 63, 183, 108, 215, 134, 118, 115]);
 //!    // get the header (usually from the received request; constructed directly here)
-//!    let hdr = Header::new(Some("dh37fgj492je"),
-//!                          Some(time::Timespec::new(1353832234, 0)),
-//!                          Some("j4h3g2"),
-//!                          Some(mac),
-//!                          Some("my-ext-value"),
-//!                          Some(vec![1, 2, 3, 4]),
-//!                          Some("my-app"),
-//!                          Some("my-dlg")).unwrap();
+//!    let hdr = HeaderBuilder::authorization("dh37fgj492je",
+//!                                           time::Timespec::new(1353832234, 0),
+//!                                           "j4h3g2",
+//!                                           mac)
+//!        .ext("my-ext-value")
+//!        .hash(&[1, 2, 3, 4][..])
+//!        .app("my-app")
+//!        .dlg("my-dlg")
+//!        .build()
+//!        .unwrap();
 //!
 //!    // build a request object based on what we know
 //!    let hash = vec![1, 2, 3, 4];
@@ -84,15 +91,24 @@ extern crate ring;
 extern crate url;
 extern crate rand;
 
+#[cfg(feature = "http")]
+extern crate http as http_crate;
+#[cfg(feature = "http")]
+extern crate http_body;
+#[cfg(feature = "http")]
+extern crate bytes;
+
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate serde_json;
+
 #[cfg(test)]
 #[macro_use]
 extern crate pretty_assertions;
 
-#[macro_use]
-extern crate error_chain;
-
 mod header;
-pub use header::Header;
+pub use header::{Header, HeaderBuilder};
 
 mod credentials;
 pub use credentials::{Credentials, Key};
@@ -100,19 +116,44 @@ pub use credentials::{Credentials, Key};
 mod request;
 pub use request::{Request, RequestBuilder};
 
+/// The URL type taken by `RequestBuilder::from_url`, for clients whose HTTP library uses another
+/// version of the `url` crate.
+pub use url::Url;
+
 mod response;
 pub use response::{Response, ResponseBuilder};
 
+mod cow;
+pub use cow::IntoOptionCow;
+
 mod error;
 pub use error::*;
 
 mod payload;
-pub use payload::PayloadHasher;
+pub use payload::{PayloadHasher, content_type};
 
 mod bewit;
-pub use bewit::Bewit;
+pub use bewit::{Bewit, BewitPolicy, BewitRevocation};
+
+mod failure;
+pub use failure::AuthFailure;
+
+mod explain;
+pub use explain::{Alternative, Explanation};
+
+mod forwarded;
+pub use forwarded::{Forwarded, TrustedProxies, split_host};
+
+mod binding;
+pub use binding::HeaderBinding;
 
 pub mod mac;
 
+#[cfg(feature = "serde")]
+pub mod ext;
+
+#[cfg(feature = "http")]
+pub mod http;
+
 // convenience imports
 pub use ring::digest::{SHA256, SHA384, SHA512};
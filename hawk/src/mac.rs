@@ -1,12 +1,16 @@
 use credentials::Key;
-use base64;
-use ring::constant_time;
+use ring::{constant_time, hmac};
+use std::fmt;
 use std::io::Write;
 use std::ops::Deref;
 use error::*;
 use time;
 
+/// The largest MAC, in bytes, produced by any supported digest algorithm (SHA-512).
+pub const MAX_LEN: usize = 64;
+
 /// The kind of MAC calcuation (corresponding to the first line of the message)
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum MacType {
     Header,
     Response,
@@ -17,8 +21,14 @@ pub enum MacType {
 ///
 /// This class supports creating Macs using the Hawk specification, and comparing Macs
 /// using a cosntant-time comparison (thus preventing timing side-channel attacks).
-#[derive(Debug, Clone)]
-pub struct Mac(Vec<u8>);
+///
+/// A Mac is stored inline, in a buffer large enough for the largest supported digest, so
+/// creating one does not allocate.
+#[derive(Clone)]
+pub struct Mac {
+    bytes: [u8; MAX_LEN],
+    len: usize,
+}
 
 impl Mac {
     pub fn new(mac_type: MacType,
@@ -32,70 +42,207 @@ impl Mac {
                hash: Option<&[u8]>,
                ext: Option<&str>)
                -> Result<Mac> {
-        let mut buffer: Vec<u8> = vec![];
-
-        write!(buffer,
-               "{}\n",
-               match mac_type {
-                   MacType::Header => "hawk.1.header",
-                   MacType::Response => "hawk.1.response",
-                   MacType::Bewit => "hawk.1.bewit",
-               })?;
-        write!(buffer, "{}\n", ts.sec)?;
-        write!(buffer, "{}\n", nonce)?;
-        write!(buffer, "{}\n", method)?;
-        write!(buffer, "{}\n", path)?;
-        write!(buffer, "{}\n", host)?;
-        write!(buffer, "{}\n", port)?;
-
-        if let Some(ref h) = hash {
-            write!(buffer, "{}\n", base64::encode(h))?;
-        } else {
-            write!(buffer, "\n")?;
-        }
+        let mut ctx = key.signing_context();
+        normalize(|data| ctx.update(data),
+                  mac_type,
+                  ts,
+                  nonce,
+                  method,
+                  host,
+                  port,
+                  path,
+                  hash,
+                  ext);
+
+        return Ok(Mac::from_signature(ctx.sign()));
+    }
 
-        match ext {
-            Some(ref e) => write!(buffer, "{}\n", e)?,
-            None => write!(buffer, "\n")?,
+    /// Create a MAC for a bare timestamp, as sent in the `tsm` attribute of a `WWW-Authenticate`
+    /// header.  This allows a client to verify that a server's timestamp is authentic.
+    pub fn new_ts(key: &Key, ts: time::Timespec) -> Result<Mac> {
+        let mut ctx = key.signing_context();
+        ctx.update(b"hawk.1.ts\n");
+        update_int(|data| ctx.update(data), ts.sec);
+        ctx.update(b"\n");
+
+        return Ok(Mac::from_signature(ctx.sign()));
+    }
+
+    /// Create a Mac from the given bytes, such as a MAC decoded from a header.  This returns
+    /// `None` if there are more than `MAX_LEN` bytes.
+    pub fn from_slice(bytes: &[u8]) -> Option<Mac> {
+        if bytes.len() > MAX_LEN {
+            return None;
+        }
+        let mut mac = Mac {
+            bytes: [0; MAX_LEN],
+            len: bytes.len(),
         };
+        mac.bytes[..bytes.len()].copy_from_slice(bytes);
+        Some(mac)
+    }
+
+    pub(crate) fn from_signature(signature: hmac::Signature) -> Mac {
+        // ring's signatures are never longer than the largest digest
+        Mac::from_slice(signature.as_ref()).unwrap()
+    }
+}
+
+/// Feed the normalized string over which a Hawk MAC is calculated to `sink`, in pieces.
+fn normalize<F>(mut sink: F,
+                mac_type: MacType,
+                ts: time::Timespec,
+                nonce: &str,
+                method: &str,
+                host: &str,
+                port: u16,
+                path: &str,
+                hash: Option<&[u8]>,
+                ext: Option<&str>)
+    where F: FnMut(&[u8])
+{
+    sink(match mac_type {
+             MacType::Header => b"hawk.1.header\n",
+             MacType::Response => b"hawk.1.response\n",
+             MacType::Bewit => b"hawk.1.bewit\n",
+         });
+    update_int(&mut sink, ts.sec);
+    sink(b"\n");
+    sink(nonce.as_bytes());
+    sink(b"\n");
+    sink(method.as_bytes());
+    sink(b"\n");
+    sink(path.as_bytes());
+    sink(b"\n");
+    sink(host.as_bytes());
+    sink(b"\n");
+    update_int(&mut sink, port as i64);
+    sink(b"\n");
+    if let Some(h) = hash {
+        update_base64(&mut sink, h);
+    }
+    sink(b"\n");
+    if let Some(e) = ext {
+        sink(e.as_bytes());
+    }
+    sink(b"\n");
+}
+
+/// Feed the decimal representation of `n` to `sink`.
+fn update_int<F: FnMut(&[u8])>(mut sink: F, n: i64) {
+    // long enough for i64::MIN
+    let mut buf = [0u8; 20];
+    let len = {
+        let mut cursor = &mut buf[..];
+        write!(cursor, "{}", n).unwrap();
+        20 - cursor.len()
+    };
+    sink(&buf[..len]);
+}
+
+const BASE64_CHARS: &'static [u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Feed the standard, padded base64 encoding of `data` to `sink`, a block at a time.
+fn update_base64<F: FnMut(&[u8])>(mut sink: F, data: &[u8]) {
+    // 48 input bytes encode to 64 output bytes
+    let mut buf = [0u8; 64];
+    for block in data.chunks(48) {
+        let mut len = 0;
+        for chunk in block.chunks(3) {
+            let b = [chunk[0],
+                     *chunk.get(1).unwrap_or(&0),
+                     *chunk.get(2).unwrap_or(&0)];
+            let n = (b[0] as usize) << 16 | (b[1] as usize) << 8 | b[2] as usize;
+            buf[len] = BASE64_CHARS[n >> 18 & 63];
+            buf[len + 1] = BASE64_CHARS[n >> 12 & 63];
+            buf[len + 2] = if chunk.len() > 1 {
+                BASE64_CHARS[n >> 6 & 63]
+            } else {
+                b'='
+            };
+            buf[len + 3] = if chunk.len() > 2 {
+                BASE64_CHARS[n & 63]
+            } else {
+                b'='
+            };
+            len += 4;
+        }
+        sink(&buf[..len]);
+    }
+}
+
+/// Construct the normalized string over which a Hawk MAC is calculated.  This contains no key
+/// material, and is useful for debugging interoperability problems; see also
+/// `Request::explain_header`.
+pub fn normalized_string(mac_type: MacType,
+                         ts: time::Timespec,
+                         nonce: &str,
+                         method: &str,
+                         host: &str,
+                         port: u16,
+                         path: &str,
+                         hash: Option<&[u8]>,
+                         ext: Option<&str>)
+                         -> String {
+    let mut buffer = vec![];
+    normalize(|data| buffer.extend_from_slice(data),
+              mac_type,
+              ts,
+              nonce,
+              method,
+              host,
+              port,
+              path,
+              hash,
+              ext);
+    // every component is either a str or ASCII
+    String::from_utf8(buffer).unwrap()
+}
 
-        return Ok(Mac(key.sign(buffer.as_ref())));
+impl fmt::Debug for Mac {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Mac({:?})", &self[..])
     }
 }
 
 impl AsRef<[u8]> for Mac {
     fn as_ref(&self) -> &[u8] {
-        &self.0[..]
+        &self.bytes[..self.len]
     }
 }
 
+/// Create a Mac from a vector of bytes.
+///
+/// Bytes beyond `MAX_LEN`, longer than any supported digest, are dropped; use `Mac::from_slice`
+/// to reject such input instead.
 impl From<Vec<u8>> for Mac {
     fn from(original: Vec<u8>) -> Self {
-        Mac(original)
+        let len = original.len().min(MAX_LEN);
+        Mac::from_slice(&original[..len]).unwrap()
     }
 }
 
 impl Deref for Mac {
-    type Target = Vec<u8>;
+    type Target = [u8];
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        self.as_ref()
     }
 }
 
 impl PartialEq for Mac {
     fn eq(&self, other: &Mac) -> bool {
-        match constant_time::verify_slices_are_equal(&self.0[..], &other.0[..]) {
+        match constant_time::verify_slices_are_equal(self.as_ref(), other.as_ref()) {
             Ok(_) => true,
             Err(_) => false,
         }
     }
 }
 
-
 #[cfg(test)]
 mod test {
-    use super::{Mac, MacType};
+    use super::{Mac, MacType, MAX_LEN, normalized_string, update_base64};
+    use base64;
     use time::Timespec;
     use credentials::Key;
     use ring::digest;
@@ -121,9 +268,9 @@ mod test {
                            None)
             .unwrap();
         println!("got {:?}", mac);
-        assert!(mac.0 ==
-                vec![192, 227, 235, 121, 157, 185, 197, 79, 189, 214, 235, 139, 9, 232, 99, 55,
-                     67, 30, 68, 0, 150, 187, 192, 238, 21, 200, 209, 107, 245, 159, 243, 178]);
+        assert!(&mac[..] ==
+                &[192, 227, 235, 121, 157, 185, 197, 79, 189, 214, 235, 139, 9, 232, 99, 55,
+                  67, 30, 68, 0, 150, 187, 192, 238, 21, 200, 209, 107, 245, 159, 243, 178]);
     }
 
     #[test]
@@ -142,9 +289,9 @@ mod test {
                            None)
             .unwrap();
         println!("got {:?}", mac);
-        assert!(mac.0 ==
-                vec![61, 128, 208, 253, 88, 135, 190, 196, 1, 69, 153, 193, 124, 4, 195, 87, 38,
-                     96, 181, 34, 65, 234, 58, 157, 175, 175, 145, 151, 61, 0, 57, 5]);
+        assert!(&mac[..] ==
+                &[61, 128, 208, 253, 88, 135, 190, 196, 1, 69, 153, 193, 124, 4, 195, 87, 38,
+                  96, 181, 34, 65, 234, 58, 157, 175, 175, 145, 151, 61, 0, 57, 5]);
     }
 
     #[test]
@@ -163,8 +310,60 @@ mod test {
                            Some(&ext))
             .unwrap();
         println!("got {:?}", mac);
-        assert!(mac.0 ==
-                vec![187, 104, 238, 100, 168, 112, 37, 68, 187, 141, 168, 155, 177, 193, 113, 0,
-                     50, 105, 127, 36, 24, 117, 200, 251, 138, 199, 108, 14, 105, 123, 234, 119]);
+        assert!(&mac[..] ==
+                &[187, 104, 238, 100, 168, 112, 37, 68, 187, 141, 168, 155, 177, 193, 113, 0,
+                  50, 105, 127, 36, 24, 117, 200, 251, 138, 199, 108, 14, 105, 123, 234, 119]);
+    }
+
+    #[test]
+    fn test_make_mac_ts() {
+        let key = key();
+        let mac = Mac::new_ts(&key, Timespec::new(1353832234, 100)).unwrap();
+        println!("got {:?}", mac);
+        assert!(&mac[..] ==
+                &[101, 165, 118, 206, 19, 176, 56, 46, 251, 48, 93, 140, 32, 136, 133, 205,
+                  67, 10, 161, 219, 105, 154, 255, 89, 181, 236, 65, 145, 43, 184, 205, 223]);
+    }
+    #[test]
+    fn test_normalized_string() {
+        let s = normalized_string(MacType::Header,
+                                  Timespec::new(1000, 100),
+                                  "nonny",
+                                  "POST",
+                                  "mysite.com",
+                                  443,
+                                  "/v1/api",
+                                  Some(&[1, 2, 3, 4, 5]),
+                                  Some("ext-data"));
+        assert_eq!(s,
+                   "hawk.1.header\n1000\nnonny\nPOST\n/v1/api\nmysite.com\n443\nAQIDBAU=\n\
+                    ext-data\n");
+    }
+    #[test]
+    fn test_update_base64() {
+        let data: Vec<u8> = (0..200).map(|i| (i * 7) as u8).collect();
+        for len in 0..data.len() {
+            let mut encoded = vec![];
+            update_base64(|d| encoded.extend_from_slice(d), &data[..len]);
+            assert_eq!(String::from_utf8(encoded).unwrap(),
+                       base64::encode(&data[..len]));
+        }
+    }
+
+    #[test]
+    fn test_from_slice() {
+        assert_eq!(&Mac::from_slice(&[1, 2, 3]).unwrap()[..], &[1, 2, 3]);
+        assert!(Mac::from_slice(&[0; MAX_LEN]).is_some());
+        assert!(Mac::from_slice(&[0; MAX_LEN + 1]).is_none());
+    }
+
+    #[test]
+    fn test_from_vec_saturates() {
+        assert_eq!(Mac::from(vec![7; MAX_LEN + 1]), Mac::from(vec![7; MAX_LEN]));
+    }
+
+    #[test]
+    fn test_eq_different_lengths() {
+        assert!(Mac::from(vec![1, 2, 3]) != Mac::from(vec![1, 2, 3, 0]));
     }
 }
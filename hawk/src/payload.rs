@@ -1,4 +1,5 @@
 use ring::digest;
+use std::io::{self, Read};
 
 /// A utility for hashing payloads. Feed your entity body to this, then pass the `finish`
 /// result to a request or response.
@@ -37,6 +38,27 @@ impl PayloadHasher {
         hasher.finish()
     }
 
+    /// Hash the entire contents of a reader and return the result
+    pub fn hash_reader<B, R>(content_type: B,
+                             algorithm: &'static digest::Algorithm,
+                             mut reader: R)
+                             -> io::Result<Vec<u8>>
+        where B: AsRef<[u8]>,
+              R: Read
+    {
+        let mut hasher = PayloadHasher::new(content_type, algorithm);
+        let mut buf = [0u8; 8192];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => hasher.update(&buf[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(hasher.finish())
+    }
+
     /// Update the hash with new data.
     pub fn update<'a, B>(&mut self, data: B)
         where B: AsRef<[u8]>
@@ -56,9 +78,15 @@ impl PayloadHasher {
     }
 }
 
+/// Convert the value of a `Content-Type` header into the form used for payload hashing: the
+/// media type alone, lower-cased, without parameters such as `charset`.
+pub fn content_type(header_value: &str) -> String {
+    header_value.split(';').next().unwrap_or("").trim().to_lowercase()
+}
+
 #[cfg(test)]
 mod tests {
-    use super::PayloadHasher;
+    use super::{PayloadHasher, content_type};
     use ring::digest::SHA256;
 
     #[test]
@@ -85,4 +113,20 @@ mod tests {
         assert_eq!(hash3, hash1);
         assert_eq!(hash4, hash1);
     }
+
+    #[test]
+    fn hash_reader() {
+        let hash1 = PayloadHasher::hash("text/plain", &SHA256, "pàyload");
+        let hash2 = PayloadHasher::hash_reader("text/plain", &SHA256, "pàyload".as_bytes())
+            .unwrap();
+        assert_eq!(hash2, hash1);
+    }
+
+    #[test]
+    fn content_type_parameters() {
+        assert_eq!(content_type("text/plain"), "text/plain");
+        assert_eq!(content_type("Text/HTML; charset=UTF-8"), "text/html");
+        assert_eq!(content_type(" application/json ;q=1"), "application/json");
+        assert_eq!(content_type(""), "");
+    }
 }
@@ -1,23 +1,34 @@
 use base64;
 use time;
-use url::Url;
+use url::{Position, Url};
 use mac::{Mac, MacType};
-use header::Header;
+use header::{Header, HeaderBuilder};
 use response::ResponseBuilder;
 use bewit::Bewit;
 use credentials::{Credentials, Key};
+use failure::AuthFailure;
+use explain::{self, Explanation};
 use rand;
 use rand::Rng;
 use error::*;
 use time::{now, Duration};
 use std::str;
+use std::borrow::Cow;
+use cow::IntoOptionCow;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+#[cfg(feature = "serde")]
+use ext;
 
 /// Request represents a single HTTP request.
 ///
 /// The structure is created using (RequestBuilder)[struct.RequestBuilder.html]. Most uses of this
 /// library will hold several of the fields in this structure fixed. Cloning the structure with
 /// these fields applied is a convenient way to avoid repeating those fields. Most fields are
-/// references, since in common use the values already exist and will outlive the request.
+/// usually references, since in common use the values already exist and will outlive the
+/// request. Where that is not the case, such as when a request must be sent to another thread or
+/// stored in a structure, `into_owned` converts a request into a `Request<'static>` which owns
+/// all of its fields.
 ///
 /// A request can be used on the client, to generate a header or a bewit, or on the server, to
 /// validate the same.
@@ -34,22 +45,32 @@ use std::str;
 /// See the documentation in the crate root for examples of creating and validating headers.
 #[derive(Debug, Clone)]
 pub struct Request<'a> {
-    method: &'a str,
-    host: &'a str,
+    method: Cow<'a, str>,
+    host: Cow<'a, str>,
     port: u16,
-    path: &'a str,
-    hash: Option<&'a [u8]>,
-    ext: Option<&'a str>,
-    app: Option<&'a str>,
-    dlg: Option<&'a str>,
+    path: Cow<'a, str>,
+    hash: Option<Cow<'a, [u8]>>,
+    ext: Option<Cow<'a, str>>,
+    app: Option<Cow<'a, str>>,
+    dlg: Option<Cow<'a, str>>,
 }
 
 impl<'a> Request<'a> {
     /// Create a new Header for this request, inventing a new nonce and setting the
     /// timestamp to the current time.
     pub fn make_header(&self, credentials: &Credentials) -> Result<Header> {
+        self.make_header_with_offset(credentials, Duration::zero())
+    }
+
+    /// Similar to `make_header`, but adding `offset` to the current time.  This allows a client
+    /// to correct for the difference between its clock and that of the server, as reported in a
+    /// `WWW-Authenticate` challenge (see `Header::challenge_ts`).
+    pub fn make_header_with_offset(&self,
+                                   credentials: &Credentials,
+                                   offset: Duration)
+                                   -> Result<Header> {
         let nonce = random_string(10);
-        self.make_header_full(credentials, time::now().to_timespec(), nonce)
+        self.make_header_full(credentials, time::now().to_timespec() + offset, nonce)
     }
 
     /// Similar to `make_header`, but allowing specification of the timestamp
@@ -66,38 +87,24 @@ impl<'a> Request<'a> {
                            &credentials.key,
                            ts,
                            &nonce,
-                           self.method,
-                           self.host,
+                           &self.method,
+                           &self.host,
                            self.port,
-                           self.path,
-                           self.hash,
-                           self.ext)?;
-        Header::new(Some(credentials.id.clone()),
-                    Some(ts),
-                    Some(nonce),
-                    Some(mac),
-                    match self.ext {
-                        None => None,
-                        Some(v) => Some(v.to_string()),
-                    },
-                    match self.hash {
-                        None => None,
-                        Some(v) => Some(v.to_vec()),
-                    },
-                    match self.app {
-                        None => None,
-                        Some(v) => Some(v.to_string()),
-                    },
-                    match self.dlg {
-                        None => None,
-                        Some(v) => Some(v.to_string()),
-                    })
+                           &self.path,
+                           self.hash(),
+                           self.ext())?;
+        HeaderBuilder::authorization(&credentials.id, ts, &nonce, mac)
+            .ext(self.ext())
+            .hash(self.hash())
+            .app(self.app.as_ref().map(|v| &v[..]))
+            .dlg(self.dlg.as_ref().map(|v| &v[..]))
+            .build()
     }
 
     /// Make a "bewit" that can be attached to a URL to authenticate GET access.
     ///
     /// The ttl gives the time for which this bewit is valid, starting now.
-    pub fn make_bewit(&self, credentials: &'a Credentials, ttl: Duration) -> Result<Bewit<'a>> {
+    pub fn make_bewit(&self, credentials: &Credentials, ttl: Duration) -> Result<Bewit<'a>> {
         let exp = time::now().to_timespec() + ttl;
         // note that this includes `method` and `hash` even though they must always be GET and None
         // for bewits.  If they aren't, then the bewit just won't validate -- no need to catch
@@ -106,13 +113,13 @@ impl<'a> Request<'a> {
                            &credentials.key,
                            exp,
                            "",
-                           self.method,
-                           self.host,
+                           &self.method,
+                           &self.host,
                            self.port,
-                           self.path,
-                           self.hash,
-                           self.ext)?;
-        let bewit = Bewit::new(&credentials.id, exp, mac, self.ext);
+                           &self.path,
+                           self.hash(),
+                           self.ext())?;
+        let bewit = Bewit::new(Cow::Owned(credentials.id.clone()), exp, mac, self.ext.clone());
         Ok(bewit)
     }
 
@@ -129,23 +136,63 @@ impl<'a> Request<'a> {
     /// If a hash has been supplied, then the header must contain a matching hash. Note that this
     /// hash must be calculated based on the request body, not copied from the request header!
     pub fn validate_header(&self, header: &Header, key: &Key, ts_skew: Duration) -> bool {
+        self.check_header(header, key, ts_skew).is_ok()
+    }
+
+    /// Validate the given header, as for `validate_header`, but returning the reason for any
+    /// failure.  The reason can be used to build a `WWW-Authenticate` challenge.
+    pub fn check_header(&self,
+                        header: &Header,
+                        key: &Key,
+                        ts_skew: Duration)
+                        -> ::std::result::Result<(), AuthFailure> {
+        self.check_header_at(header, key, ts_skew, &[(&*self.host, self.port)]).map(|_| ())
+    }
+
+    /// Validate the given header, as for `check_header`, but accepting a header signed for any of
+    /// the given `(host, port)` aliases in place of this request's host and port.  This suits a
+    /// server that answers on several names or ports.
+    ///
+    /// On success, this returns the index of the alias that matched.  The MAC is calculated for
+    /// every alias, and compared in constant time, so the time taken does not reveal which alias
+    /// matched, or how close the header came to matching any of them.
+    pub fn check_header_aliases<H>(&self,
+                                   header: &Header,
+                                   key: &Key,
+                                   ts_skew: Duration,
+                                   aliases: &[(H, u16)])
+                                   -> ::std::result::Result<usize, AuthFailure>
+        where H: AsRef<str>
+    {
+        let aliases: Vec<(&str, u16)> = aliases.iter().map(|&(ref h, p)| (h.as_ref(), p)).collect();
+        self.check_header_at(header, key, ts_skew, &aliases)
+    }
+
+    /// Check the header against each of the given hosts, returning the index of the first that
+    /// matched.
+    fn check_header_at(&self,
+                       header: &Header,
+                       key: &Key,
+                       ts_skew: Duration,
+                       hosts: &[(&str, u16)])
+                       -> ::std::result::Result<usize, AuthFailure> {
         // extract required fields, returning early if they are not present
         let ts = match header.ts {
             Some(ts) => ts,
             None => {
-                return false;
+                return Err(AuthFailure::MissingAttributes);
             }
         };
         let nonce = match header.nonce {
             Some(ref nonce) => nonce,
             None => {
-                return false;
+                return Err(AuthFailure::MissingAttributes);
             }
         };
         let header_mac = match header.mac {
             Some(ref mac) => mac,
             None => {
-                return false;
+                return Err(AuthFailure::MissingAttributes);
             }
         };
         let header_hash = match header.hash {
@@ -158,34 +205,31 @@ impl<'a> Request<'a> {
         };
 
         // first verify the MAC
-        match Mac::new(MacType::Header,
-                       key,
-                       ts,
-                       nonce,
-                       self.method,
-                       self.host,
-                       self.port,
-                       self.path,
-                       header_hash,
-                       header_ext) {
-            Ok(calculated_mac) => {
-                if &calculated_mac != header_mac {
-                    return false;
-                }
-            }
-            Err(_) => {
-                return false;
+        let matched = match_host(hosts, |host, port| {
+            match Mac::new(MacType::Header,
+                           key,
+                           ts,
+                           nonce,
+                           &self.method,
+                           host,
+                           port,
+                           &self.path,
+                           header_hash,
+                           header_ext) {
+                Ok(calculated_mac) => &calculated_mac == header_mac,
+                Err(_) => false,
             }
-        };
+        });
+        let matched = matched.ok_or(AuthFailure::BadMac)?;
 
         // ..then the hashes
-        if let Some(local_hash) = self.hash {
+        if let Some(local_hash) = self.hash() {
             if let Some(server_hash) = header_hash {
                 if local_hash != server_hash {
-                    return false;
+                    return Err(AuthFailure::BadPayloadHash);
                 }
             } else {
-                return false;
+                return Err(AuthFailure::MissingRequiredPayloadHash);
             }
         }
 
@@ -193,15 +237,15 @@ impl<'a> Request<'a> {
         let now = now().to_timespec();
         if now > ts {
             if now - ts > ts_skew {
-                return false;
+                return Err(AuthFailure::StaleTimestamp);
             }
         } else {
             if ts - now > ts_skew {
-                return false;
+                return Err(AuthFailure::StaleTimestamp);
             }
         }
 
-        true
+        Ok(matched)
     }
 
     /// Validate the given bewit matches this request.
@@ -209,62 +253,167 @@ impl<'a> Request<'a> {
     /// It is up to the caller to consult the Bewit's `id` and look up the
     /// corresponding key.
     ///
-    /// Nonces and hashes do not apply when using bewits.
+    /// Nonces and hashes do not apply when using bewits.  Bewits only grant access for GET and
+    /// HEAD, so validation fails for requests with any other method.  A server can further
+    /// restrict the bewits it accepts with a `BewitPolicy`.
     pub fn validate_bewit(&self, bewit: &Bewit, key: &Key) -> bool {
-        let calculated_mac = Mac::new(MacType::Bewit,
-                                      &key,
-                                      bewit.exp(),
-                                      "",
-                                      self.method,
-                                      self.host,
-                                      self.port,
-                                      self.path,
-                                      self.hash,
-                                      match bewit.ext() {
-                                          Some(ref e) => Some(e),
-                                          None => None,
-                                      });
-        let calculated_mac = match calculated_mac {
-            Ok(m) => m,
-            Err(_) => {
-                return false;
-            }
-        };
+        self.check_bewit(bewit, key).is_ok()
+    }
 
-        if bewit.mac() != &calculated_mac {
-            return false;
+    /// Diagnose why the given header's MAC does or does not match this request.
+    ///
+    /// The result contains the normalized string over which the MAC was calculated.  If the MAC
+    /// does not match, alternatives that commonly explain a mismatch are tried: the default ports,
+    /// the host with or without a port, the path without its query, and the payload hash present
+    /// or absent.  The first alternative that matches, if any, is reported.  The result never
+    /// contains the key or any MAC, so it is suitable for logging.
+    ///
+    /// This is considerably more expensive than `check_header`, and should only be used once a
+    /// header has been rejected with `AuthFailure::BadMac`.
+    pub fn explain_header(&self, header: &Header, key: &Key) -> Result<Explanation> {
+        explain::explain(header,
+                         key,
+                         explain::Components {
+                             method: &self.method,
+                             host: &self.host,
+                             port: self.port,
+                             path: &self.path,
+                             hash: self.hash(),
+                         })
+    }
+
+    /// Validate the given bewit, as for `validate_bewit`, but returning the reason for any
+    /// failure.
+    pub fn check_bewit(&self, bewit: &Bewit, key: &Key) -> ::std::result::Result<(), AuthFailure> {
+        self.check_bewit_at(bewit, key, &[(&*self.host, self.port)]).map(|_| ())
+    }
+
+    /// Validate the given bewit, as for `check_bewit`, but accepting a bewit made for any of the
+    /// given `(host, port)` aliases in place of this request's host and port, as for
+    /// `check_header_aliases`.  On success, this returns the index of the alias that matched.
+    pub fn check_bewit_aliases<H>(&self,
+                                  bewit: &Bewit,
+                                  key: &Key,
+                                  aliases: &[(H, u16)])
+                                  -> ::std::result::Result<usize, AuthFailure>
+        where H: AsRef<str>
+    {
+        let aliases: Vec<(&str, u16)> = aliases.iter().map(|&(ref h, p)| (h.as_ref(), p)).collect();
+        self.check_bewit_at(bewit, key, &aliases)
+    }
+
+    /// Check the bewit against each of the given hosts, returning the index of the first that
+    /// matched.
+    fn check_bewit_at(&self,
+                      bewit: &Bewit,
+                      key: &Key,
+                      hosts: &[(&str, u16)])
+                      -> ::std::result::Result<usize, AuthFailure> {
+        if self.method != "GET" && self.method != "HEAD" {
+            return Err(AuthFailure::InvalidMethod);
         }
 
+        // bewits are always signed as GET, so that the same link also serves HEAD requests
+        let matched = match_host(hosts, |host, port| {
+            let calculated_mac = Mac::new(MacType::Bewit,
+                                          &key,
+                                          bewit.exp(),
+                                          "",
+                                          "GET",
+                                          host,
+                                          port,
+                                          &self.path,
+                                          self.hash(),
+                                          match bewit.ext() {
+                                              Some(ref e) => Some(e),
+                                              None => None,
+                                          });
+            match calculated_mac {
+                Ok(m) => bewit.mac() == &m,
+                Err(_) => false,
+            }
+        });
+        let matched = matched.ok_or(AuthFailure::BadMac)?;
+
         let now = time::now().to_timespec();
         if bewit.exp() < now {
-            return false;
+            return Err(AuthFailure::AccessExpired);
         }
 
-        true
+        Ok(matched)
     }
 
     /// Get a Response instance for a response to this request.  This is a convenience
     /// wrapper around `Response::from_request_header`.
     pub fn make_response_builder(&self, req_header: &'a Header) -> ResponseBuilder<'a> {
         ResponseBuilder::from_request_header(req_header,
-                                             self.method,
-                                             self.host,
+                                             self.method.clone(),
+                                             self.host.clone(),
                                              self.port,
-                                             self.path)
+                                             self.path.clone())
+    }
+
+    /// Convert this request into one that owns all of its fields, copying any that are borrowed.
+    pub fn into_owned(self) -> Request<'static> {
+        Request {
+            method: Cow::Owned(self.method.into_owned()),
+            host: Cow::Owned(self.host.into_owned()),
+            port: self.port,
+            path: Cow::Owned(self.path.into_owned()),
+            hash: self.hash.map(|v| Cow::Owned(v.into_owned())),
+            ext: self.ext.map(|v| Cow::Owned(v.into_owned())),
+            app: self.app.map(|v| Cow::Owned(v.into_owned())),
+            dlg: self.dlg.map(|v| Cow::Owned(v.into_owned())),
+        }
+    }
+
+    fn hash(&self) -> Option<&[u8]> {
+        match self.hash {
+            Some(ref v) => Some(v.as_ref()),
+            None => None,
+        }
+    }
+
+    fn ext(&self) -> Option<&str> {
+        match self.ext {
+            Some(ref v) => Some(v.as_ref()),
+            None => None,
+        }
     }
 }
 
+/// Find the first of the given hosts for which `matches` returns true.  Every host is tried, even
+/// after a match, so that the time taken does not depend on which host matched.
+fn match_host<F>(hosts: &[(&str, u16)], matches: F) -> Option<usize>
+    where F: Fn(&str, u16) -> bool
+{
+    let mut matched = None;
+    for (i, &(host, port)) in hosts.iter().enumerate() {
+        let found = matches(host, port);
+        if found && matched.is_none() {
+            matched = Some(i);
+        }
+    }
+    matched
+}
+
 #[derive(Debug, Clone)]
 pub struct RequestBuilder<'a>(Request<'a>);
 
 impl<'a> RequestBuilder<'a> {
     /// Create a new request with the given method, host, port, and path.
-    pub fn new(method: &'a str, host: &'a str, port: u16, path: &'a str) -> Self {
+    ///
+    /// The string arguments can be either borrowed (`&str`) or owned (`String`).
+    pub fn new<M, H, P>(method: M, host: H, port: u16, path: P) -> Self
+        where M: Into<Cow<'a, str>>,
+              H: Into<Cow<'a, str>>,
+              P: Into<Cow<'a, str>>
+    {
         RequestBuilder(Request {
-            method: method,
-            host: host,
+            method: method.into(),
+            host: host.into(),
             port: port,
-            path: path,
+            path: path.into(),
             hash: None,
             ext: None,
             app: None,
@@ -272,14 +421,17 @@ impl<'a> RequestBuilder<'a> {
         })
     }
 
-    /// Create a new request with the host, port, and path determined from the URL.
-    pub fn from_url(method: &'a str, url: &'a Url) -> Result<Self> {
+    /// Create a new request with the host, port, and path determined from the URL.  The path
+    /// includes the URL's query, if any, as Hawk signs both.
+    pub fn from_url<M>(method: M, url: &'a Url) -> Result<Self>
+        where M: Into<Cow<'a, str>>
+    {
         let (host, port, path) = RequestBuilder::parse_url(url)?;
         Ok(RequestBuilder(Request {
-            method: method,
-            host: host,
+            method: method.into(),
+            host: Cow::Borrowed(host),
             port: port,
-            path: path,
+            path: Cow::Borrowed(path),
             hash: None,
             ext: None,
             app: None,
@@ -288,20 +440,20 @@ impl<'a> RequestBuilder<'a> {
     }
 
     /// Set the request method. This should be a capitalized string.
-    pub fn method(mut self, method: &'a str) -> Self {
-        self.0.method = method;
+    pub fn method<S: Into<Cow<'a, str>>>(mut self, method: S) -> Self {
+        self.0.method = method.into();
         self
     }
 
     /// Set the URL path for the request.
-    pub fn path(mut self, path: &'a str) -> Self {
-        self.0.path = path;
+    pub fn path<S: Into<Cow<'a, str>>>(mut self, path: S) -> Self {
+        self.0.path = path.into();
         self
     }
 
     /// Set the URL hostname for the request
-    pub fn host(mut self, host: &'a str) -> Self {
-        self.0.host = host;
+    pub fn host<S: Into<Cow<'a, str>>>(mut self, host: S) -> Self {
+        self.0.host = host.into();
         self
     }
 
@@ -311,33 +463,40 @@ impl<'a> RequestBuilder<'a> {
         self
     }
 
-    /// Set the hostname, port, and path for the request, from a string URL.
+    /// Set the hostname, port, and path (with the query) for the request, from a URL.
     pub fn url(self, url: &'a Url) -> Result<Self> {
         let (host, port, path) = RequestBuilder::parse_url(url)?;
         Ok(self.path(path).host(host).port(port))
     }
 
-    /// Set the content hash for the request
-    pub fn hash<H: Into<Option<&'a [u8]>>>(mut self, hash: H) -> Self {
-        self.0.hash = hash.into();
+    /// Set the content hash for the request, either borrowed or owned.
+    pub fn hash<H: IntoOptionCow<'a, [u8]>>(mut self, hash: H) -> Self {
+        self.0.hash = hash.into_option_cow();
         self
     }
 
-    /// Set the `ext` Hawk property for the request
-    pub fn ext<S: Into<Option<&'a str>>>(mut self, ext: S) -> Self {
-        self.0.ext = ext.into();
+    /// Set the `ext` Hawk property for the request, either borrowed or owned.
+    pub fn ext<S: IntoOptionCow<'a, str>>(mut self, ext: S) -> Self {
+        self.0.ext = ext.into_option_cow();
         self
     }
 
-    /// Set the `app` Hawk property for the request
-    pub fn app<S: Into<Option<&'a str>>>(mut self, app: S) -> Self {
-        self.0.app = app.into();
+    /// Set the `ext` Hawk property for the request to the given value, encoded as base64 JSON
+    /// as described in the `ext` module.
+    #[cfg(feature = "serde")]
+    pub fn ext_json<T: Serialize + ?Sized>(self, value: &T) -> Result<Self> {
+        Ok(self.ext(ext::encode(value)?))
+    }
+
+    /// Set the `app` Hawk property for the request, either borrowed or owned.
+    pub fn app<S: IntoOptionCow<'a, str>>(mut self, app: S) -> Self {
+        self.0.app = app.into_option_cow();
         self
     }
 
-    /// Set the `dlg` Hawk property for the request
-    pub fn dlg<S: Into<Option<&'a str>>>(mut self, dlg: S) -> Self {
-        self.0.dlg = dlg.into();
+    /// Set the `dlg` Hawk property for the request, either borrowed or owned.
+    pub fn dlg<S: IntoOptionCow<'a, str>>(mut self, dlg: S) -> Self {
+        self.0.dlg = dlg.into_option_cow();
         self
     }
 
@@ -346,12 +505,17 @@ impl<'a> RequestBuilder<'a> {
         self.0
     }
 
+    /// Convert this builder into one that owns all of its fields; see `Request::into_owned`.
+    pub fn into_owned(self) -> RequestBuilder<'static> {
+        RequestBuilder(self.0.into_owned())
+    }
+
     fn parse_url(url: &'a Url) -> Result<(&'a str, u16, &'a str)> {
         let host = url.host_str()
-            .ok_or(format!("url {} has no host", url))?;
+            .ok_or_else(|| Error::UrlMissingHost(url.to_string()))?;
         let port = url.port_or_known_default()
-            .ok_or(format!("url {} has no port", url))?;
-        let path = url.path();
+            .ok_or_else(|| Error::UrlMissingPort(url.to_string()))?;
+        let path = &url[Position::BeforePath..Position::AfterQuery];
         Ok((host, port, path))
     }
 }
@@ -407,10 +571,58 @@ mod test {
         assert_eq!(req.path, "/foo");
         assert_eq!(req.host, "example.com");
         assert_eq!(req.port, 443);
-        assert_eq!(req.hash, Some(&hash[..]));
-        assert_eq!(req.ext, Some("ext"));
-        assert_eq!(req.app, Some("app"));
-        assert_eq!(req.dlg, Some("dlg"));
+        assert_eq!(req.hash(), Some(&hash[..]));
+        assert_eq!(req.ext(), Some("ext"));
+        assert_eq!(req.app, Some(Cow::Borrowed("app")));
+        assert_eq!(req.dlg, Some(Cow::Borrowed("dlg")));
+    }
+
+    #[test]
+    fn test_builder_owned() {
+        let req = RequestBuilder::new("GET".to_string(),
+                                      "example.com".to_string(),
+                                      443,
+                                      format!("/foo/{}", 1))
+            .hash(vec![0u8])
+            .ext(Some("ext".to_string()))
+            .app("app".to_string())
+            .dlg(None::<String>)
+            .request();
+        assert_eq!(req.method, "GET");
+        assert_eq!(req.host, "example.com");
+        assert_eq!(req.path, "/foo/1");
+        assert_eq!(req.hash(), Some(&[0u8][..]));
+        assert_eq!(req.ext(), Some("ext"));
+        assert_eq!(req.app, Some(Cow::Borrowed("app")));
+        assert_eq!(req.dlg, None);
+    }
+
+    #[test]
+    fn test_into_owned() {
+        fn make_request() -> Request<'static> {
+            let hash = vec![0u8];
+            let url = Url::parse("https://example.com/foo").unwrap();
+            let ext = "ext".to_string();
+            RequestBuilder::from_url("GET", &url)
+                .unwrap()
+                .hash(&hash[..])
+                .ext(&ext[..])
+                .request()
+                .into_owned()
+        }
+
+        fn is_send_sync_static<T: Send + Sync + 'static>(_: &T) {}
+
+        let req = make_request();
+        is_send_sync_static(&req);
+        assert_eq!(req.method, "GET");
+        assert_eq!(req.host, "example.com");
+        assert_eq!(req.port, 443);
+        assert_eq!(req.path, "/foo");
+        assert_eq!(req.hash(), Some(&[0u8][..]));
+        assert_eq!(req.ext(), Some("ext"));
+        assert_eq!(req.app, None);
+        assert_eq!(req.dlg, None);
     }
 
     #[test]
@@ -435,6 +647,15 @@ mod test {
         assert_eq!(req.port, 443); // default for https
     }
 
+    #[test]
+    fn test_url_builder_query() {
+        let url = Url::parse("https://example.com:8443/foo?x=1&y=2#frag").unwrap();
+        let req = RequestBuilder::from_url("GET", &url).unwrap().request();
+
+        assert_eq!(req.path, "/foo?x=1&y=2");
+        assert_eq!(req.port, 8443);
+    }
+
     #[test]
     fn test_make_header_full() {
         let req = RequestBuilder::new("GET", "example.com", 443, "/foo").request();
@@ -456,6 +677,8 @@ mod test {
                        hash: None,
                        app: None,
                        dlg: None,
+                       tsm: None,
+                       error: None,
                    });
     }
 
@@ -487,6 +710,8 @@ mod test {
                        hash: Some(hash.clone()),
                        app: Some("app".to_string()),
                        dlg: Some("dlg".to_string()),
+                       tsm: None,
+                       error: None,
                    });
     }
 
@@ -502,6 +727,18 @@ mod test {
         assert!(req.validate_header(&header, &credentials.key, Duration::minutes(1)));
     }
 
+    #[test]
+    fn test_make_header_with_offset() {
+        let req = RequestBuilder::new("GET", "example.com", 443, "/foo").request();
+        let credentials = Credentials {
+            id: "me".to_string(),
+            key: Key::new(vec![99u8; 32], &digest::SHA256),
+        };
+        let header = req.make_header_with_offset(&credentials, Duration::hours(-1)).unwrap();
+        assert!(!req.validate_header(&header, &credentials.key, Duration::minutes(1)));
+        assert!(req.validate_header(&header, &credentials.key, Duration::minutes(61)));
+    }
+
     #[test]
     fn test_validate_real_request() {
         let header = Header::from_str(REAL_HEADER).unwrap();
@@ -597,6 +834,84 @@ mod test {
                                      Duration::weeks(52000)));
     }
 
+    #[test]
+    fn test_check_header_failures() {
+        let key = Key::new("tok", &digest::SHA256);
+        let header = make_header_with_hash();
+        let req = RequestBuilder::new("", "", 0, "").request();
+
+        assert_eq!(req.check_header(&header, &key, Duration::weeks(52000)), Ok(()));
+        assert_eq!(req.check_header(&header, &key, Duration::minutes(1)),
+                   Err(AuthFailure::StaleTimestamp));
+        assert_eq!(req.check_header(&header,
+                                    &Key::new("WRONG", &digest::SHA256),
+                                    Duration::weeks(52000)),
+                   Err(AuthFailure::BadMac));
+
+        let hash = vec![99, 99, 99, 99];
+        let req = RequestBuilder::new("", "", 0, "")
+            .hash(Some(&hash[..]))
+            .request();
+        assert_eq!(req.check_header(&header, &key, Duration::weeks(52000)),
+                   Err(AuthFailure::BadPayloadHash));
+        assert_eq!(req.check_header(&make_header_without_hash(), &key, Duration::weeks(52000)),
+                   Err(AuthFailure::MissingRequiredPayloadHash));
+
+        let mut header = header.clone();
+        header.nonce = None;
+        assert_eq!(req.check_header(&header, &key, Duration::weeks(52000)),
+                   Err(AuthFailure::MissingAttributes));
+    }
+
+    #[test]
+    fn test_check_header_aliases() {
+        let credentials = Credentials {
+            id: "me".to_string(),
+            key: Key::new(vec![99u8; 32], &digest::SHA256),
+        };
+        let aliases = [("legacy.example.com", 443), ("api.example.com", 443), ("sidecar", 8080)];
+        let header = RequestBuilder::new("GET", "sidecar", 8080, "/foo")
+            .request()
+            .make_header(&credentials)
+            .unwrap();
+
+        // the request's own host and port are not considered
+        let req = RequestBuilder::new("GET", "10.0.0.5", 8080, "/foo").request();
+        let ts_skew = Duration::minutes(1);
+        assert_eq!(req.check_header_aliases(&header, &credentials.key, ts_skew, &aliases),
+                   Ok(2));
+        assert_eq!(req.check_header_aliases(&header, &credentials.key, ts_skew, &aliases[..2]),
+                   Err(AuthFailure::BadMac));
+        let none: &[(String, u16)] = &[];
+        assert_eq!(req.check_header_aliases(&header, &credentials.key, ts_skew, none),
+                   Err(AuthFailure::BadMac));
+
+        // other failures are reported once an alias matches
+        let ts_skew = Duration::minutes(-1);
+        assert_eq!(req.check_header_aliases(&header, &credentials.key, ts_skew, &aliases),
+                   Err(AuthFailure::StaleTimestamp));
+    }
+
+    #[test]
+    fn test_check_bewit_aliases() {
+        let credentials = Credentials {
+            id: "me".to_string(),
+            key: Key::new("tok", &digest::SHA256),
+        };
+        let aliases = vec![("legacy.example.com".to_string(), 443),
+                           ("api.example.com".to_string(), 443)];
+        let bewit = RequestBuilder::new("GET", "api.example.com", 443, "/x")
+            .request()
+            .make_bewit(&credentials, Duration::minutes(10))
+            .unwrap();
+
+        let req = RequestBuilder::new("GET", "10.0.0.5", 8080, "/x").request();
+        assert_eq!(req.check_bewit_aliases(&bewit, &credentials.key, &aliases), Ok(1));
+        let req = RequestBuilder::new("GET", "10.0.0.5", 8080, "/y").request();
+        assert_eq!(req.check_bewit_aliases(&bewit, &credentials.key, &aliases),
+                   Err(AuthFailure::BadMac));
+    }
+
     #[test]
     fn test_validate_hash_validated() {
         let header = make_header_with_hash();
@@ -653,4 +968,41 @@ mod test {
         let req = RequestBuilder::new("GET", "foo.com", 443, "/x/y/z").request();
         round_trip_bewit(req, Duration::minutes(-10), false);
     }
+    #[test]
+    fn test_check_bewit_failures() {
+        let credentials = Credentials {
+            id: "me".to_string(),
+            key: Key::new("tok", &digest::SHA256),
+        };
+        let req = RequestBuilder::new("GET", "foo.com", 443, "/x/y/z").request();
+
+        let bewit = req.make_bewit(&credentials, Duration::minutes(-10)).unwrap();
+        assert_eq!(req.check_bewit(&bewit, &credentials.key),
+                   Err(AuthFailure::AccessExpired));
+
+        let bewit = req.make_bewit(&credentials, Duration::minutes(10)).unwrap();
+        let req = RequestBuilder::new("GET", "foo.com", 443, "/x/y/WRONG").request();
+        assert_eq!(req.check_bewit(&bewit, &credentials.key), Err(AuthFailure::BadMac));
+
+        // a bewit made for a POST, against the advice of `make_bewit`, is never valid
+        let req = RequestBuilder::new("POST", "foo.com", 443, "/x/y/z").request();
+        let bewit = req.make_bewit(&credentials, Duration::minutes(10)).unwrap();
+        assert_eq!(req.check_bewit(&bewit, &credentials.key),
+                   Err(AuthFailure::InvalidMethod));
+    }
+
+    #[test]
+    fn test_validate_bewit_head() {
+        let credentials = Credentials {
+            id: "me".to_string(),
+            key: Key::new("tok", &digest::SHA256),
+        };
+        let bewit = RequestBuilder::new("GET", "foo.com", 443, "/x/y/z")
+            .request()
+            .make_bewit(&credentials, Duration::minutes(10))
+            .unwrap();
+
+        let req = RequestBuilder::new("HEAD", "foo.com", 443, "/x/y/z").request();
+        assert!(req.validate_bewit(&bewit, &credentials.key));
+    }
 }
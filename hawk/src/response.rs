@@ -1,66 +1,104 @@
 use mac::{Mac, MacType};
-use header::Header;
+use header::{Header, HeaderBuilder};
 use credentials::Key;
+use payload::{self, PayloadHasher};
 use error::*;
+use std::io::Read;
+use std::borrow::Cow;
+use cow::IntoOptionCow;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+#[cfg(feature = "serde")]
+use ext;
 
 /// A Response represents a response from an HTTP server.
 ///
 /// The structure is created from a request and then used to either create (server) or validate
 /// (client) a `Server-Authentication` header.
 ///
-/// Like `Request`, Responses are built with ResponseBuilders.
+/// Like `Request`, Responses are built with ResponseBuilders, and can be converted to an owned
+/// `Response<'static>` with `into_owned`.
 ///
 /// # Examples
 ///
 /// See the documentation in the crate root for examples.
 #[derive(Debug, Clone)]
 pub struct Response<'a> {
-    method: &'a str,
-    host: &'a str,
+    method: Cow<'a, str>,
+    host: Cow<'a, str>,
     port: u16,
-    path: &'a str,
-    req_header: &'a Header,
-    hash: Option<&'a [u8]>,
-    ext: Option<&'a str>,
+    path: Cow<'a, str>,
+    req_header: Cow<'a, Header>,
+    hash: Option<Cow<'a, [u8]>>,
+    ext: Option<Cow<'a, str>>,
 }
 
 impl<'a> Response<'a> {
     /// Create a new Header for this response, based on the given request and request header
     pub fn make_header(&self, key: &Key) -> Result<Header> {
+        self.make_header_with_hash(key, self.hash())
+    }
+
+    /// Create a new Header for this response, hashing the given payload to produce the `hash`
+    /// attribute.
+    ///
+    /// The `content_type` is the value of the response's `Content-Type` header; parameters such
+    /// as `charset` are removed before hashing, as the JS implementation does. The payload is
+    /// hashed with the key's digest algorithm. Any hash set with `ResponseBuilder::hash` is
+    /// ignored.
+    pub fn make_header_with_payload<B>(&self,
+                                       key: &Key,
+                                       content_type: &str,
+                                       payload: B)
+                                       -> Result<Header>
+        where B: AsRef<[u8]>
+    {
+        let hash = PayloadHasher::hash(payload::content_type(content_type),
+                                       key.algorithm(),
+                                       payload);
+        self.make_header_with_hash(key, Some(&hash[..]))
+    }
+
+    /// Similar to `make_header_with_payload`, but reading the payload from the given reader
+    /// until EOF.
+    pub fn make_header_with_payload_reader<R>(&self,
+                                              key: &Key,
+                                              content_type: &str,
+                                              reader: R)
+                                              -> Result<Header>
+        where R: Read
+    {
+        let hash = PayloadHasher::hash_reader(payload::content_type(content_type),
+                                              key.algorithm(),
+                                              reader)?;
+        self.make_header_with_hash(key, Some(&hash[..]))
+    }
+
+    fn make_header_with_hash(&self, key: &Key, hash: Option<&[u8]>) -> Result<Header> {
         let mac;
         let ts = self.req_header
             .ts
-            .ok_or("Missing `ts` atttribute in Hawk header")?;
+            .ok_or(Error::MissingAttribute("ts"))?;
         let nonce = self.req_header
             .nonce
             .as_ref()
-            .ok_or("Missing `nonce` attribute in Hawk header")?;
+            .ok_or(Error::MissingAttribute("nonce"))?;
         mac = Mac::new(MacType::Response,
                        key,
                        ts,
                        nonce,
-                       self.method,
-                       self.host,
+                       &self.method,
+                       &self.host,
                        self.port,
-                       self.path,
-                       self.hash,
-                       self.ext)?;
+                       &self.path,
+                       hash,
+                       self.ext())?;
 
         // Per JS implementation, the Server-Authorization header includes only mac, hash, and ext
-        Header::new(None,
-                    None,
-                    None,
-                    Some(mac),
-                    match self.ext {
-                        None => None,
-                        Some(v) => Some(v.to_string()),
-                    },
-                    match self.hash {
-                        None => None,
-                        Some(v) => Some(v.to_vec()),
-                    },
-                    None,
-                    None)
+        HeaderBuilder::server_authorization(mac)
+            .ext(self.ext())
+            .hash(hash)
+            .build()
     }
 
     /// Validate a Server-Authorization header.
@@ -68,6 +106,51 @@ impl<'a> Response<'a> {
     /// This checks that the MAC matches and, if a hash has been supplied locally,
     /// checks that one was provided from the server and that it, too, matches.
     pub fn validate_header(&self, response_header: &Header, key: &Key) -> bool {
+        self.validate_header_with_hash(response_header, key, self.hash())
+    }
+
+    /// Validate a Server-Authorization header, additionally checking that its hash matches the
+    /// given response payload.
+    ///
+    /// The `content_type` is the value of the response's `Content-Type` header, and the payload
+    /// is hashed with the key's digest algorithm, as in `make_header_with_payload`. Validation
+    /// fails if the server did not include a hash.
+    pub fn validate_header_with_payload<B>(&self,
+                                           response_header: &Header,
+                                           key: &Key,
+                                           content_type: &str,
+                                           payload: B)
+                                           -> bool
+        where B: AsRef<[u8]>
+    {
+        let hash = PayloadHasher::hash(payload::content_type(content_type),
+                                       key.algorithm(),
+                                       payload);
+        self.validate_header_with_hash(response_header, key, Some(&hash[..]))
+    }
+
+    /// Similar to `validate_header_with_payload`, but reading the payload from the given reader
+    /// until EOF.  Errors reading the payload are returned as errors, rather than as a failed
+    /// validation.
+    pub fn validate_header_with_payload_reader<R>(&self,
+                                                  response_header: &Header,
+                                                  key: &Key,
+                                                  content_type: &str,
+                                                  reader: R)
+                                                  -> Result<bool>
+        where R: Read
+    {
+        let hash = PayloadHasher::hash_reader(payload::content_type(content_type),
+                                              key.algorithm(),
+                                              reader)?;
+        Ok(self.validate_header_with_hash(response_header, key, Some(&hash[..])))
+    }
+
+    fn validate_header_with_hash(&self,
+                                 response_header: &Header,
+                                 key: &Key,
+                                 local_hash: Option<&[u8]>)
+                                 -> bool {
         // extract required fields, returning early if they are not present
         let ts = match self.req_header.ts {
             Some(ts) => ts,
@@ -101,10 +184,10 @@ impl<'a> Response<'a> {
                        key,
                        ts,
                        nonce,
-                       self.method,
-                       self.host,
+                       &self.method,
+                       &self.host,
                        self.port,
-                       self.path,
+                       &self.path,
                        header_hash,
                        header_ext) {
             Ok(calculated_mac) => {
@@ -118,7 +201,7 @@ impl<'a> Response<'a> {
         };
 
         // ..then the hashes
-        if let Some(local_hash) = self.hash {
+        if let Some(local_hash) = local_hash {
             if let Some(server_hash) = header_hash {
                 if local_hash != server_hash {
                     return false;
@@ -133,6 +216,34 @@ impl<'a> Response<'a> {
 
         true
     }
+
+    /// Convert this response into one that owns all of its fields, copying any that are
+    /// borrowed.
+    pub fn into_owned(self) -> Response<'static> {
+        Response {
+            method: Cow::Owned(self.method.into_owned()),
+            host: Cow::Owned(self.host.into_owned()),
+            port: self.port,
+            path: Cow::Owned(self.path.into_owned()),
+            req_header: Cow::Owned(self.req_header.into_owned()),
+            hash: self.hash.map(|v| Cow::Owned(v.into_owned())),
+            ext: self.ext.map(|v| Cow::Owned(v.into_owned())),
+        }
+    }
+
+    fn hash(&self) -> Option<&[u8]> {
+        match self.hash {
+            Some(ref v) => Some(v.as_ref()),
+            None => None,
+        }
+    }
+
+    fn ext(&self) -> Option<&str> {
+        match self.ext {
+            Some(ref v) => Some(v.as_ref()),
+            None => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -142,18 +253,22 @@ impl<'a> ResponseBuilder<'a> {
     /// Generate a new Response from a request header.
     ///
     /// This is more commonly accessed through `Request::make_response`.
-    pub fn from_request_header(req_header: &'a Header,
-                               method: &'a str,
-                               host: &'a str,
-                               port: u16,
-                               path: &'a str)
-                               -> Self {
+    pub fn from_request_header<M, H, P>(req_header: &'a Header,
+                                        method: M,
+                                        host: H,
+                                        port: u16,
+                                        path: P)
+                                        -> Self
+        where M: Into<Cow<'a, str>>,
+              H: Into<Cow<'a, str>>,
+              P: Into<Cow<'a, str>>
+    {
         ResponseBuilder(Response {
-            method: method,
-            host: host,
+            method: method.into(),
+            host: host.into(),
             port: port,
-            path: path,
-            req_header: req_header,
+            path: path.into(),
+            req_header: Cow::Borrowed(req_header),
             hash: None,
             ext: None,
         })
@@ -161,32 +276,47 @@ impl<'a> ResponseBuilder<'a> {
 
     /// Set the content hash for the response.
     ///
-    /// This should always be calculated from the response payload, not copied from a header.
-    pub fn hash<H: Into<Option<&'a [u8]>>>(mut self, hash: H) -> Self {
-        self.0.hash = hash.into();
+    /// This should always be calculated from the response payload, not copied from a header.  It
+    /// may be borrowed or owned.
+    pub fn hash<H: IntoOptionCow<'a, [u8]>>(mut self, hash: H) -> Self {
+        self.0.hash = hash.into_option_cow();
         self
     }
 
     /// Set the `ext` Hawk property for the response.
     ///
     /// This need only be set on the server; it is ignored in validating responses on the client.
-    pub fn ext<S: Into<Option<&'a str>>>(mut self, ext: S) -> Self {
-        self.0.ext = ext.into();
+    /// It may be borrowed or owned.
+    pub fn ext<S: IntoOptionCow<'a, str>>(mut self, ext: S) -> Self {
+        self.0.ext = ext.into_option_cow();
         self
     }
 
+    /// Set the `ext` Hawk property for the response to the given value, encoded as base64 JSON
+    /// as described in the `ext` module.
+    #[cfg(feature = "serde")]
+    pub fn ext_json<T: Serialize + ?Sized>(self, value: &T) -> Result<Self> {
+        Ok(self.ext(ext::encode(value)?))
+    }
+
     /// Get the response from this builder
     pub fn response(self) -> Response<'a> {
         self.0
     }
+
+    /// Convert this builder into one that owns all of its fields; see `Response::into_owned`.
+    pub fn into_owned(self) -> ResponseBuilder<'static> {
+        ResponseBuilder(self.0.into_owned())
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::ResponseBuilder;
+    use super::{Response, ResponseBuilder};
     use header::Header;
     use credentials::Key;
     use mac::Mac;
+    use payload::PayloadHasher;
     use time::Timespec;
     use ring::digest;
 
@@ -302,4 +432,84 @@ mod test {
                 .response();
         assert!(!resp.validate_header(&server_header, &Key::new("tok", &digest::SHA256)));
     }
+
+    #[test]
+    fn test_make_header_with_payload() {
+        let req_header = make_req_header();
+        let key = Key::new("tok", &digest::SHA256);
+        let resp =
+            ResponseBuilder::from_request_header(&req_header, "POST", "localhost", 9988, "/a/b")
+                .ext("server-ext")
+                .response();
+        let server_header = resp.make_header_with_payload(&key,
+                                                          "Text/Plain; charset=utf-8",
+                                                          "OK")
+            .unwrap();
+        assert_eq!(server_header.hash,
+                   Some(PayloadHasher::hash("text/plain", &digest::SHA256, "OK")));
+        assert_eq!(server_header.ext, Some("server-ext".to_string()));
+
+        // the same header is produced from a reader
+        let server_header2 = resp.make_header_with_payload_reader(&key,
+                                                                  "text/plain",
+                                                                  "OK".as_bytes())
+            .unwrap();
+        assert_eq!(server_header2, server_header);
+
+        assert!(resp.validate_header_with_payload(&server_header, &key, "text/plain", "OK"));
+        assert!(resp.validate_header_with_payload_reader(&server_header,
+                                                         &key,
+                                                         "text/plain",
+                                                         "OK".as_bytes())
+                    .unwrap());
+    }
+
+    #[test]
+    fn test_validation_with_payload_mismatch() {
+        let req_header = make_req_header();
+        let key = Key::new("tok", &digest::SHA256);
+        let resp =
+            ResponseBuilder::from_request_header(&req_header, "POST", "localhost", 9988, "/a/b")
+                .response();
+        let server_header = resp.make_header_with_payload(&key, "text/plain", "OK").unwrap();
+
+        assert!(!resp.validate_header_with_payload(&server_header, &key, "text/plain", "NO"));
+        assert!(!resp.validate_header_with_payload(&server_header, &key, "text/html", "OK"));
+
+        // a header without a hash does not validate against a payload
+        let server_header = resp.make_header(&key).unwrap();
+        assert!(!resp.validate_header_with_payload(&server_header, &key, "text/plain", "OK"));
+    }
+    #[test]
+    fn test_into_owned() {
+        fn make_response() -> Response<'static> {
+            let req_header = make_req_header();
+            let hash = vec![1, 2, 3, 4];
+            ResponseBuilder::from_request_header(&req_header, "POST", "localhost", 9988, "/a/b")
+                .hash(&hash[..])
+                .ext("server-ext")
+                .response()
+                .into_owned()
+        }
+
+        fn is_send_sync_static<T: Send + Sync + 'static>(_: &T) {}
+
+        let resp = make_response();
+        is_send_sync_static(&resp);
+        let mac: Mac = Mac::from(vec![33, 147, 159, 211, 184, 194, 189, 74, 53, 229, 241, 161,
+                                      215, 145, 22, 34, 206, 207, 242, 100, 33, 193, 36, 96, 149,
+                                      133, 180, 4, 132, 87, 207, 238]);
+        let server_header = Header::new(None,
+                                        None,
+                                        None,
+                                        Some(mac),
+                                        Some("server-ext"),
+                                        Some(vec![1, 2, 3, 4]),
+                                        None,
+                                        None)
+            .unwrap();
+        assert!(resp.validate_header(&server_header, &Key::new("tok", &digest::SHA256)));
+        assert_eq!(resp.make_header(&Key::new("tok", &digest::SHA256)).unwrap(),
+                   server_header);
+    }
 }
@@ -1,7 +1,7 @@
 use hyper::header::Scheme;
 use std::str::FromStr;
 use std::fmt;
-use hawk::Header;
+use hawk::{self, Header};
 use std::ops::Deref;
 
 /// HawkScheme is a Hyper Scheme implementation for Hawk Authorization headers.
@@ -20,12 +20,9 @@ impl Deref for HawkScheme {
 }
 
 impl FromStr for HawkScheme {
-    type Err = String;
-    fn from_str(s: &str) -> Result<HawkScheme, String> {
-        match Header::from_str(s) {
-            Ok(h) => Ok(HawkScheme(h)),
-            Err(e) => Err(e.to_string()),
-        }
+    type Err = hawk::Error;
+    fn from_str(s: &str) -> Result<HawkScheme, hawk::Error> {
+        Header::from_str(s).map(HawkScheme)
     }
 }
 
@@ -0,0 +1,369 @@
+use std::error;
+use std::fmt;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicIsize, Ordering};
+use hawk::{self, AuthFailure, Credentials, Header, PayloadHasher, RequestBuilder};
+use hyper;
+use hyper::client;
+use hyper::header::{Authorization, Headers};
+use hyper::method::Method;
+use hyper::status::StatusCode;
+use time::{self, Duration};
+use url::Url;
+use authscheme::HawkScheme;
+use serverauth::ServerAuthorization;
+use wwwauthenticate::WwwAuthenticate;
+
+/// An error from a Hawk-authenticated client request.
+#[derive(Debug)]
+pub enum Error {
+    /// An error generating the Hawk header
+    Hawk(hawk::Error),
+    /// An error making the HTTP request
+    Http(hyper::Error),
+    /// An error reading the response body
+    Io(io::Error),
+    /// The server rejected the request's authentication with a 401 response.  The failure is
+    /// taken from the response's `WWW-Authenticate` challenge, if it has one.
+    Rejected(Option<AuthFailure>),
+    /// The response had no `Server-Authorization` header
+    MissingServerAuthorization(StatusCode),
+    /// The response's `Server-Authorization` header, or the response body's hash, did not
+    /// validate, so the response may not have come from the server, or may have been modified
+    InvalidServerAuthorization,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Hawk(ref e) => write!(f, "Hawk error: {}", e),
+            Error::Http(ref e) => write!(f, "HTTP error: {}", e),
+            Error::Io(ref e) => write!(f, "I/O error: {}", e),
+            Error::Rejected(Some(failure)) => write!(f, "Authentication rejected: {}", failure),
+            Error::Rejected(None) => write!(f, "Authentication rejected"),
+            Error::MissingServerAuthorization(status) => {
+                write!(f,
+                       "Response with status {} has no Server-Authorization header",
+                       status)
+            }
+            Error::InvalidServerAuthorization => write!(f, "Invalid Server-Authorization header"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Hawk(ref e) => Some(e),
+            Error::Http(ref e) => Some(e),
+            Error::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<hawk::Error> for Error {
+    fn from(e: hawk::Error) -> Self {
+        Error::Hawk(e)
+    }
+}
+
+impl From<hyper::Error> for Error {
+    fn from(e: hyper::Error) -> Self {
+        Error::Http(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// A response whose `Server-Authorization` header, and body hash if any, have been verified.
+#[derive(Debug)]
+pub struct VerifiedResponse {
+    /// The response status
+    pub status: StatusCode,
+    /// The response headers
+    pub headers: Headers,
+    /// The response body
+    pub body: Vec<u8>,
+}
+
+/// A Hyper client that signs each request with Hawk credentials.
+///
+/// When the server rejects a request with a stale-timestamp challenge whose `tsm` verifies with
+/// the client's key, the client adjusts its clock offset to match the server's and retries the
+/// request once, with a fresh nonce.  The offset is kept for later requests, so a `Client` should
+/// be shared (it is `Sync`) rather than created per request.
+pub struct Client {
+    client: hyper::Client,
+    credentials: Credentials,
+    // offset from the local clock to the server's clock, in seconds
+    offset: AtomicIsize,
+}
+
+impl Client {
+    /// Create a new client with the given credentials, using a default Hyper client.
+    pub fn new(credentials: Credentials) -> Client {
+        Client::with_client(hyper::Client::new(), credentials)
+    }
+
+    /// Create a new client with the given credentials, sending requests with the given Hyper
+    /// client.
+    pub fn with_client(client: hyper::Client, credentials: Credentials) -> Client {
+        Client {
+            client: client,
+            credentials: credentials,
+            offset: AtomicIsize::new(0),
+        }
+    }
+
+    /// Get the credentials used by this client.
+    pub fn credentials(&self) -> &Credentials {
+        &self.credentials
+    }
+
+    /// Get the offset currently applied to the local clock when generating headers.
+    pub fn offset(&self) -> Duration {
+        Duration::seconds(self.offset.load(Ordering::SeqCst) as i64)
+    }
+
+    /// Set the offset applied to the local clock, such as one saved from a previous run.
+    pub fn set_offset(&self, offset: Duration) {
+        self.offset.store(offset.num_seconds() as isize, Ordering::SeqCst);
+    }
+
+    /// Send a request to the given URL with a Hawk `Authorization` header.
+    ///
+    /// The `request` describes the same request for Hawk's purposes, and is typically built with
+    /// `hawk::RequestBuilder::from_url`, adding a payload hash or `ext` as required.  It is
+    /// signed with a fresh nonce and timestamp each time it is sent.
+    ///
+    /// The Hawk header that was sent is returned alongside the response, for use in validating
+    /// the `Server-Authorization` header.
+    pub fn send(&self,
+                method: Method,
+                url: &Url,
+                request: &hawk::Request,
+                headers: Headers,
+                body: Option<&[u8]>)
+                -> Result<(client::Response, Header), Error> {
+        let header = request.make_header_with_offset(&self.credentials, self.offset())?;
+        let mut res = self.send_once(method.clone(), url, &header, headers.clone(), body)?;
+
+        let server_ts = match self.stale_timestamp(&res) {
+            Some(ts) => ts,
+            None => return Ok((res, header)),
+        };
+
+        // read the remainder of the rejected response so the connection can be reused
+        let _ = io::copy(&mut res, &mut io::sink());
+        drop(res);
+
+        let offset = server_ts - time::now().to_timespec();
+        self.set_offset(offset);
+
+        let header = request.make_header_with_offset(&self.credentials, offset)?;
+        let res = self.send_once(method, url, &header, headers, body)?;
+        Ok((res, header))
+    }
+
+    /// Send a request to the given URL, signed over the URL and, if given, a hash of the body
+    /// with its content type, and verify the response.
+    ///
+    /// The response is read in full, then its `Server-Authorization` header is validated,
+    /// along with the body against the header's hash if it has one.  A 401 response with a Hawk
+    /// challenge gives `Error::Rejected`; any other response without a valid
+    /// `Server-Authorization` header gives an error, too.
+    pub fn request(&self,
+                   method: Method,
+                   url: &Url,
+                   body: Option<(&str, &[u8])>)
+                   -> Result<VerifiedResponse, Error> {
+        let hash = body.map(|(content_type, body)| {
+            PayloadHasher::hash(hawk::content_type(content_type),
+                                self.credentials.key.algorithm(),
+                                body)
+        });
+        let request = RequestBuilder::from_url(method.as_ref(), url)?
+            .hash(hash.as_ref().map(|h| &h[..]))
+            .request();
+        let mut headers = Headers::new();
+        if let Some((content_type, _)) = body {
+            headers.set_raw("Content-Type", vec![content_type.as_bytes().to_vec()]);
+        }
+
+        let (mut res, header) =
+            self.send(method.clone(), url, &request, headers, body.map(|b| b.1))?;
+        let mut body = vec![];
+        res.read_to_end(&mut body)?;
+
+        if res.status == StatusCode::Unauthorized {
+            if let Some(challenge) = res.headers.get::<WwwAuthenticate<HawkScheme>>() {
+                return Err(Error::Rejected(AuthFailure::from_header(challenge)));
+            }
+        }
+        let valid = {
+            let server_header = match res.headers.get::<ServerAuthorization<HawkScheme>>() {
+                Some(server_header) => server_header,
+                None => return Err(Error::MissingServerAuthorization(res.status)),
+            };
+            let response = request.make_response_builder(&header).response();
+            if server_header.hash.is_some() {
+                response.validate_header_with_payload(server_header,
+                                                      &self.credentials.key,
+                                                      content_type(&res.headers),
+                                                      &body)
+            } else {
+                response.validate_header(server_header, &self.credentials.key)
+            }
+        };
+        if !valid {
+            return Err(Error::InvalidServerAuthorization);
+        }
+
+        Ok(VerifiedResponse {
+               status: res.status,
+               headers: res.headers.clone(),
+               body: body,
+           })
+    }
+
+    fn send_once(&self,
+                 method: Method,
+                 url: &Url,
+                 header: &Header,
+                 mut headers: Headers,
+                 body: Option<&[u8]>)
+                 -> hyper::Result<client::Response> {
+        headers.set(Authorization(HawkScheme(header.clone())));
+        let req = self.client
+            .request(method, url.clone())
+            .headers(headers);
+        match body {
+            Some(body) => req.body(body).send(),
+            None => req.send(),
+        }
+    }
+
+    /// If this response is a stale-timestamp challenge with a valid `tsm`, return the server's
+    /// timestamp.
+    fn stale_timestamp(&self, res: &client::Response) -> Option<time::Timespec> {
+        if res.status != StatusCode::Unauthorized {
+            return None;
+        }
+        let challenge: &WwwAuthenticate<HawkScheme> = match res.headers.get() {
+            Some(challenge) => challenge,
+            None => return None,
+        };
+        if AuthFailure::from_header(challenge) != Some(AuthFailure::StaleTimestamp) {
+            return None;
+        }
+        challenge.challenge_ts(&self.credentials.key)
+    }
+}
+
+/// A reader over the body of a Hyper response, verifying the body against the hash in the
+/// response's `Server-Authorization` header as it is read.
+///
+/// The header's MAC is validated when the reader is created, but the body can only be checked
+/// once all of it has been read, so the read that reaches EOF returns an `InvalidData` error if
+/// the body does not match the hash.  Callers should not act on the body until they have read
+/// it to EOF without error.
+pub struct VerifyingReader {
+    res: client::Response,
+    hasher: Option<PayloadHasher>,
+    expected: Vec<u8>,
+    failed: bool,
+}
+
+impl VerifyingReader {
+    /// Wrap a response to the given request, which was sent with the given Hawk header, as
+    /// returned from `Client::send`.
+    ///
+    /// This fails with `Error::MissingServerAuthorization` if the response has no
+    /// `Server-Authorization` header, or with `Error::InvalidServerAuthorization` if the header's
+    /// MAC does not validate or the header has no hash.
+    pub fn new(res: client::Response,
+               request: &hawk::Request,
+               header: &Header,
+               key: &hawk::Key)
+               -> Result<VerifyingReader, Error> {
+        let expected = {
+            let server_header = match res.headers.get::<ServerAuthorization<HawkScheme>>() {
+                Some(server_header) => server_header,
+                None => return Err(Error::MissingServerAuthorization(res.status)),
+            };
+            let expected = match server_header.hash {
+                Some(ref hash) => hash.clone(),
+                None => return Err(Error::InvalidServerAuthorization),
+            };
+            let response = request.make_response_builder(header).response();
+            if !response.validate_header(server_header, key) {
+                return Err(Error::InvalidServerAuthorization);
+            }
+            expected
+        };
+        let hasher = PayloadHasher::new(hawk::content_type(content_type(&res.headers)),
+                                        key.algorithm());
+
+        Ok(VerifyingReader {
+               res: res,
+               hasher: Some(hasher),
+               expected: expected,
+               failed: false,
+           })
+    }
+
+    /// Get the response status.
+    pub fn status(&self) -> StatusCode {
+        self.res.status
+    }
+
+    /// Get the response headers.
+    pub fn headers(&self) -> &Headers {
+        &self.res.headers
+    }
+
+    /// Get the wrapped response, abandoning verification of the rest of the body.
+    pub fn into_inner(self) -> client::Response {
+        self.res
+    }
+
+    fn mismatch() -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData,
+                       "response body does not match the Server-Authorization hash")
+    }
+}
+
+impl Read for VerifyingReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.failed {
+            return Err(VerifyingReader::mismatch());
+        }
+        let n = self.res.read(buf)?;
+        if n > 0 {
+            if let Some(ref mut hasher) = self.hasher {
+                hasher.update(&buf[..n]);
+            }
+        } else if !buf.is_empty() {
+            if let Some(hasher) = self.hasher.take() {
+                if hasher.finish() != self.expected {
+                    self.failed = true;
+                    return Err(VerifyingReader::mismatch());
+                }
+            }
+        }
+        Ok(n)
+    }
+}
+
+/// Get the value of the `Content-Type` header, or an empty string if it is absent or invalid.
+fn content_type(headers: &Headers) -> &str {
+    headers.get_raw("Content-Type")
+        .and_then(|values| values.first())
+        .and_then(|value| ::std::str::from_utf8(value).ok())
+        .unwrap_or("")
+}
@@ -0,0 +1,406 @@
+use std::fmt;
+use std::io::{self, Cursor, Read, Write};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use hawk::http::server::{authenticate, needs_body, Authenticated, KeyLookup, PeerAddr, Rejection,
+                         ServerConfig, Verified, DEFAULT_PAYLOAD_LIMIT};
+use http;
+use hyper;
+use hyper::buffer::BufReader;
+use hyper::header::{ContentLength, Encoding, Header, HeaderFormat, Headers, TransferEncoding};
+use hyper::method::Method;
+use hyper::net::{Fresh, NetworkStream};
+use hyper::server::{Handler, Request, Response};
+use hyper::status::StatusCode;
+use hyper::uri::RequestUri;
+use authscheme::HawkScheme;
+use serverauth::ServerAuthorization;
+
+/// The client authenticated by `HawkHandler`.
+///
+/// Hyper requests carry no extensions, so `HawkHandler` passes this to the inner handler as a
+/// typed header, to be retrieved with `req.headers.get::<HawkAuth>()`.  It cannot be parsed from
+/// a raw header, so clients cannot supply it themselves.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HawkAuth(pub Authenticated);
+
+impl Header for HawkAuth {
+    fn header_name() -> &'static str {
+        "X-Hawk-Auth"
+    }
+
+    fn parse_header(_raw: &[Vec<u8>]) -> hyper::Result<HawkAuth> {
+        Err(hyper::Error::Header)
+    }
+}
+
+impl HeaderFormat for HawkAuth {
+    fn fmt_header(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0.id)
+    }
+}
+
+/// A Hyper handler that authenticates each request with Hawk before passing it to an inner
+/// handler, and signs each response with a `Server-Authorization` header.
+///
+/// Requests that fail authentication are answered with a 401 or 400 response carrying a
+/// `WWW-Authenticate` challenge, without calling the inner handler.  The inner handler finds the
+/// authenticated client in the request's `HawkAuth` header.
+///
+/// When the `Authorization` header includes a payload hash, the request body is read and
+/// verified before the inner handler is called, and the inner handler reads it from a buffer.
+/// Bodies larger than `payload_limit` are answered with a 413 response.  The response, too, is
+/// then buffered, as it is when `hash_responses` is set so that its body can be hashed.
+/// Otherwise, the inner handler writes directly to the connection.
+///
+/// A buffered response is parsed from what the inner handler wrote, once it returns, so the
+/// inner handler must finish its response by calling `send` or `end`, or by dropping it.  If
+/// what it wrote is not a complete response, the client is sent a 500 response instead.
+pub struct HawkHandler<H> {
+    inner: H,
+    keys: Arc<dyn KeyLookup>,
+    config: ServerConfig,
+    hash_responses: bool,
+    payload_limit: usize,
+}
+
+impl<H: Handler> HawkHandler<H> {
+    /// Create a new handler, looking up client keys with `keys`.
+    pub fn new<K>(inner: H, keys: K, config: ServerConfig) -> HawkHandler<H>
+        where K: KeyLookup + 'static
+    {
+        HawkHandler {
+            inner: inner,
+            keys: Arc::new(keys),
+            config: config,
+            hash_responses: false,
+            payload_limit: DEFAULT_PAYLOAD_LIMIT,
+        }
+    }
+
+    /// Set whether to include a hash of the response payload in `Server-Authorization`.  This
+    /// requires buffering each response.
+    pub fn hash_responses(mut self, hash_responses: bool) -> Self {
+        self.hash_responses = hash_responses;
+        self
+    }
+
+    /// Set the limit, in bytes, on the size of request bodies read to verify a payload hash.
+    /// This defaults to `DEFAULT_PAYLOAD_LIMIT`.
+    pub fn payload_limit(mut self, payload_limit: usize) -> Self {
+        self.payload_limit = payload_limit;
+        self
+    }
+
+    /// Get a reference to the inner handler.
+    pub fn get_ref(&self) -> &H {
+        &self.inner
+    }
+}
+
+impl<H: Handler> Handler for HawkHandler<H> {
+    fn handle<'a, 'k>(&'a self, mut req: Request<'a, 'k>, mut res: Response<'a, Fresh>) {
+        let parts = match http_parts(&req) {
+            Some(parts) => parts,
+            None => {
+                *res.status_mut() = StatusCode::BadRequest;
+                let _ = res.send(b"Invalid request");
+                return;
+            }
+        };
+
+        let body = if needs_body(&parts) {
+            let limit = self.payload_limit as u64;
+            if req.headers.get::<ContentLength>().map(|len| len.0 > limit).unwrap_or(false) {
+                return payload_too_large(res);
+            }
+            // read one byte past the limit, to tell a body at the limit from a larger one
+            let mut body = vec![];
+            if (&mut req).take(limit + 1).read_to_end(&mut body).is_err() {
+                *res.status_mut() = StatusCode::BadRequest;
+                let _ = res.send(b"Error reading request body");
+                return;
+            }
+            if body.len() as u64 > limit {
+                return payload_too_large(res);
+            }
+            Some(body)
+        } else {
+            None
+        };
+
+        let verified = match authenticate(&parts, body.as_ref().map(|b| &b[..]), &*self.keys,
+                                          &self.config) {
+            Ok(verified) => verified,
+            Err(rejection) => return reject(rejection, res),
+        };
+        req.headers.set(HawkAuth(verified.client().clone()));
+
+        if body.is_none() && !self.hash_responses {
+            if let Ok(Some(header)) = verified.server_authorization(None) {
+                res.headers_mut().set(ServerAuthorization(HawkScheme(header)));
+            }
+            return self.inner.handle(req, res);
+        }
+
+        // run the inner handler against buffers, then sign and send what it wrote
+        let mut written = vec![];
+        let mut headers = Headers::new();
+        match body {
+            Some(body) => {
+                let mut stream = BufferedStream::new(req.remote_addr, body);
+                let mut reader = BufReader::new(&mut stream as &mut dyn NetworkStream);
+                let mut buffered = match Request::new(&mut reader, req.remote_addr) {
+                    Ok(buffered) => buffered,
+                    Err(_) => {
+                        *res.status_mut() = StatusCode::InternalServerError;
+                        return;
+                    }
+                };
+                let len = buffered.headers.get::<ContentLength>().cloned();
+                buffered.method = req.method.clone();
+                buffered.uri = req.uri.clone();
+                buffered.version = req.version;
+                buffered.headers = req.headers.clone();
+                buffered.headers.remove::<TransferEncoding>();
+                if let Some(len) = len {
+                    buffered.headers.set(len);
+                }
+                self.inner.handle(buffered, Response::new(&mut written, &mut headers));
+            }
+            None => self.inner.handle(req, Response::new(&mut written, &mut headers)),
+        }
+        send_buffered(&verified, self.hash_responses, written, headers, res);
+    }
+
+    fn check_continue(&self, request: (&Method, &RequestUri, &Headers)) -> StatusCode {
+        self.inner.check_continue(request)
+    }
+
+    fn on_connection_start(&self) {
+        self.inner.on_connection_start()
+    }
+
+    fn on_connection_end(&self) {
+        self.inner.on_connection_end()
+    }
+}
+
+/// Describe a Hyper request as `http` request parts, for `authenticate`.
+fn http_parts(req: &Request) -> Option<http::request::Parts> {
+    let uri = match req.uri {
+        RequestUri::AbsolutePath(ref path) => path.clone(),
+        RequestUri::AbsoluteUri(ref url) => url.to_string(),
+        RequestUri::Authority(ref authority) => authority.clone(),
+        RequestUri::Star => "*".to_string(),
+    };
+    let mut builder = http::Request::builder()
+        .method(req.method.as_ref())
+        .uri(uri)
+        .extension(PeerAddr(req.remote_addr.ip()));
+    for view in req.headers.iter() {
+        for value in req.headers.get_raw(view.name()).unwrap_or(&[]) {
+            builder = builder.header(view.name(), &value[..]);
+        }
+    }
+    builder.body(()).ok().map(|req| req.into_parts().0)
+}
+
+/// Answer a request that failed authentication.
+fn reject(rejection: Rejection, mut res: Response<Fresh>) {
+    let message = rejection.failure().to_string();
+    let response = rejection.response(());
+    *res.status_mut() = StatusCode::from_u16(response.status().as_u16());
+    for (name, value) in response.headers() {
+        res.headers_mut().set_raw(name.as_str().to_string(), vec![value.as_bytes().to_vec()]);
+    }
+    let _ = res.send(message.as_bytes());
+}
+
+/// Answer a request whose body is larger than the payload limit.
+fn payload_too_large(mut res: Response<Fresh>) {
+    *res.status_mut() = StatusCode::PayloadTooLarge;
+    let _ = res.send(b"Request body too large");
+}
+
+/// Send a response that the inner handler wrote to `written`, signing it along the way.
+fn send_buffered(verified: &Verified,
+                 hash_responses: bool,
+                 written: Vec<u8>,
+                 mut headers: Headers,
+                 mut res: Response<Fresh>) {
+    let (status, body) = match parse_response(&written, &mut headers) {
+        Some(parsed) => parsed,
+        None => {
+            *res.status_mut() = StatusCode::InternalServerError;
+            return;
+        }
+    };
+
+    let content_type = headers.get_raw("Content-Type")
+        .and_then(|values| values.first())
+        .and_then(|value| ::std::str::from_utf8(value).ok())
+        .unwrap_or("")
+        .to_string();
+    let payload = if hash_responses {
+        Some((&content_type[..], &body[..]))
+    } else {
+        None
+    };
+    if let Ok(Some(header)) = verified.server_authorization(payload) {
+        headers.set(ServerAuthorization(HawkScheme(header)));
+    }
+
+    *res.status_mut() = status;
+    *res.headers_mut() = headers;
+    let _ = res.send(&body);
+}
+
+/// Parse the status and body from a response written by Hyper, removing the chunked transfer
+/// encoding that Hyper added to the headers, if any.
+///
+/// Hyper 0.10 has no way to get at a response's body other than as written to a stream, so this
+/// depends on the HTTP/1.1 framing Hyper writes: a status line and headers (the headers are
+/// written to `headers` as well, and taken from there), then the body, either as written or in
+/// chunks.  Only the framing Hyper writes itself needs to be understood.
+fn parse_response(written: &[u8], headers: &mut Headers) -> Option<(StatusCode, Vec<u8>)> {
+    let head_len = written.windows(4).position(|w| w == b"\r\n\r\n")? + 4;
+    let status_line = written[..head_len].split(|&b| b == b'\r').next()?;
+    let status = ::std::str::from_utf8(status_line).ok()?.split(' ').nth(1)?.parse().ok()?;
+    let body = &written[head_len..];
+
+    let chunked = match headers.get_mut::<TransferEncoding>() {
+        Some(&mut TransferEncoding(ref mut encodings)) => {
+            if encodings.last() == Some(&Encoding::Chunked) {
+                encodings.pop();
+                true
+            } else {
+                false
+            }
+        }
+        None => false,
+    };
+    if headers.get::<TransferEncoding>().map(|te| te.is_empty()).unwrap_or(false) {
+        headers.remove::<TransferEncoding>();
+    }
+
+    let body = if chunked {
+        dechunk(body)?
+    } else {
+        body.to_vec()
+    };
+    Some((StatusCode::from_u16(status), body))
+}
+
+/// Decode a body written with chunked transfer encoding.
+fn dechunk(mut data: &[u8]) -> Option<Vec<u8>> {
+    let mut body = vec![];
+    loop {
+        let line_len = data.windows(2).position(|w| w == b"\r\n")?;
+        let size = ::std::str::from_utf8(&data[..line_len]).ok()?;
+        let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+        data = &data[line_len + 2..];
+        if size == 0 {
+            return Some(body);
+        }
+        if data.len() < size + 2 {
+            return None;
+        }
+        body.extend_from_slice(&data[..size]);
+        data = &data[size + 2..];
+    }
+}
+
+/// A `NetworkStream` over a buffered request body, from which Hyper can read a request for the
+/// inner handler.  Writes are discarded.
+///
+/// Hyper 0.10 can only create a `Request` by parsing one from a stream, so this supplies a
+/// stand-in request head ahead of the body, and the handler then replaces the parsed method,
+/// URI, version and headers with the original request's.  The head's `Content-Length` is what
+/// makes Hyper read the buffered body, and no more.
+struct BufferedStream {
+    data: Cursor<Vec<u8>>,
+    peer: SocketAddr,
+}
+
+impl BufferedStream {
+    fn new(peer: SocketAddr, body: Vec<u8>) -> BufferedStream {
+        let mut data = format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n", body.len())
+            .into_bytes();
+        data.extend(body);
+        BufferedStream {
+            data: Cursor::new(data),
+            peer: peer,
+        }
+    }
+}
+
+impl Read for BufferedStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.data.read(buf)
+    }
+}
+
+impl Write for BufferedStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl NetworkStream for BufferedStream {
+    fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+        Ok(self.peer)
+    }
+
+    fn set_read_timeout(&self, _dur: Option<Duration>) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn set_write_timeout(&self, _dur: Option<Duration>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_dechunk() {
+        assert_eq!(dechunk(b"3\r\nfoo\r\na;ext=1\r\n0123456789\r\n0\r\n\r\n"),
+                   Some(b"foo0123456789".to_vec()));
+        assert_eq!(dechunk(b"0\r\n\r\n"), Some(vec![]));
+        assert_eq!(dechunk(b"5\r\nfoo"), None);
+        assert_eq!(dechunk(b"zz\r\n"), None);
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let mut written = vec![];
+        let mut headers = Headers::new();
+        {
+            let mut res = Response::new(&mut written, &mut headers);
+            *res.status_mut() = StatusCode::Created;
+            let mut res = res.start().unwrap();
+            res.write_all(b"hello, ").unwrap();
+            res.write_all(b"world").unwrap();
+            res.end().unwrap();
+        }
+        let (status, body) = parse_response(&written, &mut headers).unwrap();
+        assert_eq!(status, StatusCode::Created);
+        assert_eq!(body, b"hello, world");
+        assert!(!headers.has::<TransferEncoding>());
+    }
+
+    #[test]
+    fn test_hawk_auth_not_parsed() {
+        let mut headers = Headers::new();
+        headers.set_raw("X-Hawk-Auth", vec![b"spoofed".to_vec()]);
+        assert!(headers.get::<HawkAuth>().is_none());
+    }
+}
@@ -10,7 +10,7 @@
 //! # extern crate url;
 //! use hawk::{RequestBuilder, Credentials, Key, SHA256};
 //! use std::io::{Read, Write};
-//! use hyper_hawk::{HawkScheme, ServerAuthorization};
+//! use hyper_hawk::{HawkScheme, ServerAuthorization, ServerRequest};
 //! use hyper::Client;
 //! use hyper::header;
 //! use hyper::server;
@@ -60,9 +60,9 @@
 //!         // get the Authorization header the client sent
 //!         let hdr: &header::Authorization<HawkScheme> = req.headers.get().unwrap();
 //!
-//!         // build a request object based on what we know (note: this would include a body
-//!         // hash if one was given)
-//!         let request = RequestBuilder::new("GET", "localhost", PORT, "/resource").request();
+//!         // derive the request from the method, Host header and URI (note: this would need a
+//!         // body hash added, if one was given)
+//!         let request = ServerRequest::from_request(&req, 80).unwrap().request.request();
 //!
 //!         let key = Key::new(vec![1u8; 32], &SHA256);
 //!         if !request.validate_header(&hdr, &key, time::Duration::minutes(1)) {
@@ -102,6 +102,7 @@
 //! ```
 extern crate hyper;
 extern crate hawk;
+extern crate http;
 extern crate rustc_serialize;
 extern crate time;
 extern crate url;
@@ -109,5 +110,19 @@ extern crate url;
 mod serverauth;
 pub use serverauth::ServerAuthorization;
 
+mod wwwauthenticate;
+pub use wwwauthenticate::WwwAuthenticate;
+
 mod authscheme;
 pub use authscheme::HawkScheme;
+
+pub mod client;
+pub use client::{Client, VerifyingReader};
+
+mod server;
+pub use server::ServerRequest;
+
+mod handler;
+pub use handler::{HawkAuth, HawkHandler};
+pub use hawk::http::server::{Authenticated, KeyLookup, PeerAddr, ServerConfig,
+                             DEFAULT_PAYLOAD_LIMIT};
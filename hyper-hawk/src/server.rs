@@ -0,0 +1,270 @@
+use std::net::IpAddr;
+use hawk::{self, Bewit, RequestBuilder, TrustedProxies};
+use hyper::header::Headers;
+use hyper::method::Method;
+use hyper::server;
+use hyper::uri::RequestUri;
+
+/// The Hawk view of a request received by a Hyper server.
+#[derive(Debug, Clone)]
+pub struct ServerRequest {
+    /// The Hawk request, against which the client's `Authorization` header or bewit can be
+    /// validated.  Add a payload hash with `hash` before validating a request with a body.
+    pub request: RequestBuilder<'static>,
+    /// The bewit given in the request's query, if any.  It has been removed from the request's
+    /// path, as it is not part of the signed resource.
+    pub bewit: Option<Bewit<'static>>,
+}
+
+impl ServerRequest {
+    /// Derive the Hawk request from a request received by a Hyper server, as for `from_parts`.
+    pub fn from_request(req: &server::Request, default_port: u16) -> hawk::Result<ServerRequest> {
+        ServerRequest::from_parts(&req.method, &req.headers, &req.uri, default_port)
+    }
+
+    /// Derive the Hawk request from a request received by a Hyper server, as for `from_request`,
+    /// but taking the host and port from the `Forwarded` or `X-Forwarded-*` headers if the
+    /// request came from one of the given proxies.  See `hawk::TrustedProxies`.
+    pub fn from_proxied_request(req: &server::Request,
+                                default_port: u16,
+                                proxies: &TrustedProxies)
+                                -> hawk::Result<ServerRequest> {
+        ServerRequest::from_request(req, default_port)
+            .map(|sr| sr.forwarded(&req.headers, req.remote_addr.ip(), proxies))
+    }
+
+    /// Derive the Hawk request from the method, headers and URI of a request received by a Hyper
+    /// server.
+    ///
+    /// The host and port are taken from an absolute URI, as sent to a proxy, or else from the
+    /// `Host` header.  IPv6 hosts retain their brackets, as in `[::1]`, matching
+    /// `Url::host_str`.  If neither gives a port, the default port for the URI's scheme is used,
+    /// or `default_port` if the URI has no scheme, as is typical.  A server behind a TLS
+    /// terminator should pass 443 here.
+    ///
+    /// The resource is the URI's path and query, less any `bewit` parameter.
+    pub fn from_parts(method: &Method,
+                      headers: &Headers,
+                      uri: &RequestUri,
+                      default_port: u16)
+                      -> hawk::Result<ServerRequest> {
+        let (host, port, resource) = match *uri {
+            RequestUri::AbsoluteUri(ref url) => {
+                let host = url.host_str()
+                    .ok_or_else(|| hawk::Error::UrlMissingHost(url.to_string()))?;
+                let port = url.port_or_known_default().unwrap_or(default_port);
+                let mut resource = url.path().to_string();
+                if let Some(query) = url.query() {
+                    resource.push('?');
+                    resource.push_str(query);
+                }
+                (host.to_string(), port, resource)
+            }
+            RequestUri::AbsolutePath(ref path) => {
+                let (host, port) = host_header(headers, uri)?;
+                (host, port.unwrap_or(default_port), path.clone())
+            }
+            RequestUri::Star => {
+                let (host, port) = host_header(headers, uri)?;
+                (host, port.unwrap_or(default_port), "*".to_string())
+            }
+            RequestUri::Authority(ref authority) => {
+                let (host, port) = hawk::split_host(authority)?;
+                (host, port.unwrap_or(default_port), authority.clone())
+            }
+        };
+
+        let (bewit, resource) = match Bewit::from_resource(&resource)? {
+            Some((bewit, stripped)) => (Some(bewit), stripped),
+            None => (None, resource),
+        };
+
+        Ok(ServerRequest {
+            request: RequestBuilder::new(method.as_ref().to_string(), host, port, resource),
+            bewit: bewit,
+        })
+    }
+
+    /// Apply the forwarding headers in `headers`, if `peer` is a trusted proxy.
+    fn forwarded(self, headers: &Headers, peer: IpAddr, proxies: &TrustedProxies) -> Self {
+        let forwarded = proxies.forwarded(peer, |name| {
+            headers.get_raw(name)
+                .unwrap_or(&[])
+                .iter()
+                .filter_map(|v| ::std::str::from_utf8(v).ok())
+                .map(|v| v.to_string())
+                .collect()
+        });
+        match forwarded {
+            Some(forwarded) => {
+                ServerRequest {
+                    request: forwarded.apply(self.request),
+                    bewit: self.bewit,
+                }
+            }
+            None => self,
+        }
+    }
+}
+
+/// Get the host and optional port from the request's `Host` header.
+///
+/// The raw header is used, rather than Hyper's `Host`, which converts internationalized domain
+/// names to Unicode and so would not match the host the client signed.
+fn host_header(headers: &Headers, uri: &RequestUri) -> hawk::Result<(String, Option<u16>)> {
+    let value = match headers.get_raw("Host") {
+        Some(values) if values.len() == 1 => &values[0],
+        Some(_) => return Err(hawk::Error::InvalidHeaderValue),
+        None => return Err(hawk::Error::UrlMissingHost(uri.to_string())),
+    };
+    let value = ::std::str::from_utf8(value).map_err(|_| hawk::Error::InvalidHeaderValue)?;
+    hawk::split_host(value.trim())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use hawk::{Credentials, Error, Key, SHA256};
+    use hyper::header::Headers;
+    use hyper::method::Method;
+    use hyper::uri::RequestUri;
+    use std::str::FromStr;
+    use time::Duration;
+    use url::Url;
+
+    fn credentials() -> Credentials {
+        Credentials {
+            id: "me".to_string(),
+            key: Key::new(vec![1u8; 32], &SHA256),
+        }
+    }
+
+    fn headers(host: &str) -> Headers {
+        let mut headers = Headers::new();
+        headers.set_raw("Host", vec![host.as_bytes().to_vec()]);
+        headers
+    }
+
+    fn path(path: &str) -> RequestUri {
+        RequestUri::AbsolutePath(path.to_string())
+    }
+
+    /// Check that a header for the given request validates against the derived request.
+    fn check(req: &ServerRequest, method: &str, host: &str, port: u16, path: &str) {
+        let credentials = credentials();
+        let header = RequestBuilder::new(method, host, port, path)
+            .request()
+            .make_header(&credentials)
+            .unwrap();
+        let req = req.request.clone().request();
+        assert!(req.validate_header(&header, &credentials.key, Duration::minutes(1)));
+    }
+
+    #[test]
+    fn test_host_header() {
+        let req = ServerRequest::from_parts(&Method::Get,
+                                            &headers("example.com"),
+                                            &path("/foo?x=1"),
+                                            80)
+            .unwrap();
+        check(&req, "GET", "example.com", 80, "/foo?x=1");
+        assert!(req.bewit.is_none());
+    }
+
+    #[test]
+    fn test_host_header_with_port() {
+        let req = ServerRequest::from_parts(&Method::Get,
+                                            &headers("example.com:8443"),
+                                            &path("/"),
+                                            443)
+            .unwrap();
+        check(&req, "GET", "example.com", 8443, "/");
+    }
+
+    #[test]
+    fn test_ipv6_host() {
+        let req = ServerRequest::from_parts(&Method::Get, &headers("[::1]:9000"), &path("/"), 80)
+            .unwrap();
+        check(&req, "GET", "[::1]", 9000, "/");
+        let req = ServerRequest::from_parts(&Method::Get, &headers("[::1]"), &path("/"), 80)
+            .unwrap();
+        check(&req, "GET", "[::1]", 80, "/");
+    }
+
+    #[test]
+    fn test_invalid_host() {
+        for host in &["", "example.com:http", "[::1", "[::1]x", "user@example.com"] {
+            match ServerRequest::from_parts(&Method::Get, &headers(host), &path("/"), 80) {
+                Err(Error::InvalidHost(_)) => {}
+                r => panic!("unexpected result for {:?}: {:?}", host, r),
+            }
+        }
+    }
+
+    #[test]
+    fn test_missing_host() {
+        match ServerRequest::from_parts(&Method::Get, &Headers::new(), &path("/"), 80) {
+            Err(Error::UrlMissingHost(_)) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_absolute_uri() {
+        let uri = RequestUri::AbsoluteUri(Url::parse("https://example.com/foo?x=1").unwrap());
+        let req = ServerRequest::from_parts(&Method::Get, &headers("ignored.com"), &uri, 80)
+            .unwrap();
+        check(&req, "GET", "example.com", 443, "/foo?x=1");
+    }
+
+    #[test]
+    fn test_bewit() {
+        let credentials = credentials();
+        let bewit = RequestBuilder::new("GET", "example.com", 80, "/resource?x=1&y=2")
+            .request()
+            .make_bewit(&credentials, Duration::minutes(1))
+            .unwrap();
+        let uri = path(&format!("/resource?x=1&bewit={}&y=2", bewit.to_str()));
+        let req = ServerRequest::from_parts(&Method::Get, &headers("example.com"), &uri, 80)
+            .unwrap();
+        let found = req.bewit.unwrap();
+        assert_eq!(found.id(), "me");
+        assert!(req.request.request().validate_bewit(&found, &credentials.key));
+    }
+
+    #[test]
+    fn test_invalid_bewit() {
+        match ServerRequest::from_parts(&Method::Get,
+                                        &headers("example.com"),
+                                        &path("/resource?bewit=!!"),
+                                        80) {
+            Err(Error::BewitEncoding) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_forwarded() {
+        let proxies = TrustedProxies::new(vec!["10.0.0.1".parse().unwrap()]);
+        let mut headers = headers("10.0.0.5:8080");
+        headers.set_raw("X-Forwarded-Host", vec![b"api.example.com".to_vec()]);
+        headers.set_raw("X-Forwarded-Proto", vec![b"https".to_vec()]);
+        let req = ServerRequest::from_parts(&Method::Get, &headers, &path("/"), 8080).unwrap();
+
+        let trusted = req.clone().forwarded(&headers, "10.0.0.1".parse().unwrap(), &proxies);
+        check(&trusted, "GET", "api.example.com", 443, "/");
+
+        let untrusted = req.forwarded(&headers, "192.0.2.1".parse().unwrap(), &proxies);
+        check(&untrusted, "GET", "10.0.0.5", 8080, "/");
+    }
+
+    #[test]
+    fn test_method() {
+        let req = ServerRequest::from_parts(&Method::from_str("PATCH").unwrap(),
+                                            &headers("example.com"),
+                                            &path("/"),
+                                            80)
+            .unwrap();
+        check(&req, "PATCH", "example.com", 80, "/");
+    }
+}
@@ -0,0 +1,144 @@
+use std::fmt;
+use std::any::Any;
+use std::str::{self, FromStr};
+use hyper::Result;
+use hyper::header::{Scheme, Header, HeaderFormat};
+use std::ops::{Deref, DerefMut};
+
+/// 'WWW-Authenticate' header, carrying an authentication challenge.
+///
+/// For Hawk, a server sends this header with a 401 response, with a value such as
+/// `Hawk error="Bad mac"` (see `hawk::AuthFailure`).  Unlike `Authorization`, the scheme may
+/// appear without any attributes, as `Hawk`, when the request carried no authentication.
+#[derive(Clone, PartialEq, Debug)]
+pub struct WwwAuthenticate<S: Scheme>(pub S);
+
+impl<S: Scheme> Deref for WwwAuthenticate<S> {
+    type Target = S;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<S: Scheme> DerefMut for WwwAuthenticate<S> {
+    fn deref_mut(&mut self) -> &mut S {
+        &mut self.0
+    }
+}
+
+impl<S: Scheme + Any> Header for WwwAuthenticate<S>
+    where <S as FromStr>::Err: 'static
+{
+    fn header_name() -> &'static str {
+        "WWW-Authenticate"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> Result<WwwAuthenticate<S>> {
+        if raw.len() != 1 {
+            return Err(::hyper::Error::Header);
+        }
+        let header = try!(str::from_utf8(&raw[0][..]));
+        let attrs = match <S as Scheme>::scheme() {
+            Some(scheme) => {
+                if !header.starts_with(scheme) {
+                    return Err(::hyper::Error::Header);
+                }
+                let rest = &header[scheme.len()..];
+                // a bare scheme is a valid challenge, with no attributes
+                if rest.len() > 0 && !rest.starts_with(" ") {
+                    return Err(::hyper::Error::Header);
+                }
+                rest.trim_left()
+            }
+            None => header,
+        };
+        match attrs.parse::<S>() {
+            Ok(s) => Ok(WwwAuthenticate(s)),
+            Err(_) => Err(::hyper::Error::Header),
+        }
+    }
+}
+
+impl<S: Scheme + Any> HeaderFormat for WwwAuthenticate<S>
+    where <S as FromStr>::Err: 'static
+{
+    fn fmt_header(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // format the attributes first, so that a challenge without attributes is rendered as
+        // the bare scheme, without a trailing space
+        let attrs = format!("{}", SchemeAttributes(&self.0));
+        match <S as Scheme>::scheme() {
+            Some(scheme) if attrs.len() > 0 => write!(f, "{} {}", scheme, attrs),
+            Some(scheme) => write!(f, "{}", scheme),
+            None => write!(f, "{}", attrs),
+        }
+    }
+}
+
+struct SchemeAttributes<'a, S: Scheme + 'a>(&'a S);
+
+impl<'a, S: Scheme> fmt::Display for SchemeAttributes<'a, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt_scheme(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::WwwAuthenticate;
+    use hyper::header::{Header, Headers};
+    use hawk::{AuthFailure, Key, SHA256};
+    use authscheme::HawkScheme;
+
+    #[test]
+    fn parse_challenge() {
+        let raw = vec![b"Hawk error=\"Bad mac\"".to_vec()];
+        let hdr: WwwAuthenticate<HawkScheme> = Header::parse_header(&raw[..]).unwrap();
+        assert_eq!(hdr.error, Some("Bad mac".to_string()));
+        assert_eq!(AuthFailure::from_header(&hdr), Some(AuthFailure::BadMac));
+    }
+
+    #[test]
+    fn parse_bare_scheme() {
+        let raw = vec![b"Hawk".to_vec()];
+        let hdr: WwwAuthenticate<HawkScheme> = Header::parse_header(&raw[..]).unwrap();
+        assert_eq!(hdr.error, None);
+        assert_eq!(AuthFailure::from_header(&hdr),
+                   Some(AuthFailure::MissingAuthentication));
+    }
+
+    #[test]
+    fn parse_wrong_scheme() {
+        let raw = vec![b"Hawkish error=\"Bad mac\"".to_vec()];
+        let hdr: ::hyper::Result<WwwAuthenticate<HawkScheme>> = Header::parse_header(&raw[..]);
+        assert!(hdr.is_err());
+        let raw = vec![b"Basic realm=\"foo\"".to_vec()];
+        let hdr: ::hyper::Result<WwwAuthenticate<HawkScheme>> = Header::parse_header(&raw[..]);
+        assert!(hdr.is_err());
+    }
+
+    #[test]
+    fn format_challenges() {
+        let mut headers = Headers::new();
+        let failure = AuthFailure::MissingAuthentication;
+        headers.set(WwwAuthenticate(HawkScheme(failure.make_header(None).unwrap())));
+        assert_eq!(headers.to_string(), "WWW-Authenticate: Hawk\r\n");
+
+        let failure = AuthFailure::BadMac;
+        headers.set(WwwAuthenticate(HawkScheme(failure.make_header(None).unwrap())));
+        assert_eq!(headers.to_string(), "WWW-Authenticate: Hawk error=\"Bad mac\"\r\n");
+    }
+
+    #[test]
+    fn round_trip_stale_timestamp() {
+        let key = Key::new(vec![1u8; 32], &SHA256);
+        let failure = AuthFailure::StaleTimestamp;
+        let challenge = WwwAuthenticate(HawkScheme(failure.make_header(Some(&key)).unwrap()));
+        let mut headers = Headers::new();
+        headers.set(challenge.clone());
+
+        let raw = headers.get_raw("WWW-Authenticate").unwrap();
+        let hdr: WwwAuthenticate<HawkScheme> = Header::parse_header(raw).unwrap();
+        assert_eq!(hdr, challenge);
+    }
+}
@@ -0,0 +1,133 @@
+extern crate time;
+extern crate hawk;
+extern crate hyper;
+extern crate hyper_hawk;
+extern crate url;
+
+use hawk::{RequestBuilder, Credentials, Key, SHA256};
+use std::io::{Read, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use hyper_hawk::{Client, HawkScheme, WwwAuthenticate};
+use hyper::header;
+use hyper::method::Method;
+use hyper::server;
+use hyper::status::StatusCode;
+use url::Url;
+
+fn credentials() -> Credentials {
+    Credentials {
+        id: "test-client".to_string(),
+        key: Key::new(vec![1u8; 32], &SHA256),
+    }
+}
+
+struct TestHandler {
+    key: Key,
+    // key used to sign the timestamp in challenges
+    challenge_key: Key,
+    requests: Arc<AtomicUsize>,
+}
+
+impl server::Handler for TestHandler {
+    fn handle(&self, req: server::Request, mut res: server::Response) {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+
+        let hdr: &header::Authorization<HawkScheme> = req.headers.get().unwrap();
+        let request = RequestBuilder::new("GET", "localhost", 9999, "/resource").request();
+
+        let result = request.check_header(&hdr, &self.key, time::Duration::minutes(1));
+        let body: &[u8] = match result {
+            Ok(()) => b"OK",
+            Err(failure) => {
+                let challenge = failure.make_header(Some(&self.challenge_key)).unwrap();
+                res.headers_mut().set(WwwAuthenticate(HawkScheme(challenge)));
+                *res.status_mut() = StatusCode::from_u16(failure.status_code());
+                b"Unauthorized"
+            }
+        };
+
+        res.headers_mut()
+            .set(header::ContentLength(body.len() as u64));
+        let mut res = res.start().unwrap();
+        res.write_all(body).unwrap();
+    }
+}
+
+/// Run a server signing its challenges with the given key, and send a single GET request to it
+/// using `client`, after setting its offset to `initial_offset`.  Returns the client's response
+/// status and body, the client's final offset, and the number of requests the server saw.
+fn run_client_server(challenge_key: Key,
+                     client: Client,
+                     initial_offset: time::Duration)
+                     -> (StatusCode, String, time::Duration, usize) {
+    let requests = Arc::new(AtomicUsize::new(0));
+    let handler = TestHandler {
+        key: credentials().key,
+        challenge_key: challenge_key,
+        requests: requests.clone(),
+    };
+    let mut server = server::Server::http(("127.0.0.1", 0)).unwrap();
+    let local_address = server.local_addr().unwrap();
+    let mut listening = server.handle_threads(handler, 1).unwrap();
+
+    let url = Url::parse(&format!("http://localhost:{}/resource", local_address.port())).unwrap();
+    // for purposes of the test, we pretend we're using port 9999
+    let request = RequestBuilder::from_url("GET", &url).unwrap().port(9999).request();
+
+    client.set_offset(initial_offset);
+    let (mut res, _) = client
+        .send(Method::Get, &url, &request, header::Headers::new(), None)
+        .unwrap();
+    let mut body = String::new();
+    res.read_to_string(&mut body).unwrap();
+    let status = res.status;
+    drop(res);
+    let offset = client.offset();
+    drop(client);
+
+    listening.close().unwrap();
+
+    (status, body, offset, requests.load(Ordering::SeqCst))
+}
+
+#[test]
+fn no_offset() {
+    let (status, body, offset, requests) =
+        run_client_server(credentials().key, Client::new(credentials()), time::Duration::zero());
+    assert_eq!(status, StatusCode::Ok);
+    assert_eq!(body, "OK");
+    assert_eq!(offset, time::Duration::zero());
+    assert_eq!(requests, 1);
+}
+
+#[test]
+fn stale_timestamp_retried() {
+    let (status, body, offset, requests) =
+        run_client_server(credentials().key,
+                          Client::new(credentials()),
+                          time::Duration::hours(-1));
+    assert_eq!(status, StatusCode::Ok);
+    assert_eq!(body, "OK");
+    // the offset has been corrected to (nearly) zero, since client and server share a clock
+    assert!(offset.num_seconds().abs() <= 2);
+    assert_eq!(requests, 2);
+}
+
+#[test]
+fn stale_timestamp_bad_tsm() {
+    // a challenge signed with a different key is not trusted, so the request is not retried
+    let (status, _, offset, requests) =
+        run_client_server(Key::new(vec![2u8; 32], &SHA256),
+                          Client::new(credentials()),
+                          time::Duration::hours(-1));
+    assert_eq!(status, StatusCode::Unauthorized);
+    assert_eq!(offset, time::Duration::hours(-1));
+    assert_eq!(requests, 1);
+}
+
+#[test]
+fn client_is_sync() {
+    fn is_sync<T: Sync + Send>() {}
+    is_sync::<Client>();
+}
@@ -0,0 +1,323 @@
+extern crate time;
+extern crate hawk;
+extern crate hyper;
+extern crate hyper_hawk;
+extern crate url;
+
+use hawk::{AuthFailure, RequestBuilder, Credentials, Header, Key, SHA256, PayloadHasher};
+use std::io::{Read, Write};
+use std::sync::Arc;
+use hyper_hawk::{Client as HawkClient, HawkAuth, HawkHandler, HawkScheme, ServerAuthorization,
+                 ServerConfig, ServerRequest, VerifyingReader, WwwAuthenticate};
+use hyper_hawk::client::Error;
+use hyper::Client;
+use hyper::client::Response;
+use hyper::method::Method;
+use hyper::header;
+use hyper::server;
+use url::Url;
+
+fn credentials() -> Credentials {
+    Credentials {
+        id: "test-client".to_string(),
+        key: Key::new(vec![1u8; 32], &SHA256),
+    }
+}
+
+/// Answer each request with the authenticated id and the request body.
+struct EchoHandler;
+
+impl server::Handler for EchoHandler {
+    fn handle(&self, mut req: server::Request, mut res: server::Response) {
+        let id = req.headers.get::<HawkAuth>().map(|auth| auth.0.id.clone());
+        let mut body = String::new();
+        req.read_to_string(&mut body).unwrap();
+
+        res.headers_mut().set_raw("Content-Type", vec![b"text/plain".to_vec()]);
+        let mut res = res.start().unwrap();
+        write!(res, "{:?}: {}", id, body).unwrap();
+    }
+}
+
+fn serve(hash_responses: bool) -> (server::Listening, u16) {
+    listen(hawk_handler(EchoHandler).hash_responses(hash_responses))
+}
+
+/// Wrap the given handler in a `HawkHandler` knowing the test client's key.
+fn hawk_handler<H: server::Handler>(inner: H) -> HawkHandler<H> {
+    let key = Arc::new(credentials().key);
+    HawkHandler::new(inner,
+                     move |id: &str| if id == "test-client" {
+                         Some(key.clone())
+                     } else {
+                         None
+                     },
+                     ServerConfig::new(80))
+}
+
+fn listen<H: server::Handler + 'static>(handler: H) -> (server::Listening, u16) {
+    let mut server = server::Server::http(("127.0.0.1", 0)).unwrap();
+    let port = server.local_addr().unwrap().port();
+    (server.handle_threads(handler, 1).unwrap(), port)
+}
+
+/// Send a request signed for the given body, actually sending `sent_body`.
+fn send(port: u16,
+        method: &str,
+        signed_body: Option<&str>,
+        sent_body: &str,
+        headers: header::Headers)
+        -> (Response, hawk::Request<'static>, Header) {
+    let url = Url::parse(&format!("http://127.0.0.1:{}/resource", port)).unwrap();
+    let hash = signed_body.map(|b| PayloadHasher::hash("text/plain", &SHA256, b));
+    let request = RequestBuilder::from_url(method, &url)
+        .unwrap()
+        .hash(hash.as_ref().map(|h| &h[..]))
+        .request()
+        .into_owned();
+    let header = request.make_header(&credentials()).unwrap();
+
+    let mut headers = headers;
+    headers.set(header::Authorization(HawkScheme(header.clone())));
+    headers.set_raw("Content-Type", vec![b"text/plain".to_vec()]);
+    let res = Client::new()
+        .request(method.parse().unwrap(), url.as_str())
+        .headers(headers)
+        .body(sent_body)
+        .send()
+        .unwrap();
+    (res, request, header)
+}
+
+#[test]
+fn authenticated() {
+    let (mut listening, port) = serve(false);
+    let (mut res, request, header) = send(port, "POST", None, "foo=bar", header::Headers::new());
+    let mut body = String::new();
+    res.read_to_string(&mut body).unwrap();
+    listening.close().unwrap();
+
+    assert_eq!(res.status, hyper::Ok);
+    assert_eq!(body, "Some(\"test-client\"): foo=bar");
+    let server_hdr: &ServerAuthorization<HawkScheme> = res.headers.get().unwrap();
+    assert_eq!(server_hdr.hash, None);
+    let response = request.make_response_builder(&header).response();
+    assert!(response.validate_header(server_hdr, &credentials().key));
+}
+
+#[test]
+fn payload_hashes() {
+    let (mut listening, port) = serve(true);
+    let (mut res, request, header) =
+        send(port, "POST", Some("foo=bar"), "foo=bar", header::Headers::new());
+    let mut body = String::new();
+    res.read_to_string(&mut body).unwrap();
+    listening.close().unwrap();
+
+    assert_eq!(res.status, hyper::Ok);
+    assert_eq!(body, "Some(\"test-client\"): foo=bar");
+    let server_hdr: &ServerAuthorization<HawkScheme> = res.headers.get().unwrap();
+    let response = request.make_response_builder(&header).response();
+    assert!(response.validate_header_with_payload(server_hdr,
+                                                  &credentials().key,
+                                                  "text/plain",
+                                                  body.as_bytes()));
+}
+
+#[test]
+fn wrong_payload() {
+    let (mut listening, port) = serve(false);
+    let (res, _, _) = send(port, "POST", Some("foo=bar"), "foo=baz", header::Headers::new());
+    listening.close().unwrap();
+
+    assert_eq!(res.status, hyper::status::StatusCode::Unauthorized);
+    let challenge: &WwwAuthenticate<HawkScheme> = res.headers.get().unwrap();
+    assert_eq!(challenge.error, Some("Bad payload hash".to_string()));
+}
+
+#[test]
+fn payload_too_large() {
+    let (mut listening, port) = listen(hawk_handler(EchoHandler).payload_limit(4));
+    let (res, _, _) = send(port, "POST", Some("foo=bar"), "foo=bar", header::Headers::new());
+    listening.close().unwrap();
+
+    assert_eq!(res.status, hyper::status::StatusCode::PayloadTooLarge);
+}
+
+/// Answer with a chunked body larger than the buffer Hyper writes responses through.
+struct LargeHandler;
+
+impl server::Handler for LargeHandler {
+    fn handle(&self, _req: server::Request, mut res: server::Response) {
+        res.headers_mut().set_raw("Content-Type", vec![b"text/plain".to_vec()]);
+        let mut res = res.start().unwrap();
+        for chunk in 0..4u8 {
+            res.write_all(&[b'a' + chunk; 10000]).unwrap();
+        }
+        res.end().unwrap();
+    }
+}
+
+#[test]
+fn large_chunked_response() {
+    let (mut listening, port) = listen(hawk_handler(LargeHandler).hash_responses(true));
+    let (mut res, request, header) = send(port, "GET", None, "", header::Headers::new());
+    let mut body = vec![];
+    res.read_to_end(&mut body).unwrap();
+    listening.close().unwrap();
+
+    assert_eq!(res.status, hyper::Ok);
+    assert_eq!(body.len(), 40000);
+    assert!(body[..10000].iter().all(|&b| b == b'a'));
+    assert!(body[30000..].iter().all(|&b| b == b'd'));
+    let server_hdr: &ServerAuthorization<HawkScheme> = res.headers.get().unwrap();
+    let response = request.make_response_builder(&header).response();
+    assert!(response.validate_header_with_payload(server_hdr,
+                                                  &credentials().key,
+                                                  "text/plain",
+                                                  &body));
+}
+
+#[test]
+fn unauthenticated() {
+    let (mut listening, port) = serve(false);
+    let res = Client::new()
+        .get(&format!("http://127.0.0.1:{}/resource", port))
+        .send()
+        .unwrap();
+    listening.close().unwrap();
+
+    assert_eq!(res.status, hyper::status::StatusCode::Unauthorized);
+    assert!(res.headers.get_raw("WWW-Authenticate").is_some());
+}
+
+#[test]
+fn spoofed_client() {
+    let (mut listening, port) = serve(false);
+    let mut headers = header::Headers::new();
+    headers.set_raw("X-Hawk-Auth", vec![b"someone-else".to_vec()]);
+    let (mut res, _, _) = send(port, "GET", None, "", headers);
+    let mut body = String::new();
+    res.read_to_string(&mut body).unwrap();
+    listening.close().unwrap();
+
+    assert_eq!(body, "Some(\"test-client\"): ");
+}
+
+#[test]
+fn client_request() {
+    let (mut listening, port) = serve(true);
+    let url = Url::parse(&format!("http://127.0.0.1:{}/resource", port)).unwrap();
+    let res = HawkClient::new(credentials())
+        .request(Method::Post, &url, Some(("text/plain", b"foo=bar")))
+        .unwrap();
+    listening.close().unwrap();
+
+    assert_eq!(res.status, hyper::Ok);
+    assert_eq!(res.body, b"Some(\"test-client\"): foo=bar");
+}
+
+#[test]
+fn client_request_rejected() {
+    let (mut listening, port) = serve(false);
+    let url = Url::parse(&format!("http://127.0.0.1:{}/resource", port)).unwrap();
+    let credentials = Credentials {
+        id: "test-client".to_string(),
+        key: Key::new(vec![2u8; 32], &SHA256),
+    };
+    let res = HawkClient::new(credentials).request(Method::Get, &url, None);
+    listening.close().unwrap();
+
+    match res {
+        Err(Error::Rejected(Some(AuthFailure::BadMac))) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+}
+
+#[test]
+fn client_request_unsigned_response() {
+    let mut server = server::Server::http(("127.0.0.1", 0)).unwrap();
+    let port = server.local_addr().unwrap().port();
+    let mut listening = server.handle_threads(EchoHandler, 1).unwrap();
+    let url = Url::parse(&format!("http://127.0.0.1:{}/resource", port)).unwrap();
+    let res = HawkClient::new(credentials()).request(Method::Get, &url, None);
+    listening.close().unwrap();
+
+    match res {
+        Err(Error::MissingServerAuthorization(hyper::Ok)) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+}
+
+/// Sign a hash of one body, but send another, as an attacker modifying the response would.
+struct TamperingHandler;
+
+impl server::Handler for TamperingHandler {
+    fn handle(&self, req: server::Request, mut res: server::Response) {
+        let header = req.headers
+            .get::<header::Authorization<HawkScheme>>()
+            .unwrap()
+            .0
+            .clone();
+        let request = ServerRequest::from_request(&req, 80).unwrap().request.request();
+        let server_header = request.make_response_builder(&header)
+            .response()
+            .make_header_with_payload(&credentials().key, "text/plain", "original")
+            .unwrap();
+
+        res.headers_mut().set(ServerAuthorization(HawkScheme(server_header)));
+        res.headers_mut().set_raw("Content-Type", vec![b"text/plain".to_vec()]);
+        let mut res = res.start().unwrap();
+        res.write_all(b"tampered").unwrap();
+    }
+}
+
+/// Send a GET request with `Client::send`, and wrap the response in a `VerifyingReader`.
+fn send_verifying(port: u16) -> Result<VerifyingReader, Error> {
+    let url = Url::parse(&format!("http://127.0.0.1:{}/resource", port)).unwrap();
+    let request = RequestBuilder::from_url("GET", &url).unwrap().request();
+    let client = HawkClient::new(credentials());
+    let (res, header) = client.send(Method::Get, &url, &request, header::Headers::new(), None)
+        .unwrap();
+    VerifyingReader::new(res, &request, &header, &credentials().key)
+}
+
+#[test]
+fn verifying_reader() {
+    let (mut listening, port) = serve(true);
+    let mut reader = send_verifying(port).unwrap();
+    let mut body = String::new();
+    reader.read_to_string(&mut body).unwrap();
+    listening.close().unwrap();
+
+    assert_eq!(reader.status(), hyper::Ok);
+    assert_eq!(body, "Some(\"test-client\"): ");
+}
+
+#[test]
+fn verifying_reader_tampered() {
+    let mut server = server::Server::http(("127.0.0.1", 0)).unwrap();
+    let port = server.local_addr().unwrap().port();
+    let mut listening = server.handle_threads(TamperingHandler, 1).unwrap();
+    let mut reader = send_verifying(port).unwrap();
+    let mut body = vec![];
+    let err = reader.read_to_end(&mut body).unwrap_err();
+    listening.close().unwrap();
+
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    // the error persists, rather than the body appearing complete on the next read
+    assert!(reader.read(&mut [0u8; 16]).is_err());
+}
+
+#[test]
+fn verifying_reader_unhashed() {
+    let (mut listening, port) = serve(false);
+    let res = send_verifying(port);
+    listening.close().unwrap();
+
+    match res {
+        Err(Error::InvalidServerAuthorization) => {}
+        Err(e) => panic!("unexpected error {:?}", e),
+        Ok(_) => panic!("unhashed response accepted"),
+    }
+}
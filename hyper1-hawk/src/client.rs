@@ -0,0 +1,231 @@
+use std::error;
+use std::fmt;
+
+use bytes::Bytes;
+use hawk::{Credentials, PayloadHasher};
+use http::header::{HeaderValue, CONTENT_TYPE, TE, WWW_AUTHENTICATE};
+use http::{HeaderMap, Request, Response, StatusCode};
+use http_body_util::{BodyExt, Full};
+use hyper_util::client::legacy::{self, connect::Connect, connect::HttpConnector};
+use hyper_util::rt::TokioExecutor;
+
+/// An error from a Hawk-authenticated client request.
+#[derive(Debug)]
+pub enum Error {
+    /// An error generating or parsing a Hawk header
+    Hawk(hawk::Error),
+    /// An error making the HTTP request
+    Http(legacy::Error),
+    /// An error reading the response body
+    Body(hyper::Error),
+    /// The response had no `Server-Authorization` header or trailer.  This includes 401
+    /// responses rejecting the request, unless `accept_challenges` is set.
+    MissingServerAuthorization(StatusCode),
+    /// The response's `Server-Authorization` header did not validate, so the response may not
+    /// have come from the server, or may have been modified
+    InvalidServerAuthorization,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Hawk(ref e) => write!(f, "Hawk error: {}", e),
+            Error::Http(ref e) => write!(f, "HTTP error: {}", e),
+            Error::Body(ref e) => write!(f, "Error reading response body: {}", e),
+            Error::MissingServerAuthorization(status) => {
+                write!(
+                    f,
+                    "Response with status {} has no Server-Authorization header",
+                    status
+                )
+            }
+            Error::InvalidServerAuthorization => write!(f, "Invalid Server-Authorization header"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Hawk(ref e) => Some(e),
+            Error::Http(ref e) => Some(e),
+            Error::Body(ref e) => Some(e),
+            Error::MissingServerAuthorization(_) | Error::InvalidServerAuthorization => None,
+        }
+    }
+}
+
+impl From<hawk::Error> for Error {
+    fn from(e: hawk::Error) -> Self {
+        Error::Hawk(e)
+    }
+}
+
+impl From<legacy::Error> for Error {
+    fn from(e: legacy::Error) -> Self {
+        Error::Http(e)
+    }
+}
+
+impl From<hyper::Error> for Error {
+    fn from(e: hyper::Error) -> Self {
+        Error::Body(e)
+    }
+}
+
+/// A hyper client that signs each request with Hawk credentials and verifies the
+/// `Server-Authorization` header of each response.
+///
+/// Response bodies are buffered, so that a payload hash in `Server-Authorization` can be
+/// verified before the response is returned.  Responses without a `Server-Authorization`
+/// header give `Error::MissingServerAuthorization`, except for 401 challenges when
+/// `accept_challenges` is set.
+///
+/// With `accept_trailers`, the client also accepts `Server-Authorization` as a trailer, which a
+/// server can send after streaming a body it could not hash in advance.
+pub struct Client<C> {
+    client: legacy::Client<C, Full<Bytes>>,
+    credentials: Credentials,
+    hash_payloads: bool,
+    accept_trailers: bool,
+    accept_challenges: bool,
+}
+
+impl Client<HttpConnector> {
+    /// Create a new client with the given credentials, using a default hyper client.
+    pub fn new(credentials: Credentials) -> Self {
+        Client::with_client(
+            legacy::Client::builder(TokioExecutor::new()).build_http(),
+            credentials,
+        )
+    }
+}
+
+impl<C> Client<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    /// Create a new client with the given credentials, sending requests with the given hyper
+    /// client.
+    pub fn with_client(client: legacy::Client<C, Full<Bytes>>, credentials: Credentials) -> Self {
+        Client {
+            client,
+            credentials,
+            hash_payloads: false,
+            accept_trailers: false,
+            accept_challenges: false,
+        }
+    }
+
+    /// Set whether to include a hash of the request body, with its `Content-Type`, in the
+    /// `Authorization` header.
+    pub fn hash_payloads(mut self, hash_payloads: bool) -> Self {
+        self.hash_payloads = hash_payloads;
+        self
+    }
+
+    /// Set whether to ask for `Server-Authorization` as a trailer, by sending `TE: trailers`.
+    /// Servers that do not support this send it as a header, as usual, and either is verified.
+    pub fn accept_trailers(mut self, accept_trailers: bool) -> Self {
+        self.accept_trailers = accept_trailers;
+        self
+    }
+
+    /// Set whether to return 401 responses with a Hawk `WWW-Authenticate` challenge, which have
+    /// no `Server-Authorization` header, rather than failing with
+    /// `Error::MissingServerAuthorization`.  The challenge is not authenticated, so the caller
+    /// should treat it only as a hint, such as to retry with a corrected clock.
+    pub fn accept_challenges(mut self, accept_challenges: bool) -> Self {
+        self.accept_challenges = accept_challenges;
+        self
+    }
+
+    /// Get the credentials used by this client.
+    pub fn credentials(&self) -> &Credentials {
+        &self.credentials
+    }
+
+    /// Send the given request, which must have an absolute URI, with a Hawk `Authorization`
+    /// header.
+    ///
+    /// A `Server-Authorization` trailer is verified like the header, and then added to the
+    /// returned response's headers.
+    pub async fn request(&self, req: Request<Bytes>) -> Result<Response<Bytes>, Error> {
+        let (parts, body) = req.into_parts();
+
+        let hash = if self.hash_payloads {
+            Some(PayloadHasher::hash(
+                hawk::content_type(header_str(&parts.headers, CONTENT_TYPE)),
+                self.credentials.key.algorithm(),
+                &body[..],
+            ))
+        } else {
+            None
+        };
+        // the port is always known, as the URI is absolute
+        let request = hawk::http::request_builder_from_parts(&parts, 80)?
+            .hash(hash)
+            .request()
+            .into_owned();
+        let header = request.make_header(&self.credentials)?;
+
+        let mut req = Request::from_parts(parts, Full::new(body));
+        hawk::http::set_authorization(&mut req, &header)?;
+        if self.accept_trailers {
+            req.headers_mut()
+                .insert(TE, HeaderValue::from_static("trailers"));
+        }
+
+        let res = self.client.request(req).await?;
+        let (mut parts, body) = res.into_parts();
+        let collected = body.collect().await?;
+        if !parts.headers.contains_key(hawk::http::SERVER_AUTHORIZATION) {
+            if let Some(value) = collected
+                .trailers()
+                .and_then(|trailers| trailers.get(hawk::http::SERVER_AUTHORIZATION))
+            {
+                parts
+                    .headers
+                    .insert(hawk::http::SERVER_AUTHORIZATION, value.clone());
+            }
+        }
+        let body = collected.to_bytes();
+
+        let server_header = match hawk::http::server_authorization(&parts.headers)? {
+            Some(server_header) => server_header,
+            None if self.accept_challenges && is_challenge(&parts) => {
+                return Ok(Response::from_parts(parts, body))
+            }
+            None => return Err(Error::MissingServerAuthorization(parts.status)),
+        };
+        let response = request.make_response_builder(&header).response();
+        let valid = if server_header.hash.is_some() {
+            response.validate_header_with_payload(
+                &server_header,
+                &self.credentials.key,
+                header_str(&parts.headers, CONTENT_TYPE),
+                &body[..],
+            )
+        } else {
+            response.validate_header(&server_header, &self.credentials.key)
+        };
+        if !valid {
+            return Err(Error::InvalidServerAuthorization);
+        }
+
+        Ok(Response::from_parts(parts, body))
+    }
+}
+
+/// Determine whether a response is a 401 with a Hawk challenge.
+fn is_challenge(parts: &http::response::Parts) -> bool {
+    parts.status == StatusCode::UNAUTHORIZED
+        && header_str(&parts.headers, WWW_AUTHENTICATE).starts_with("Hawk")
+}
+
+fn header_str(headers: &HeaderMap, name: http::header::HeaderName) -> &str {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+}
@@ -0,0 +1,114 @@
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use hawk::http::server::{KeyLookup, ServerConfig, SignedBody, DEFAULT_PAYLOAD_LIMIT};
+use http::{Request, Response};
+use hyper::body::Body;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::server::{handle, HawkBody, HawkService, Settings};
+
+/// A tower layer that wraps services in `HawkService`, so that any tower-based server (such as
+/// axum or tonic) can authenticate requests with Hawk.
+///
+/// The wrapped service receives requests with an `Authenticated` extension, and requests that
+/// fail authentication are answered with a 401 response carrying a `WWW-Authenticate`
+/// challenge.
+///
+/// ```
+/// use std::sync::Arc;
+/// use hawk::{Key, SHA256};
+/// use hyper1_hawk::{HawkLayer, ServerConfig};
+///
+/// let key = Arc::new(Key::new(vec![1u8; 32], &SHA256));
+/// let layer = HawkLayer::new(
+///     move |id: &str| if id == "me" { Some(key.clone()) } else { None },
+///     ServerConfig::new(443),
+/// )
+/// .hash_responses(true);
+/// # let _ = layer;
+/// ```
+#[derive(Clone)]
+pub struct HawkLayer {
+    settings: Settings,
+}
+
+impl HawkLayer {
+    /// Create a new layer, looking up client keys with `keys`.
+    pub fn new<K>(keys: K, config: ServerConfig) -> Self
+    where
+        K: KeyLookup + 'static,
+    {
+        HawkLayer {
+            settings: Settings {
+                keys: Arc::new(keys),
+                config,
+                hash_responses: false,
+                trailer_responses: false,
+                payload_limit: DEFAULT_PAYLOAD_LIMIT,
+            },
+        }
+    }
+
+    /// Set whether to include a hash of the response body in `Server-Authorization`.  This
+    /// requires buffering each response body.
+    pub fn hash_responses(mut self, hash_responses: bool) -> Self {
+        self.settings.hash_responses = hash_responses;
+        self
+    }
+
+    /// Set whether to send `Server-Authorization` as a trailer to clients that accept trailers,
+    /// as for `HawkService::trailer_responses`.
+    pub fn trailer_responses(mut self, trailer_responses: bool) -> Self {
+        self.settings.trailer_responses = trailer_responses;
+        self
+    }
+
+    /// Set the limit, in bytes, on the size of request bodies buffered to verify a payload hash,
+    /// as for `HawkService::payload_limit`.
+    pub fn payload_limit(mut self, payload_limit: usize) -> Self {
+        self.settings.payload_limit = payload_limit;
+        self
+    }
+}
+
+impl<S> Layer<S> for HawkLayer {
+    type Service = HawkService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HawkService::with_settings(inner, self.settings.clone())
+    }
+}
+
+impl<S, B, ResB> Service<Request<B>> for HawkService<S>
+where
+    S: Service<Request<HawkBody<B>>, Response = Response<ResB>> + Clone + Send + 'static,
+    S::Future: Send,
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    ResB: Body + Send + 'static,
+    ResB::Data: Send,
+{
+    type Response = Response<HawkBody<SignedBody<ResB>>>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        // tower requires calling the service that was polled ready; the clone left in its
+        // place is polled before the next call
+        let clone = self.get_ref().clone();
+        let mut inner = mem::replace(self.get_mut(), clone);
+        Box::pin(handle(self.settings().clone(), req, move |req| {
+            inner.call(req)
+        }))
+    }
+}
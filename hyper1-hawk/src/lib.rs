@@ -0,0 +1,61 @@
+//! Hawk authentication for Hyper 1.x.
+//!
+//! `HawkService` wraps a hyper service, authenticating each request before passing it on and
+//! signing each response with a `Server-Authorization` header.  `Client` signs requests with
+//! Hawk credentials and verifies the `Server-Authorization` header of each response.
+//!
+//! Both are built on `hawk::http`, so other integrations can share the same logic.  With the
+//! `tower` feature, `HawkLayer` adds Hawk authentication to any tower-based server.
+//!
+//! Behind a reverse proxy, use `ServerConfig::trusted_proxies` to take the host and port from
+//! the `Forwarded` or `X-Forwarded-*` headers the proxy adds.  Hyper does not record the peer's
+//! address on requests, so add it as a `PeerAddr` extension before `HawkService` sees them,
+//! such as in a `service_fn` wrapping it for each accepted connection.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use hawk::{Credentials, Key, SHA256};
+//! use hyper::body::Incoming;
+//! use hyper1_hawk::{Authenticated, HawkBody, HawkService, ServerConfig};
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let key = Arc::new(Key::new(vec![1u8; 32], &SHA256));
+//! let service = HawkService::new(
+//!     hyper::service::service_fn(|req: http::Request<HawkBody<Incoming>>| async move {
+//!         let client = req.extensions().get::<Authenticated>().unwrap();
+//!         let body = format!("Hello, {}", client.id);
+//!         Ok::<_, std::convert::Infallible>(http::Response::new(http_body_util::Full::new(
+//!             bytes::Bytes::from(body),
+//!         )))
+//!     }),
+//!     move |id: &str| if id == "me" { Some(key.clone()) } else { None },
+//!     ServerConfig::new(80),
+//! );
+//! # let _ = service;
+//!
+//! let client = hyper1_hawk::Client::new(Credentials {
+//!     id: "me".to_string(),
+//!     key: Key::new(vec![1u8; 32], &SHA256),
+//! });
+//! let res = client
+//!     .request(http::Request::get("http://localhost:8000/").body(bytes::Bytes::new())?)
+//!     .await?;
+//! # let _ = res;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod client;
+#[cfg(feature = "tower")]
+pub mod layer;
+pub mod server;
+
+pub use client::{Client, Error};
+pub use hawk::http::server::{
+    Authenticated, KeyLookup, PeerAddr, ServerConfig, SignedBody, DEFAULT_PAYLOAD_LIMIT,
+};
+#[cfg(feature = "tower")]
+pub use layer::HawkLayer;
+pub use server::{HawkBody, HawkService};
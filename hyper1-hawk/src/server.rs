@@ -0,0 +1,275 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use hawk::http::server::{
+    authenticate, needs_body, KeyLookup, ServerConfig, SignedBody, Verified, DEFAULT_PAYLOAD_LIMIT,
+};
+use hawk::http::SERVER_AUTHORIZATION;
+use http::header::{HeaderValue, CONTENT_LENGTH, CONTENT_TYPE, TE, TRAILER};
+use http::{HeaderMap, Request, Response, StatusCode};
+use http_body_util::{BodyExt, Either, Full, LengthLimitError, Limited};
+use hyper::body::Body;
+use hyper::service::Service;
+
+/// The body of a request passed to the inner service, or of a response from `HawkService`.
+///
+/// Bodies that had to be buffered, to verify or calculate a payload hash, are `Left`; all
+/// others are passed through unchanged as `Right`.
+pub type HawkBody<B> = Either<Full<Bytes>, B>;
+
+/// A hyper service that authenticates each request with Hawk before passing it to an inner
+/// service.  With the `tower` feature, this is also a tower service, usually created with
+/// `HawkLayer`.
+///
+/// Requests may be authenticated with an `Authorization` header or, for GET and HEAD, a
+/// `bewit` query parameter.  Requests that fail authentication are answered with a 401 or 400
+/// response carrying a `WWW-Authenticate` challenge, without calling the inner service.
+///
+/// When the `Authorization` header includes a payload hash, the request body is buffered and
+/// verified; bodies larger than `payload_limit` are answered with a 413 response.  The
+/// authenticated client is added to the request's extensions as a
+/// `hawk::http::server::Authenticated`.  Responses to header-authenticated requests are signed
+/// with a `Server-Authorization` header, which includes a hash of the response body if
+/// `hash_responses` is enabled.
+///
+/// With `trailer_responses`, clients that send `TE: trailers` instead get a response body that
+/// is hashed as it is streamed, with `Server-Authorization` sent as a trailer after it.
+pub struct HawkService<S> {
+    inner: S,
+    settings: Settings,
+}
+
+/// The configuration shared by clones of a `HawkService`.
+#[derive(Clone)]
+pub(crate) struct Settings {
+    pub(crate) keys: Arc<dyn KeyLookup>,
+    pub(crate) config: ServerConfig,
+    pub(crate) hash_responses: bool,
+    pub(crate) trailer_responses: bool,
+    pub(crate) payload_limit: usize,
+}
+
+impl<S> HawkService<S> {
+    /// Wrap the given service, looking up client keys with `keys`.
+    pub fn new<K>(inner: S, keys: K, config: ServerConfig) -> Self
+    where
+        K: KeyLookup + 'static,
+    {
+        HawkService {
+            inner,
+            settings: Settings {
+                keys: Arc::new(keys),
+                config,
+                hash_responses: false,
+                trailer_responses: false,
+                payload_limit: DEFAULT_PAYLOAD_LIMIT,
+            },
+        }
+    }
+
+    #[cfg(feature = "tower")]
+    pub(crate) fn with_settings(inner: S, settings: Settings) -> Self {
+        HawkService { inner, settings }
+    }
+
+    #[cfg(feature = "tower")]
+    pub(crate) fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Set whether to include a hash of the response body in `Server-Authorization`.  This
+    /// requires buffering each response body.
+    pub fn hash_responses(mut self, hash_responses: bool) -> Self {
+        self.settings.hash_responses = hash_responses;
+        self
+    }
+
+    /// Set whether to send `Server-Authorization`, with a hash of the response body, as a
+    /// trailer to clients that accept trailers.  The body is then streamed rather than
+    /// buffered; responses to other clients are signed as usual.
+    pub fn trailer_responses(mut self, trailer_responses: bool) -> Self {
+        self.settings.trailer_responses = trailer_responses;
+        self
+    }
+
+    /// Set the limit, in bytes, on the size of request bodies buffered to verify a payload hash.
+    /// This defaults to `DEFAULT_PAYLOAD_LIMIT`.
+    pub fn payload_limit(mut self, payload_limit: usize) -> Self {
+        self.settings.payload_limit = payload_limit;
+        self
+    }
+
+    /// Get a reference to the inner service.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner service.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Consume this service, returning the inner service.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: Clone> Clone for HawkService<S> {
+    fn clone(&self) -> Self {
+        HawkService {
+            inner: self.inner.clone(),
+            settings: self.settings.clone(),
+        }
+    }
+}
+
+impl<S, B, ResB> Service<Request<B>> for HawkService<S>
+where
+    S: Service<Request<HawkBody<B>>, Response = Response<ResB>> + Clone + Send + 'static,
+    S::Future: Send,
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    ResB: Body + Send + 'static,
+    ResB::Data: Send,
+{
+    type Response = Response<HawkBody<SignedBody<ResB>>>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn call(&self, req: Request<B>) -> Self::Future {
+        let inner = self.inner.clone();
+        Box::pin(handle(self.settings.clone(), req, move |req| {
+            inner.call(req)
+        }))
+    }
+}
+
+/// Authenticate the given request, pass it to the inner service with `call`, and sign the
+/// response.  This is shared by the hyper and tower implementations of `HawkService`.
+pub(crate) async fn handle<B, ResB, F, Fut, E>(
+    settings: Settings,
+    req: Request<B>,
+    call: F,
+) -> Result<Response<HawkBody<SignedBody<ResB>>>, E>
+where
+    B: Body,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    ResB: Body,
+    F: FnOnce(Request<HawkBody<B>>) -> Fut,
+    Fut: Future<Output = Result<Response<ResB>, E>>,
+{
+    let (parts, body) = req.into_parts();
+    let trailers = settings.trailer_responses && accepts_trailers(&parts.headers);
+
+    let (body, bytes) = if needs_body(&parts) {
+        match Limited::new(body, settings.payload_limit).collect().await {
+            Ok(collected) => {
+                let bytes = collected.to_bytes();
+                (Either::Left(Full::new(bytes.clone())), Some(bytes))
+            }
+            Err(e) if e.is::<LengthLimitError>() => {
+                return Ok(status_response(StatusCode::PAYLOAD_TOO_LARGE))
+            }
+            Err(_) => return Ok(status_response(StatusCode::BAD_REQUEST)),
+        }
+    } else {
+        (Either::Right(body), None)
+    };
+
+    let verified = match authenticate(&parts, bytes.as_deref(), &*settings.keys, &settings.config) {
+        Ok(verified) => verified,
+        Err(rejection) => {
+            let message = Bytes::from(rejection.failure().to_string());
+            return Ok(rejection.response(Either::Left(Full::new(message))));
+        }
+    };
+
+    let mut req = Request::from_parts(parts, body);
+    req.extensions_mut().insert(verified.client().clone());
+    let res = call(req).await?;
+    if trailers && !verified.client().bewit {
+        return Ok(sign_trailer(res, verified));
+    }
+    Ok(sign_response(res, &verified, settings.hash_responses).await)
+}
+
+/// Check whether the request's `TE` header allows trailers in the response.
+fn accepts_trailers(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(TE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|v| v.trim().eq_ignore_ascii_case("trailers"))
+}
+
+/// Prepare the given response to carry `Server-Authorization` as a trailer, hashing its body as
+/// it is sent.
+fn sign_trailer<B: Body>(
+    res: Response<B>,
+    verified: Verified,
+) -> Response<HawkBody<SignedBody<B>>> {
+    let (mut parts, body) = res.into_parts();
+    let content_type = parts
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    let body = SignedBody::new(body, verified, content_type);
+
+    // trailers require chunked encoding, and hyper only sends those declared in `Trailer`
+    parts.headers.remove(CONTENT_LENGTH);
+    parts
+        .headers
+        .append(TRAILER, HeaderValue::from_static(SERVER_AUTHORIZATION));
+    Response::from_parts(parts, Either::Right(body))
+}
+
+/// Add `Server-Authorization` to the given response, buffering its body if it is to be hashed.
+async fn sign_response<B: Body>(
+    res: Response<B>,
+    verified: &Verified,
+    hash_responses: bool,
+) -> Response<HawkBody<SignedBody<B>>> {
+    let (parts, body) = res.into_parts();
+    let (body, header) = if hash_responses && !verified.client().bewit {
+        let bytes = match body.collect().await {
+            Ok(collected) => collected.to_bytes(),
+            Err(_) => return status_response(StatusCode::INTERNAL_SERVER_ERROR),
+        };
+        let content_type = parts
+            .headers
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("");
+        let header = verified.server_authorization(Some((content_type, &bytes[..])));
+        (Either::Left(Full::new(bytes)), header)
+    } else {
+        (
+            Either::Right(SignedBody::unsigned(body)),
+            verified.server_authorization(None),
+        )
+    };
+
+    let mut res = Response::from_parts(parts, body);
+    match header {
+        Ok(Some(header)) => {
+            if hawk::http::set_server_authorization(&mut res, &header).is_err() {
+                return status_response(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        }
+        Ok(None) => {}
+        Err(_) => return status_response(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+    res
+}
+
+fn status_response<B>(status: StatusCode) -> Response<HawkBody<B>> {
+    let mut res = Response::new(Either::Left(Full::new(Bytes::new())));
+    *res.status_mut() = status;
+    res
+}
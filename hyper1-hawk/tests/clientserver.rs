@@ -0,0 +1,273 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use hawk::{Credentials, Key, RequestBuilder, SHA256};
+use http::{Request, Response, StatusCode};
+use http_body_util::{BodyExt, Full};
+use hyper::body::Incoming;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper1_hawk::{Authenticated, Client, Error, HawkBody, HawkService, ServerConfig};
+use hyper_util::client::legacy;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use tokio::net::TcpListener;
+
+fn credentials(key: u8) -> Credentials {
+    Credentials {
+        id: "test-client".to_string(),
+        key: Key::new(vec![key; 32], &SHA256),
+    }
+}
+
+/// Answer each request with the authenticated id and the request body.
+async fn echo(req: Request<HawkBody<Incoming>>) -> Result<Response<Full<Bytes>>, Infallible> {
+    let id = req.extensions().get::<Authenticated>().unwrap().id.clone();
+    let body = req.into_body().collect().await.unwrap().to_bytes();
+    let mut res = Response::new(Full::new(Bytes::from(format!(
+        "{}: {}",
+        id,
+        String::from_utf8_lossy(&body)
+    ))));
+    res.headers_mut()
+        .insert("content-type", "text/plain".parse().unwrap());
+    Ok(res)
+}
+
+/// Serve the given service on a local port, returning the port.
+async fn serve<S, B>(service: S) -> u16
+where
+    S: hyper::service::Service<Request<Incoming>, Response = Response<B>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    B: hyper::body::Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let service = service.clone();
+            tokio::spawn(async move {
+                let _ = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    });
+    port
+}
+
+async fn hawk_server(hash_responses: bool) -> u16 {
+    hawk_server_with(hash_responses, false).await
+}
+
+async fn hawk_server_with(hash_responses: bool, trailer_responses: bool) -> u16 {
+    let key = Arc::new(credentials(1).key);
+    let service = HawkService::new(
+        service_fn(echo),
+        move |id: &str| {
+            if id == "test-client" {
+                Some(key.clone())
+            } else {
+                None
+            }
+        },
+        ServerConfig::new(80),
+    )
+    .hash_responses(hash_responses)
+    .trailer_responses(trailer_responses);
+    serve(service).await
+}
+
+fn plain_client() -> legacy::Client<legacy::connect::HttpConnector, Full<Bytes>> {
+    legacy::Client::builder(TokioExecutor::new()).build_http()
+}
+
+#[tokio::test]
+async fn signed_get() {
+    let port = hawk_server(false).await;
+    let client = Client::new(credentials(1));
+    let req = Request::get(format!("http://127.0.0.1:{}/resource?x=1", port))
+        .body(Bytes::new())
+        .unwrap();
+    let res = client.request(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res.headers().contains_key("server-authorization"));
+    assert_eq!(res.body(), &Bytes::from("test-client: "));
+}
+
+#[tokio::test]
+async fn signed_post_with_hashes() {
+    let port = hawk_server(true).await;
+    let client = Client::new(credentials(1)).hash_payloads(true);
+    let req = Request::post(format!("http://127.0.0.1:{}/resource", port))
+        .header("content-type", "text/plain; charset=utf-8")
+        .body(Bytes::from("foo=bar"))
+        .unwrap();
+    let res = client.request(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let server_header = hawk::http::server_authorization(res.headers())
+        .unwrap()
+        .unwrap();
+    assert!(server_header.hash.is_some());
+    assert_eq!(res.body(), &Bytes::from("test-client: foo=bar"));
+}
+
+#[tokio::test]
+async fn unauthenticated() {
+    let port = hawk_server(false).await;
+    let req = Request::get(format!("http://127.0.0.1:{}/resource", port))
+        .body(Full::new(Bytes::new()))
+        .unwrap();
+    let res = plain_client().request(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(res.headers()["www-authenticate"], "Hawk");
+}
+
+#[tokio::test]
+async fn wrong_key() {
+    let port = hawk_server(false).await;
+    let client = Client::new(credentials(2)).accept_challenges(true);
+    let req = Request::get(format!("http://127.0.0.1:{}/resource", port))
+        .body(Bytes::new())
+        .unwrap();
+    let res = client.request(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert!(!res.headers().contains_key("server-authorization"));
+}
+
+#[tokio::test]
+async fn wrong_key_challenge_not_accepted() {
+    let port = hawk_server(false).await;
+    let client = Client::new(credentials(2));
+    let req = Request::get(format!("http://127.0.0.1:{}/resource", port))
+        .body(Bytes::new())
+        .unwrap();
+    match client.request(req).await {
+        Err(Error::MissingServerAuthorization(StatusCode::UNAUTHORIZED)) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+}
+
+#[tokio::test]
+async fn bewit_get() {
+    let port = hawk_server(false).await;
+    let request = RequestBuilder::new("GET", "127.0.0.1", port, "/resource").request();
+    let bewit = request
+        .make_bewit(&credentials(1), time::Duration::minutes(1))
+        .unwrap();
+    let req = Request::get(format!(
+        "http://127.0.0.1:{}/resource?bewit={}",
+        port,
+        bewit.to_str()
+    ))
+    .body(Full::new(Bytes::new()))
+    .unwrap();
+    let res = plain_client().request(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(!res.headers().contains_key("server-authorization"));
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(body, Bytes::from("test-client: "));
+}
+
+#[tokio::test]
+async fn bogus_server_authorization() {
+    let port = serve(service_fn(|_req: Request<Incoming>| async {
+        let mut res: Response<HawkBody<Full<Bytes>>> =
+            Response::new(http_body_util::Either::Right(Full::new(Bytes::from("OK"))));
+        res.headers_mut().insert(
+            "server-authorization",
+            "Hawk mac=\"AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=\""
+                .parse()
+                .unwrap(),
+        );
+        Ok::<_, Infallible>(res)
+    }))
+    .await;
+    let client = Client::new(credentials(1));
+    let req = Request::get(format!("http://127.0.0.1:{}/resource", port))
+        .body(Bytes::new())
+        .unwrap();
+    match client.request(req).await {
+        Err(Error::InvalidServerAuthorization) => {}
+        r => panic!("unexpected result {:?}", r.map(|r| r.status())),
+    }
+}
+
+#[tokio::test]
+async fn trailer_response() {
+    let port = hawk_server_with(false, true).await;
+    let client = Client::new(credentials(1)).accept_trailers(true);
+    let req = Request::get(format!("http://127.0.0.1:{}/resource", port))
+        .body(Bytes::new())
+        .unwrap();
+    let res = client.request(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()["trailer"], "server-authorization");
+    let server_header = hawk::http::server_authorization(res.headers())
+        .unwrap()
+        .unwrap();
+    assert!(server_header.hash.is_some());
+    assert_eq!(res.body(), &Bytes::from("test-client: "));
+}
+
+#[tokio::test]
+async fn trailer_response_sent_as_trailer() {
+    let port = hawk_server_with(false, true).await;
+    let url = format!("http://127.0.0.1:{}/resource", port);
+    let request = RequestBuilder::new("GET", "127.0.0.1", port, "/resource").request();
+    let header = request.make_header(&credentials(1)).unwrap();
+    let mut req = Request::get(&url)
+        .header("te", "trailers")
+        .body(Full::new(Bytes::new()))
+        .unwrap();
+    hawk::http::set_authorization(&mut req, &header).unwrap();
+
+    let res = plain_client().request(req).await.unwrap();
+    assert!(!res.headers().contains_key("server-authorization"));
+    assert!(!res.headers().contains_key("content-length"));
+    let content_type = res.headers()["content-type"].to_str().unwrap().to_string();
+    let collected = res.into_body().collect().await.unwrap();
+    let server_header = hawk::http::server_authorization(collected.trailers().unwrap())
+        .unwrap()
+        .unwrap();
+    let body = collected.to_bytes();
+    let response = request.make_response_builder(&header).response();
+    assert!(response.validate_header_with_payload(
+        &server_header,
+        &credentials(1).key,
+        &content_type,
+        &body[..]
+    ));
+}
+
+#[tokio::test]
+async fn trailer_response_not_accepted() {
+    // without `TE: trailers`, the response is signed with a header, as usual
+    let port = hawk_server_with(false, true).await;
+    let client = Client::new(credentials(1));
+    let req = Request::get(format!("http://127.0.0.1:{}/resource", port))
+        .body(Bytes::new())
+        .unwrap();
+    let res = client.request(req).await.unwrap();
+    assert!(!res.headers().contains_key("trailer"));
+    let server_header = hawk::http::server_authorization(res.headers())
+        .unwrap()
+        .unwrap();
+    assert!(server_header.hash.is_none());
+}
+
+#[tokio::test]
+async fn trailers_requested_from_header_server() {
+    let port = hawk_server(true).await;
+    let client = Client::new(credentials(1)).accept_trailers(true);
+    let req = Request::get(format!("http://127.0.0.1:{}/resource", port))
+        .body(Bytes::new())
+        .unwrap();
+    let res = client.request(req).await.unwrap();
+    assert!(!res.headers().contains_key("trailer"));
+    assert!(res.headers().contains_key("server-authorization"));
+}
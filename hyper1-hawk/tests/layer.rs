@@ -0,0 +1,154 @@
+#![cfg(feature = "tower")]
+
+use std::convert::Infallible;
+use std::future::{poll_fn, ready, Ready};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use hawk::{Credentials, Key, PayloadHasher, RequestBuilder, SHA256};
+use http::{Request, Response, StatusCode};
+use http_body_util::{BodyExt, Full};
+use hyper1_hawk::{Authenticated, HawkBody, HawkLayer, ServerConfig};
+use tower_layer::Layer;
+use tower_service::Service;
+
+fn credentials() -> Credentials {
+    Credentials {
+        id: "test-client".to_string(),
+        key: Key::new(vec![1u8; 32], &SHA256),
+    }
+}
+
+fn layer() -> HawkLayer {
+    let key = Arc::new(credentials().key);
+    HawkLayer::new(
+        move |id: &str| {
+            if id == "test-client" {
+                Some(key.clone())
+            } else {
+                None
+            }
+        },
+        ServerConfig::new(80),
+    )
+}
+
+/// A tower service answering each request with the authenticated client's id and ext.
+#[derive(Clone)]
+struct Echo;
+
+impl Service<Request<HawkBody<Full<Bytes>>>> for Echo {
+    type Response = Response<Full<Bytes>>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::Response, Infallible>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<HawkBody<Full<Bytes>>>) -> Self::Future {
+        let client = req.extensions().get::<Authenticated>().unwrap();
+        let body = format!("{} {}", client.id, client.ext.as_deref().unwrap_or("-"));
+        ready(Ok(Response::new(Full::new(Bytes::from(body)))))
+    }
+}
+
+async fn call<S>(service: &mut S, req: Request<Full<Bytes>>) -> S::Response
+where
+    S: Service<Request<Full<Bytes>>>,
+    S::Error: std::fmt::Debug,
+{
+    poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+    service.call(req).await.unwrap()
+}
+
+fn signed_request(
+    ext: Option<&str>,
+) -> (Request<Full<Bytes>>, hawk::Request<'static>, hawk::Header) {
+    let request = RequestBuilder::new("GET", "example.com", 80, "/resource")
+        .ext(ext)
+        .request()
+        .into_owned();
+    let header = request.make_header(&credentials()).unwrap();
+    let mut req = Request::get("/resource")
+        .header("host", "example.com")
+        .body(Full::new(Bytes::new()))
+        .unwrap();
+    hawk::http::set_authorization(&mut req, &header).unwrap();
+    (req, request, header)
+}
+
+#[tokio::test]
+async fn authenticated() {
+    let mut service = layer().layer(Echo);
+    let (req, request, header) = signed_request(Some("data"));
+    let res = call(&mut service, req).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let server_header = hawk::http::server_authorization(res.headers())
+        .unwrap()
+        .unwrap();
+    assert!(server_header.hash.is_none());
+    let response = request.make_response_builder(&header).response();
+    assert!(response.validate_header(&server_header, &credentials().key));
+
+    let body = res.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(body, Bytes::from("test-client data"));
+}
+
+#[tokio::test]
+async fn hash_responses() {
+    let mut service = layer().hash_responses(true).layer(Echo);
+    let (req, request, header) = signed_request(None);
+    let res = call(&mut service, req).await;
+    let (parts, body) = res.into_parts();
+    let body = body.collect().await.unwrap().to_bytes();
+    assert_eq!(body, Bytes::from("test-client -"));
+
+    let server_header = hawk::http::server_authorization(&parts.headers)
+        .unwrap()
+        .unwrap();
+    let response = request.make_response_builder(&header).response();
+    assert!(response.validate_header_with_payload(&server_header, &credentials().key, "", &body));
+}
+
+#[tokio::test]
+async fn unauthenticated() {
+    let mut service = layer().layer(Echo);
+    let req = Request::get("/resource")
+        .header("host", "example.com")
+        .body(Full::new(Bytes::new()))
+        .unwrap();
+    let res = call(&mut service, req).await;
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(res.headers()["www-authenticate"], "Hawk");
+    assert!(!res.headers().contains_key("server-authorization"));
+}
+
+#[tokio::test]
+async fn payload_too_large() {
+    let hash = PayloadHasher::hash("text/plain", &SHA256, "foo=bar");
+    let header = RequestBuilder::new("POST", "example.com", 80, "/resource")
+        .hash(&hash[..])
+        .request()
+        .make_header(&credentials())
+        .unwrap();
+    let request = || {
+        let mut req = Request::post("/resource")
+            .header("host", "example.com")
+            .header("content-type", "text/plain")
+            .body(Full::new(Bytes::from("foo=bar")))
+            .unwrap();
+        hawk::http::set_authorization(&mut req, &header).unwrap();
+        req
+    };
+
+    let mut service = layer().payload_limit(4).layer(Echo);
+    let res = call(&mut service, request()).await;
+    assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let mut service = layer().payload_limit(7).layer(Echo);
+    let res = call(&mut service, request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+}
@@ -0,0 +1,217 @@
+//! Hawk authentication for reqwest.
+//!
+//! `Client` wraps a `reqwest::Client`, signing each request with Hawk credentials and, unless
+//! disabled, verifying the `Server-Authorization` header of each response.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use hawk::{Credentials, Key, SHA256};
+//!
+//! # async fn example() -> Result<(), reqwest_hawk::Error> {
+//! let client = reqwest_hawk::Client::new(Credentials {
+//!     id: "me".to_string(),
+//!     key: Key::new(vec![1u8; 32], &SHA256),
+//! });
+//! let req = client
+//!     .inner()
+//!     .post("https://example.com/resource?x=1")
+//!     .header("content-type", "application/json")
+//!     .body("{}")
+//!     .build()?;
+//! let res = client.execute(req).await?;
+//! # let _ = res;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::error;
+use std::fmt;
+use std::sync::Arc;
+
+use hawk::{Credentials, PayloadHasher, RequestBuilder};
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use reqwest::{Request, Response, StatusCode};
+
+/// An error from a Hawk-authenticated request.
+#[derive(Debug)]
+pub enum Error {
+    /// An error generating or parsing a Hawk header
+    Hawk(hawk::Error),
+    /// An error making the HTTP request or reading the response
+    Http(reqwest::Error),
+    /// The request's URL could not be read for signing
+    InvalidUrl(String),
+    /// The response had no `Server-Authorization` header, but verification is enabled.  This
+    /// includes responses rejecting the request, such as 401s.
+    MissingServerAuthorization(StatusCode),
+    /// The response's `Server-Authorization` header did not validate, so the response may not
+    /// have come from the server, or may have been modified
+    InvalidServerAuthorization,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Hawk(ref e) => write!(f, "Hawk error: {}", e),
+            Error::Http(ref e) => write!(f, "HTTP error: {}", e),
+            Error::InvalidUrl(ref url) => write!(f, "Cannot sign URL {}", url),
+            Error::MissingServerAuthorization(status) => {
+                write!(
+                    f,
+                    "Response with status {} has no Server-Authorization header",
+                    status
+                )
+            }
+            Error::InvalidServerAuthorization => write!(f, "Invalid Server-Authorization header"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Hawk(ref e) => Some(e),
+            Error::Http(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<hawk::Error> for Error {
+    fn from(e: hawk::Error) -> Self {
+        Error::Hawk(e)
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Http(e)
+    }
+}
+
+/// A reqwest client wrapper that signs each request with Hawk credentials.
+///
+/// Each request is signed over its full URL, including the query.  Buffered request bodies are
+/// hashed along with their `Content-Type`; streaming bodies are sent without a hash.
+///
+/// With verification enabled (the default), each response must carry a valid
+/// `Server-Authorization` header.  If that header includes a payload hash, the response body is
+/// buffered and checked against it before the response is returned.  Such a response is
+/// rebuilt from the buffered body, so its `url()` is not available.
+#[derive(Clone)]
+pub struct Client {
+    client: reqwest::Client,
+    credentials: Arc<Credentials>,
+    hash_payloads: bool,
+    verify_responses: bool,
+}
+
+impl Client {
+    /// Create a new client with the given credentials, using a default reqwest client.
+    pub fn new(credentials: Credentials) -> Self {
+        Client::with_client(reqwest::Client::new(), credentials)
+    }
+
+    /// Create a new client with the given credentials, sending requests with the given
+    /// reqwest client.
+    pub fn with_client(client: reqwest::Client, credentials: Credentials) -> Self {
+        Client {
+            client,
+            credentials: Arc::new(credentials),
+            hash_payloads: true,
+            verify_responses: true,
+        }
+    }
+
+    /// Set whether to include a hash of buffered request bodies in the `Authorization` header.
+    pub fn hash_payloads(mut self, hash_payloads: bool) -> Self {
+        self.hash_payloads = hash_payloads;
+        self
+    }
+
+    /// Set whether to require and verify a `Server-Authorization` header on each response.
+    pub fn verify_responses(mut self, verify_responses: bool) -> Self {
+        self.verify_responses = verify_responses;
+        self
+    }
+
+    /// Get the wrapped reqwest client, for building requests to pass to `execute`.
+    pub fn inner(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    /// Get the credentials used by this client.
+    pub fn credentials(&self) -> &Credentials {
+        &self.credentials
+    }
+
+    /// Sign and send the given request, verifying the response if enabled.
+    pub async fn execute(&self, mut req: Request) -> Result<Response, Error> {
+        // reqwest's URL type may be from another version of the `url` crate than hawk's
+        let url = hawk::Url::parse(req.url().as_str())
+            .map_err(|_| Error::InvalidUrl(req.url().to_string()))?;
+        let hash = match req.body().and_then(|body| body.as_bytes()) {
+            Some(body) if self.hash_payloads => Some(PayloadHasher::hash(
+                hawk::content_type(content_type_header(req.headers())),
+                self.credentials.key.algorithm(),
+                body,
+            )),
+            _ => None,
+        };
+        let request = RequestBuilder::from_url(req.method().to_string(), &url)?
+            .hash(hash)
+            .request();
+        let header = request.make_header(&self.credentials)?;
+        req.headers_mut()
+            .insert(AUTHORIZATION, hawk::http::header_value(&header)?);
+
+        let res = self.client.execute(req).await?;
+        if !self.verify_responses {
+            return Ok(res);
+        }
+
+        let server_header = match hawk::http::server_authorization(res.headers())? {
+            Some(server_header) => server_header,
+            None => return Err(Error::MissingServerAuthorization(res.status())),
+        };
+        let response = request.make_response_builder(&header).response();
+        if server_header.hash.is_none() {
+            if !response.validate_header(&server_header, &self.credentials.key) {
+                return Err(Error::InvalidServerAuthorization);
+            }
+            return Ok(res);
+        }
+
+        let (res, body) = buffer(res).await?;
+        let valid = response.validate_header_with_payload(
+            &server_header,
+            &self.credentials.key,
+            content_type_header(res.headers()),
+            &body[..],
+        );
+        if !valid {
+            return Err(Error::InvalidServerAuthorization);
+        }
+        Ok(Response::from(res.map(|()| body)))
+    }
+}
+
+/// Read the body of the given response, returning its head and the body.
+async fn buffer(res: Response) -> Result<(http::Response<()>, bytes::Bytes), Error> {
+    let mut head = http::Response::builder()
+        .status(res.status())
+        .version(res.version())
+        .body(())
+        .expect("status and version are valid");
+    *head.headers_mut() = res.headers().clone();
+    let body = res.bytes().await?;
+    Ok((head, body))
+}
+
+fn content_type_header(headers: &reqwest::header::HeaderMap) -> &str {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+}
@@ -0,0 +1,151 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use hawk::{Credentials, Key, SHA256};
+use http::{Request, Response, StatusCode};
+use http_body_util::{BodyExt, Full};
+use hyper::body::Incoming;
+use hyper::server::conn::http1;
+use hyper::service::{service_fn, Service};
+use hyper1_hawk::{Authenticated, HawkBody, HawkService, ServerConfig};
+use hyper_util::rt::TokioIo;
+use reqwest_hawk::{Client, Error};
+use tokio::net::TcpListener;
+
+fn credentials() -> Credentials {
+    Credentials {
+        id: "test-client".to_string(),
+        key: Key::new(vec![1u8; 32], &SHA256),
+    }
+}
+
+/// Answer each request with the authenticated id, the URI and the request body.
+async fn echo(req: Request<HawkBody<Incoming>>) -> Result<Response<Full<Bytes>>, Infallible> {
+    let id = req.extensions().get::<Authenticated>().unwrap().id.clone();
+    let uri = req.uri().to_string();
+    let body = req.into_body().collect().await.unwrap().to_bytes();
+    let body = format!("{} {}: {}", id, uri, String::from_utf8_lossy(&body));
+    Ok(Response::new(Full::new(Bytes::from(body))))
+}
+
+/// Serve the given service on a local port, returning the port.
+async fn serve<S, B>(service: S) -> u16
+where
+    S: Service<Request<Incoming>, Response = Response<B>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    B: hyper::body::Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let conn =
+                http1::Builder::new().serve_connection(TokioIo::new(stream), service.clone());
+            tokio::spawn(conn);
+        }
+    });
+    port
+}
+
+/// Serve `echo` with Hawk authentication on a local port, returning the port.
+async fn hawk_server(hash_responses: bool) -> u16 {
+    let key = Arc::new(credentials().key);
+    let keys = move |id: &str| (id == "test-client").then(|| key.clone());
+    serve(
+        HawkService::new(service_fn(echo), keys, ServerConfig::new(80))
+            .hash_responses(hash_responses),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn get_with_query() {
+    let port = hawk_server(false).await;
+    let client = Client::new(credentials());
+    let req = client
+        .inner()
+        .get(format!("http://127.0.0.1:{}/resource?x=1&y=2", port))
+        .build()
+        .unwrap();
+    let res = client.execute(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.text().await.unwrap(), "test-client /resource?x=1&y=2: ");
+}
+
+#[tokio::test]
+async fn post_with_hashes() {
+    let port = hawk_server(true).await;
+    let client = Client::new(credentials());
+    let req = client
+        .inner()
+        .post(format!("http://127.0.0.1:{}/resource", port))
+        .header("content-type", "text/plain; charset=utf-8")
+        .body("foo=bar")
+        .build()
+        .unwrap();
+    let res = client.execute(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    let server_header = hawk::http::server_authorization(res.headers())
+        .unwrap()
+        .unwrap();
+    assert!(server_header.hash.is_some());
+    assert_eq!(res.text().await.unwrap(), "test-client /resource: foo=bar");
+}
+
+#[tokio::test]
+async fn rejected() {
+    let port = hawk_server(false).await;
+    let client = Client::new(Credentials {
+        key: Key::new(vec![2u8; 32], &SHA256),
+        ..credentials()
+    });
+    let req = client
+        .inner()
+        .get(format!("http://127.0.0.1:{}/resource", port))
+        .build()
+        .unwrap();
+    match client.execute(req).await {
+        Err(Error::MissingServerAuthorization(StatusCode::UNAUTHORIZED)) => {}
+        r => panic!("unexpected result {:?}", r.map(|r| r.status())),
+    }
+
+    // without verification, the 401 is returned
+    let client = client.verify_responses(false);
+    let req = client
+        .inner()
+        .get(format!("http://127.0.0.1:{}/resource", port))
+        .build()
+        .unwrap();
+    let res = client.execute(req).await.unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn bogus_server_authorization() {
+    let port = serve(service_fn(|_req: Request<Incoming>| async {
+        let mut res = Response::new(Full::new(Bytes::from("OK")));
+        res.headers_mut().insert(
+            "server-authorization",
+            "Hawk mac=\"AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=\", hash=\"AAAA\""
+                .parse()
+                .unwrap(),
+        );
+        Ok::<_, Infallible>(res)
+    }))
+    .await;
+    let client = Client::new(credentials());
+    let req = client
+        .inner()
+        .get(format!("http://127.0.0.1:{}/resource", port))
+        .build()
+        .unwrap();
+    match client.execute(req).await {
+        Err(Error::InvalidServerAuthorization) => {}
+        r => panic!("unexpected result {:?}", r.map(|r| r.status())),
+    }
+}
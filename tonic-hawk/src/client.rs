@@ -0,0 +1,289 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use hawk::{Credentials, Header, PayloadHasher, RequestBuilder, Response};
+use http::header::{HeaderValue, AUTHORIZATION, WWW_AUTHENTICATE};
+use http_body::{Frame, SizeHint};
+use http_body_util::{BodyExt, Full};
+use tonic::body::Body;
+use tonic::{Code, Status};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{take_ready, CONTENT_TYPE};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A tower layer signing gRPC calls with Hawk, for use around a tonic `Channel`.
+///
+/// Each request body is buffered to hash it, so streaming calls are only sent once the client
+/// finishes its stream.  Use `hash_messages(false)` for channels carrying long-lived client
+/// streams.
+///
+/// Response messages are hashed as they arrive.  The response must carry `server-authorization`
+/// metadata, in its trailers or in a trailers-only response, that validates against the call
+/// and the hash of those messages; otherwise the call fails with status `UNAUTHENTICATED`.  The
+/// exception is a server's challenge, a trailers-only `UNAUTHENTICATED` response with a
+/// `www-authenticate` header, which cannot be signed and is passed on as it is.
+#[derive(Clone)]
+pub struct HawkClientLayer {
+    settings: Settings,
+}
+
+#[derive(Clone)]
+struct Settings {
+    credentials: Arc<Credentials>,
+    host: String,
+    port: u16,
+    hash_messages: bool,
+}
+
+impl HawkClientLayer {
+    /// Create a new layer signing calls with the given credentials, for an endpoint at the given
+    /// host and port.
+    pub fn new<H: Into<String>>(credentials: Credentials, host: H, port: u16) -> Self {
+        HawkClientLayer {
+            settings: Settings {
+                credentials: Arc::new(credentials),
+                host: host.into(),
+                port,
+                hash_messages: true,
+            },
+        }
+    }
+
+    /// Set whether to include a hash of the request messages in the `authorization` metadata.
+    /// This is enabled by default.
+    pub fn hash_messages(mut self, hash_messages: bool) -> Self {
+        self.settings.hash_messages = hash_messages;
+        self
+    }
+}
+
+impl<S> Layer<S> for HawkClientLayer {
+    type Service = HawkClient<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HawkClient {
+            inner,
+            settings: self.settings.clone(),
+        }
+    }
+}
+
+/// The service created by `HawkClientLayer`.
+#[derive(Clone)]
+pub struct HawkClient<S> {
+    inner: S,
+    settings: Settings,
+}
+
+impl<S, ResB> Service<http::Request<Body>> for HawkClient<S>
+where
+    S: Service<http::Request<Body>, Response = http::Response<ResB>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<BoxError>,
+    ResB: http_body::Body<Data = Bytes> + Send + 'static,
+    ResB::Error: Into<BoxError>,
+{
+    type Response = http::Response<Body>;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, req: http::Request<Body>) -> Self::Future {
+        let mut inner = take_ready(&mut self.inner);
+        let settings = self.settings.clone();
+
+        Box::pin(async move {
+            let (mut parts, body) = req.into_parts();
+            let (body, hash) = if settings.hash_messages {
+                let bytes = body.collect().await?.to_bytes();
+                let hash = PayloadHasher::hash(
+                    CONTENT_TYPE,
+                    settings.credentials.key.algorithm(),
+                    &bytes[..],
+                );
+                (Body::new(Full::new(bytes)), Some(hash))
+            } else {
+                (body, None)
+            };
+
+            let request = RequestBuilder::new(
+                parts.method.to_string(),
+                settings.host.clone(),
+                settings.port,
+                parts.uri.path().to_string(),
+            )
+            .hash(hash)
+            .request();
+            let header = request
+                .make_header(&settings.credentials)
+                .map_err(|e| Status::internal(e.to_string()))?;
+            let value = HeaderValue::from_str(&format!("Hawk {}", header))
+                .map_err(|_| Status::internal("invalid Hawk header"))?;
+            parts.headers.insert(AUTHORIZATION, value);
+
+            let res = inner
+                .call(http::Request::from_parts(parts, body))
+                .await
+                .map_err(Into::into)?;
+
+            let response = request
+                .make_response_builder(&header)
+                .response()
+                .into_owned();
+            let hasher = PayloadHasher::new(CONTENT_TYPE, settings.credentials.key.algorithm());
+            let (parts, body) = res.into_parts();
+
+            // a trailers-only response is signed in its headers, with no messages
+            if let Some(status) = Status::from_header_map(&parts.headers) {
+                match server_authorization(&parts.headers)? {
+                    Some(server_header) => {
+                        if !validate(&response, &server_header, &settings.credentials, hasher) {
+                            return Err(invalid_server_authorization().into());
+                        }
+                    }
+                    None if is_challenge(&status, &parts.headers) => {}
+                    None => return Err(missing_server_authorization(Some(&status)).into()),
+                }
+                let body = body.map_err(|e| Status::from_error(e.into()));
+                return Ok(http::Response::from_parts(parts, Body::new(body)));
+            }
+
+            let body = VerifiedBody {
+                inner: Box::pin(body),
+                verifier: Some((response, hasher)),
+                credentials: settings.credentials,
+            };
+            Ok(http::Response::from_parts(parts, Body::new(body)))
+        })
+    }
+}
+
+fn invalid_server_authorization() -> Status {
+    Status::unauthenticated("Invalid Server-Authorization")
+}
+
+/// The status for a response without `server-authorization`, including the status the response
+/// ended the call with, if any, since it cannot be trusted.
+fn missing_server_authorization(status: Option<&Status>) -> Status {
+    match status {
+        Some(status) => Status::unauthenticated(format!(
+            "Missing Server-Authorization, in a response with status {:?}: {}",
+            status.code(),
+            status.message()
+        )),
+        None => Status::unauthenticated("Missing Server-Authorization"),
+    }
+}
+
+/// Check whether a trailers-only response is a server's challenge, rejecting the call.
+fn is_challenge(status: &Status, headers: &http::HeaderMap) -> bool {
+    status.code() == Code::Unauthenticated && headers.contains_key(WWW_AUTHENTICATE)
+}
+
+/// Parse the `server-authorization` metadata in the given headers or trailers, if any.
+fn server_authorization(headers: &http::HeaderMap) -> Result<Option<Header>, Status> {
+    hawk::http::server_authorization(headers).map_err(|_| invalid_server_authorization())
+}
+
+/// Validate a `server-authorization` header against the response to a call, including its hash,
+/// if it has one, of the messages hashed with `hasher`.
+fn validate(
+    response: &Response<'_>,
+    server_header: &Header,
+    credentials: &Credentials,
+    hasher: PayloadHasher,
+) -> bool {
+    if !response.validate_header(server_header, &credentials.key) {
+        return false;
+    }
+    match server_header.hash {
+        Some(ref hash) => *hash == hasher.finish(),
+        None => true,
+    }
+}
+
+/// A response body hashing the messages as they arrive, and validating the
+/// `server-authorization` in the trailers that follow them.
+struct VerifiedBody<B> {
+    inner: Pin<Box<B>>,
+    // the expected response and the running hash of the messages, until the trailers arrive
+    verifier: Option<(Response<'static>, PayloadHasher)>,
+    credentials: Arc<Credentials>,
+}
+
+impl<B> VerifiedBody<B> {
+    /// Validate the `server-authorization` in the given trailers, finishing the hash.
+    fn verify(&mut self, trailers: &http::HeaderMap) -> Result<(), Status> {
+        if let Some((response, hasher)) = self.verifier.take() {
+            let server_header = server_authorization(trailers)?.ok_or_else(|| {
+                missing_server_authorization(Status::from_header_map(trailers).as_ref())
+            })?;
+            if !validate(&response, &server_header, &self.credentials, hasher) {
+                return Err(invalid_server_authorization());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<B> http_body::Body for VerifiedBody<B>
+where
+    B: http_body::Body<Data = Bytes>,
+    B::Error: Into<BoxError>,
+{
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        match this.inner.as_mut().poll_frame(cx) {
+            Poll::Ready(Some(Ok(frame))) => {
+                let frame = match frame.into_data() {
+                    Ok(data) => {
+                        if let Some((_, ref mut hasher)) = this.verifier {
+                            hasher.update(&data);
+                        }
+                        Frame::data(data)
+                    }
+                    Err(frame) => match frame.into_trailers() {
+                        Ok(trailers) => {
+                            if let Err(status) = this.verify(&trailers) {
+                                return Poll::Ready(Some(Err(status)));
+                            }
+                            Frame::trailers(trailers)
+                        }
+                        Err(frame) => frame,
+                    },
+                };
+                Poll::Ready(Some(Ok(frame)))
+            }
+            Poll::Ready(Some(Err(e))) => Poll::Ready(Some(Err(Status::from_error(e.into())))),
+            // the body ended without trailers, so without `server-authorization`
+            Poll::Ready(None) if this.verifier.take().is_some() => {
+                Poll::Ready(Some(Err(missing_server_authorization(None))))
+            }
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.verifier.is_none() && self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
@@ -0,0 +1,54 @@
+//! Hawk authentication for gRPC services built with tonic.
+//!
+//! `HawkClientLayer` wraps a tonic `Channel`, signing each call, and `HawkServerLayer` wraps a
+//! tonic server's routes, authenticating each call and adding the client's `Authenticated`
+//! identity to the request extensions, where handlers can find it with `client`.
+//!
+//! These are tower layers rather than tonic `Interceptor`s, because interceptors see neither the
+//! message nor, on the server, the method path, both of which Hawk signs.
+//!
+//! # Mapping
+//!
+//! A gRPC call is signed as a Hawk request with:
+//!
+//!  * method `POST`;
+//!  * the gRPC method path, such as `/grpc.health.v1.Health/Check`, as the resource;
+//!  * the host and port of the endpoint, as configured on the client and as seen in the
+//!    `:authority` of the call on the server; and
+//!  * a payload hash over the request body as sent, that is, the length-prefixed gRPC message
+//!    frames, with content type `application/grpc`.
+//!
+//! The Hawk header is sent in the `authorization` request metadata.  The server's reply is
+//! signed in the `server-authorization` response metadata, sent as a trailer with a payload
+//! hash over the response message frames, again with content type `application/grpc`.  A
+//! trailers-only response, such as an error ending a call before any messages, is signed in its
+//! headers instead, with the hash of an empty payload.  Calls that fail authentication end with
+//! status `UNAUTHENTICATED` (or `INVALID_ARGUMENT`, for malformed headers) and a
+//! `www-authenticate` challenge in the metadata.  The client fails any other call whose reply is
+//! not signed with status `UNAUTHENTICATED`.
+//!
+//! Bewits do not apply to gRPC, so `HawkServerLayer` disables them in its `ServerConfig`.
+//!
+//! Behind a reverse proxy, use `ServerConfig::trusted_proxies` to take the `:authority` from
+//! the `Forwarded` or `X-Forwarded-*` headers the proxy adds.  The peer's address must then be
+//! added to each call as a `PeerAddr` extension by a layer outside `HawkServerLayer`.
+
+use std::mem;
+
+mod client;
+mod server;
+
+pub use client::{HawkClient, HawkClientLayer};
+pub use hawk::http::server::{Authenticated, KeyLookup, PeerAddr, ServerConfig};
+pub use server::{client, HawkServer, HawkServerLayer, DEFAULT_PAYLOAD_LIMIT};
+
+/// The content type used to hash gRPC messages.
+pub const CONTENT_TYPE: &str = "application/grpc";
+
+/// Take the inner service for a call, leaving a clone in its place.  The service taken is the
+/// one `poll_ready` was called on, which tower requires of the service then called, while the
+/// clone must be polled ready again before the next call.
+fn take_ready<S: Clone>(service: &mut S) -> S {
+    let clone = service.clone();
+    mem::replace(service, clone)
+}
@@ -0,0 +1,175 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use hawk::http::server::{
+    authenticate, needs_body, Authenticated, KeyLookup, Rejection, ServerConfig, SignedBody,
+    Verified,
+};
+use hawk::http::set_server_authorization;
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+use tonic::body::Body;
+use tonic::Status;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{take_ready, CONTENT_TYPE};
+
+/// The header carrying a gRPC call's status, in the trailers or in a trailers-only response.
+const GRPC_STATUS: &str = "grpc-status";
+
+/// Get the client authenticated by `HawkServerLayer` for the given call.
+pub fn client<T>(request: &tonic::Request<T>) -> Option<&Authenticated> {
+    request.extensions().get::<Authenticated>()
+}
+
+/// The default limit, in bytes, on the size of request bodies buffered to verify a payload hash,
+/// matching tonic's default limit on the size of a decoded message.
+pub const DEFAULT_PAYLOAD_LIMIT: usize = 4 * 1024 * 1024;
+
+/// A tower layer authenticating gRPC calls with Hawk, for use with tonic's
+/// `Server::builder().layer(..)`.  Bewits do not apply to gRPC, so the layer disables them in
+/// the given `ServerConfig`.
+///
+/// Calls whose `authorization` metadata includes a payload hash are buffered to verify it;
+/// those larger than `payload_limit` end with status `RESOURCE_EXHAUSTED`.
+#[derive(Clone)]
+pub struct HawkServerLayer {
+    keys: Arc<dyn KeyLookup>,
+    config: ServerConfig,
+    payload_limit: usize,
+}
+
+impl HawkServerLayer {
+    /// Create a new layer, looking up client keys with `keys`.
+    pub fn new<K>(keys: K, config: ServerConfig) -> Self
+    where
+        K: KeyLookup + 'static,
+    {
+        HawkServerLayer {
+            keys: Arc::new(keys),
+            config: config.bewits(false),
+            payload_limit: DEFAULT_PAYLOAD_LIMIT,
+        }
+    }
+
+    /// Set the limit, in bytes, on the size of request bodies buffered to verify a payload hash.
+    /// This defaults to `DEFAULT_PAYLOAD_LIMIT`.
+    pub fn payload_limit(mut self, payload_limit: usize) -> Self {
+        self.payload_limit = payload_limit;
+        self
+    }
+}
+
+impl<S> Layer<S> for HawkServerLayer {
+    type Service = HawkServer<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HawkServer {
+            inner,
+            keys: self.keys.clone(),
+            config: self.config.clone(),
+            payload_limit: self.payload_limit,
+        }
+    }
+}
+
+/// The service created by `HawkServerLayer`.
+#[derive(Clone)]
+pub struct HawkServer<S> {
+    inner: S,
+    keys: Arc<dyn KeyLookup>,
+    config: ServerConfig,
+    payload_limit: usize,
+}
+
+impl<S> Service<http::Request<Body>> for HawkServer<S>
+where
+    S: Service<http::Request<Body>, Response = http::Response<Body>> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    type Response = http::Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<Body>) -> Self::Future {
+        let mut inner = take_ready(&mut self.inner);
+        let keys = self.keys.clone();
+        let config = self.config.clone();
+        let payload_limit = self.payload_limit;
+
+        Box::pin(async move {
+            let (parts, body) = req.into_parts();
+            let (body, bytes) = if needs_body(&parts) {
+                match Limited::new(body, payload_limit).collect().await {
+                    Ok(collected) => {
+                        let bytes = collected.to_bytes();
+                        (Body::new(Full::new(bytes.clone())), Some(bytes))
+                    }
+                    Err(e) if e.is::<LengthLimitError>() => {
+                        let status = Status::resource_exhausted("message too large to verify");
+                        return Ok(status.into_http());
+                    }
+                    Err(e) => return Ok(Status::from_error(e).into_http()),
+                }
+            } else {
+                (body, None)
+            };
+
+            let verified = match authenticate(&parts, bytes.as_deref(), &*keys, &config) {
+                Ok(verified) => verified,
+                Err(rejection) => return Ok(reject(rejection)),
+            };
+
+            let mut req = http::Request::from_parts(parts, body);
+            req.extensions_mut().insert(verified.client().clone());
+            let res = inner.call(req).await?;
+            Ok(sign(res, verified))
+        })
+    }
+}
+
+/// Sign the response to a call with `server-authorization`, including a hash of the response
+/// messages.
+///
+/// The signature is sent in the trailers, after the messages have been hashed.  A
+/// trailers-only response, which ends the call in its headers, has no messages, and is signed
+/// in its headers.
+fn sign(res: http::Response<Body>, verified: Verified) -> http::Response<Body> {
+    if !res.headers().contains_key(GRPC_STATUS) {
+        return res.map(|body| Body::new(SignedBody::new(body, verified, CONTENT_TYPE)));
+    }
+
+    let mut res = res;
+    let hash = verified.response_hasher(CONTENT_TYPE).finish();
+    let signed = verified
+        .server_authorization_with_hash(&hash)
+        .and_then(|header| match header {
+            Some(header) => set_server_authorization(&mut res, &header),
+            None => Ok(()),
+        });
+    match signed {
+        Ok(()) => res,
+        Err(e) => Status::internal(format!("Cannot sign response: {}", e)).into_http(),
+    }
+}
+
+/// Build a gRPC response ending the call with the rejection's failure.
+fn reject(rejection: Rejection) -> http::Response<Body> {
+    let failure = rejection.failure();
+    let status = if failure.status_code() == 401 {
+        Status::unauthenticated(failure.to_string())
+    } else {
+        Status::invalid_argument(failure.to_string())
+    };
+    let mut res = status.into_http::<Body>();
+    for (name, value) in rejection.response(()).headers() {
+        res.headers_mut().insert(name.clone(), value.clone());
+    }
+    res
+}
@@ -0,0 +1,233 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use hawk::{Credentials, Header, Key, SHA256};
+use http_body::Frame;
+use http_body_util::BodyExt;
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::body::Body;
+use tonic::transport::{Channel, Server};
+use tonic::{Code, Request, Response, Status};
+use tonic_hawk::{HawkClientLayer, HawkServerLayer, ServerConfig, DEFAULT_PAYLOAD_LIMIT};
+use tonic_health::pb::health_check_response::ServingStatus;
+use tonic_health::pb::health_client::HealthClient;
+use tonic_health::pb::health_server::{Health, HealthServer};
+use tonic_health::pb::{HealthCheckRequest, HealthCheckResponse};
+
+fn credentials(key: u8) -> Credentials {
+    Credentials {
+        id: "test-client".to_string(),
+        key: Key::new(vec![key; 32], &SHA256),
+    }
+}
+
+/// A health service reporting SERVING for the "service" named by the authenticated client id.
+struct IdHealth;
+
+#[tonic::async_trait]
+impl Health for IdHealth {
+    async fn check(
+        &self,
+        request: Request<HealthCheckRequest>,
+    ) -> Result<Response<HealthCheckResponse>, Status> {
+        let id = tonic_hawk::client(&request).map(|client| client.id.clone());
+        let status = if id.as_deref() == Some(&request.get_ref().service[..]) {
+            ServingStatus::Serving
+        } else {
+            ServingStatus::NotServing
+        };
+        Ok(Response::new(HealthCheckResponse {
+            status: status as i32,
+        }))
+    }
+
+    type WatchStream = tokio_stream::Empty<Result<HealthCheckResponse, Status>>;
+
+    async fn watch(
+        &self,
+        _request: Request<HealthCheckRequest>,
+    ) -> Result<Response<Self::WatchStream>, Status> {
+        Err(Status::unimplemented("watch"))
+    }
+}
+
+/// Start a Hawk-authenticated server on a local port, returning a channel connected to it and
+/// the port.
+async fn server() -> (Channel, u16) {
+    server_with(DEFAULT_PAYLOAD_LIMIT).await
+}
+
+/// Start a server as for `server`, with the given limit on the size of calls it verifies.
+async fn server_with(payload_limit: usize) -> (Channel, u16) {
+    let key = Arc::new(credentials(1).key);
+    let layer = HawkServerLayer::new(
+        move |id: &str| {
+            if id == "test-client" {
+                Some(key.clone())
+            } else {
+                None
+            }
+        },
+        ServerConfig::new(80),
+    )
+    .payload_limit(payload_limit);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(
+        Server::builder()
+            .layer(layer)
+            .add_service(HealthServer::new(IdHealth))
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+
+    let channel = Channel::from_shared(format!("http://127.0.0.1:{}", port))
+        .unwrap()
+        .connect()
+        .await
+        .unwrap();
+    (channel, port)
+}
+
+fn check_request() -> HealthCheckRequest {
+    HealthCheckRequest {
+        service: "test-client".to_string(),
+    }
+}
+
+#[tokio::test]
+async fn signed_call() {
+    let (channel, port) = server().await;
+    let channel = tower::ServiceBuilder::new()
+        .layer(HawkClientLayer::new(credentials(1), "127.0.0.1", port))
+        .service(channel);
+    let mut client = HealthClient::new(channel);
+    let res = client.check(check_request()).await.unwrap();
+    assert_eq!(res.get_ref().status, ServingStatus::Serving as i32);
+    let value = res.metadata().get("server-authorization").unwrap();
+    let header = Header::from_str(&value.to_str().unwrap()[5..]).unwrap();
+    assert!(header.hash.is_some());
+}
+
+#[tokio::test]
+async fn tampered_response() {
+    let (channel, port) = server().await;
+    // flip the response from SERVING to NOT_SERVING, after the server signed it
+    let tamper = |res: http::Response<Body>| {
+        res.map(|body| {
+            Body::new(body.map_frame(|frame| {
+                frame.map_data(|data| {
+                    let mut data = data.to_vec();
+                    if let Some(last) = data.last_mut() {
+                        *last ^= 3;
+                    }
+                    Bytes::from(data)
+                })
+            }))
+        })
+    };
+    let channel = tower::ServiceBuilder::new()
+        .layer(HawkClientLayer::new(credentials(1), "127.0.0.1", port))
+        .map_response(tamper)
+        .service(channel);
+    let mut client = HealthClient::new(channel);
+    let status = client.check(check_request()).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+    assert_eq!(status.message(), "Invalid Server-Authorization");
+}
+
+#[tokio::test]
+async fn unsigned_response() {
+    let (channel, port) = server().await;
+    // drop the server-authorization trailer the server sent
+    let strip = |res: http::Response<Body>| {
+        res.map(|body| {
+            Body::new(body.map_frame(|frame| match frame.into_trailers() {
+                Ok(mut trailers) => {
+                    trailers.remove("server-authorization");
+                    Frame::trailers(trailers)
+                }
+                Err(frame) => frame,
+            }))
+        })
+    };
+    let channel = tower::ServiceBuilder::new()
+        .layer(HawkClientLayer::new(credentials(1), "127.0.0.1", port))
+        .map_response(strip)
+        .service(channel);
+    let mut client = HealthClient::new(channel);
+    let status = client.check(check_request()).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+    assert!(status.message().starts_with("Missing Server-Authorization"));
+}
+
+#[tokio::test]
+async fn signed_call_without_hash() {
+    let (channel, port) = server().await;
+    let channel = tower::ServiceBuilder::new()
+        .layer(HawkClientLayer::new(credentials(1), "127.0.0.1", port).hash_messages(false))
+        .service(channel);
+    let mut client = HealthClient::new(channel);
+    let res = client.check(check_request()).await.unwrap();
+    assert_eq!(res.get_ref().status, ServingStatus::Serving as i32);
+}
+
+#[tokio::test]
+async fn wrong_port() {
+    let (channel, port) = server().await;
+    let channel = tower::ServiceBuilder::new()
+        .layer(HawkClientLayer::new(credentials(1), "127.0.0.1", port + 1))
+        .service(channel);
+    let mut client = HealthClient::new(channel);
+    let status = client.check(check_request()).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+    assert_eq!(status.message(), "Bad mac");
+}
+
+#[tokio::test]
+async fn wrong_key() {
+    let (channel, port) = server().await;
+    let channel = tower::ServiceBuilder::new()
+        .layer(HawkClientLayer::new(credentials(2), "127.0.0.1", port))
+        .service(channel);
+    let mut client = HealthClient::new(channel);
+    let status = client.check(check_request()).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn unsigned_call() {
+    let (channel, _) = server().await;
+    let mut client = HealthClient::new(channel);
+    let status = client.check(check_request()).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+    assert_eq!(status.metadata().get("www-authenticate").unwrap(), "Hawk");
+}
+
+#[tokio::test]
+async fn payload_too_large() {
+    let (channel, port) = server_with(4).await;
+    let channel = tower::ServiceBuilder::new()
+        .layer(HawkClientLayer::new(credentials(1), "127.0.0.1", port))
+        .service(channel);
+    let mut client = HealthClient::new(channel);
+    // the server rejects the call before authenticating it, so cannot sign the rejection
+    let status = client.check(check_request()).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+    assert!(status.message().contains("ResourceExhausted"));
+}
+
+#[tokio::test]
+async fn signed_error() {
+    let (channel, port) = server().await;
+    let channel = tower::ServiceBuilder::new()
+        .layer(HawkClientLayer::new(credentials(1), "127.0.0.1", port))
+        .service(channel);
+    let mut client = HealthClient::new(channel);
+    // a trailers-only response, signed in its headers
+    let status = client.watch(check_request()).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unimplemented);
+    assert!(status.metadata().contains_key("server-authorization"));
+}